@@ -0,0 +1,14 @@
+// the stable surface of the library. what `v1` exports only changes in
+// semver-compatible ways within a major version, while the other public
+// modules exist for the CLI and may change in any release. a breaking change
+// to this surface goes into a new `v2` next to `v1`, which stays until the
+// next major version.
+pub mod v1 {
+    pub use crate::reader::TModReader as Archive;
+    pub use crate::sink::{MemorySink, OutputSink};
+    pub use crate::ModFile as Entry;
+    pub use crate::ModHeader as Metadata;
+    pub use crate::TModError as Error;
+
+    pub type Result<T> = std::result::Result<T, Error>;
+}
@@ -0,0 +1,193 @@
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use log::{debug, trace};
+
+use crate::names::extension;
+use crate::TModError;
+
+// more than any sound effect or music track needs once it is in game
+pub const HIGH_BITRATE_KBPS: u32 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioIssue {
+    // PCM or float samples in a WAV container
+    UncompressedWav,
+    HighBitrate(u32),
+}
+
+impl AudioIssue {
+    pub fn message(&self) -> String {
+        match self {
+            AudioIssue::UncompressedWav => {
+                "WAV audio is stored uncompressed, encode it as OGG".to_string()
+            }
+            AudioIssue::HighBitrate(kbps) => format!(
+                "audio is encoded at {} kbps, over {} kbps",
+                kbps, HIGH_BITRATE_KBPS
+            ),
+        }
+    }
+}
+
+// what makes an audio entry bigger than it needs to be, if anything
+pub fn check(name: &str, data: &[u8]) -> Option<AudioIssue> {
+    let kbps = match extension(name).as_deref() {
+        Some("wav") => {
+            let (format, kbps) = wav_format(data)?;
+            // 1 is integer PCM, 3 is IEEE float
+            if format == 1 || format == 3 {
+                return Some(AudioIssue::UncompressedWav);
+            }
+            kbps
+        }
+        Some("ogg") => vorbis_bitrate(data)?,
+        Some("mp3") => mp3_bitrate(data)?,
+        _ => return None,
+    };
+    (kbps > HIGH_BITRATE_KBPS).then_some(AudioIssue::HighBitrate(kbps))
+}
+
+// the format tag and bitrate from the `fmt ` chunk of a RIFF WAVE file
+fn wav_format(data: &[u8]) -> Option<(u16, u32)> {
+    if data.get(0..4)? != b"RIFF" || data.get(8..12)? != b"WAVE" {
+        return None;
+    }
+    let mut at = 12;
+    while let Some(header) = data.get(at..at + 8) {
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        if &header[0..4] == b"fmt " {
+            let fmt = data.get(at + 8..at + 8 + 16)?;
+            let format = u16::from_le_bytes([fmt[0], fmt[1]]);
+            let byte_rate = u32::from_le_bytes([fmt[8], fmt[9], fmt[10], fmt[11]]);
+            return Some((format, byte_rate * 8 / 1000));
+        }
+        // chunks are padded to an even length
+        at += 8 + len + (len & 1);
+    }
+    None
+}
+
+// the nominal bitrate from the identification header at the start of an Ogg
+// Vorbis stream
+fn vorbis_bitrate(data: &[u8]) -> Option<u32> {
+    if data.get(0..4)? != b"OggS" {
+        return None;
+    }
+    let start = data
+        .windows(7)
+        .take(256)
+        .position(|window| window == b"\x01vorbis")?;
+    // version, channels and sample rate, then the maximum and nominal bitrate
+    let nominal = data.get(start + 7 + 4 + 1 + 4 + 4..start + 7 + 4 + 1 + 4 + 8)?;
+    let bps = i32::from_le_bytes([nominal[0], nominal[1], nominal[2], nominal[3]]);
+    (bps > 0).then_some(bps as u32 / 1000)
+}
+
+// the bitrate of the first MPEG-1 Layer III frame, after any ID3v2 tag
+fn mp3_bitrate(data: &[u8]) -> Option<u32> {
+    const KBPS: [u32; 16] = [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 0,
+    ];
+    let mut at = 0;
+    if data.get(0..3)? == b"ID3" {
+        let size = data.get(6..10)?;
+        // a syncsafe integer, 7 bits per byte
+        let size = size
+            .iter()
+            .fold(0usize, |size, &byte| (size << 7) | (byte & 0x7f) as usize);
+        at = 10 + size;
+    }
+    let header = data.get(at..at + 3)?;
+    // the frame sync, then MPEG-1 (0b11) and Layer III (0b01)
+    if header[0] != 0xff || header[1] & 0xfe != 0xfa {
+        return None;
+    }
+    let kbps = KBPS[(header[2] >> 4) as usize];
+    (kbps > 0).then_some(kbps)
+}
+
+// `--reencode-audio ogg:q<quality>`: Vorbis quality from -1 to 10, 5 is about
+// 160 kbps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reencode {
+    pub quality: i8,
+}
+
+impl Reencode {
+    pub fn parse(value: &str) -> Result<Self, TModError> {
+        let invalid = || TModError::InvalidNumber("--reencode-audio", value.to_string());
+        let quality = value
+            .strip_prefix("ogg:q")
+            .ok_or_else(invalid)?
+            .parse()
+            .map_err(|_| invalid())?;
+        if !(-1..=10).contains(&quality) {
+            return Err(invalid());
+        }
+        Ok(Self { quality })
+    }
+
+    // the entry encoded as Ogg Vorbis by `oggenc` or `ffmpeg`, whichever is
+    // installed
+    pub fn encode(&self, name: &str, data: &[u8]) -> Result<Vec<u8>, TModError> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let stem = std::env::temp_dir().join(format!(
+            "tmod-unpacker-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let input = stem.with_extension(extension(name).unwrap_or_default());
+        let output = stem.with_extension("ogg");
+        std::fs::write(&input, data)?;
+        let result = self.run_encoder(&input, &output);
+        let _ = std::fs::remove_file(&input);
+        let encoded = result.and_then(|_| std::fs::read(&output).map_err(|e| e.to_string()));
+        let _ = std::fs::remove_file(&output);
+        encoded.map_err(|reason| TModError::ReencodeFailed(name.to_string(), reason))
+    }
+
+    fn run_encoder(&self, input: &Path, output: &Path) -> Result<(), String> {
+        let quality = self.quality.to_string();
+        let mut oggenc = Command::new("oggenc");
+        oggenc
+            .args(["--quiet", "-q", &quality, "-o"])
+            .arg(output)
+            .arg(input);
+        let mut ffmpeg = Command::new("ffmpeg");
+        ffmpeg
+            .args(["-loglevel", "error", "-y", "-i"])
+            .arg(input)
+            .args(["-vn", "-c:a", "libvorbis", "-q:a", &quality])
+            .arg(output);
+
+        for mut command in [oggenc, ffmpeg] {
+            let program = command.get_program().to_string_lossy().into_owned();
+            trace!("running {:?}", command);
+            match command.stdin(Stdio::null()).output() {
+                Ok(result) if result.status.success() => return Ok(()),
+                Ok(result) => {
+                    return Err(format!(
+                        "{} failed: {}",
+                        program,
+                        String::from_utf8_lossy(&result.stderr).trim()
+                    ))
+                }
+                Err(e) => debug!("can't run {}: {}", program, e),
+            }
+        }
+        Err("neither oggenc nor ffmpeg is installed".to_string())
+    }
+}
+
+// where a re-encoded entry goes: tModLoader finds sounds by their name
+// without the extension, so only the extension changes
+pub fn ogg_name(name: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !ext.contains('/') => format!("{}.ogg", stem),
+        _ => format!("{}.ogg", name),
+    }
+}
@@ -0,0 +1,284 @@
+use binary_rw::BinaryReader;
+use log::{info, trace};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::names::{components, extension, is_absolute};
+use crate::output::{self, OutputFormat};
+#[cfg(feature = "rules")]
+use crate::rules::Rule;
+use crate::source;
+use crate::{read_entry_table, read_file_data, read_header, ModFile, TModError};
+
+// managed assemblies larger than this are unusual enough to point out
+const LARGE_DLL_THRESHOLD: usize = 32 * 1024 * 1024;
+
+const SCRIPT_EXTENSIONS: &[&str] = &[
+    "bat", "cmd", "ps1", "psm1", "sh", "bash", "vbs", "vbe", "js", "jse", "wsf", "py", "scr",
+];
+
+const NATIVE_EXTENSIONS: &[&str] = &["exe", "so", "dylib", "sys", "com"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum FindingKind {
+    NativeExecutable,
+    Script,
+    LargeAssembly,
+    HiddenName,
+    AbsolutePath,
+    ParentTraversal,
+    #[cfg(feature = "rules")]
+    Rule,
+}
+
+impl FindingKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            FindingKind::NativeExecutable => "native-executable",
+            FindingKind::Script => "script",
+            FindingKind::LargeAssembly => "large-assembly",
+            FindingKind::HiddenName => "hidden-name",
+            FindingKind::AbsolutePath => "absolute-path",
+            FindingKind::ParentTraversal => "parent-traversal",
+            #[cfg(feature = "rules")]
+            FindingKind::Rule => "rule",
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Finding {
+    pub entry: String,
+    pub kind: FindingKind,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct AuditReport {
+    pub mod_name: String,
+    pub mod_version: String,
+    pub fingerprint: String,
+    pub files: usize,
+    pub findings: Vec<Finding>,
+}
+
+#[derive(Default)]
+pub struct AuditOptions {
+    pub format: OutputFormat,
+    #[cfg(feature = "rules")]
+    pub rules: Vec<Rule>,
+}
+
+pub fn audit(path: String, options: &AuditOptions) -> Result<(), TModError> {
+    trace!("opening file: {}", path);
+    let mut stream = source::open(&path)?;
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+
+    let header = read_header(&mut reader, None)?;
+
+    let file_entries = read_entry_table(&mut reader)?;
+
+    info!("Auditing file entries");
+    let mut findings = Vec::new();
+    for file in &file_entries {
+        let data = read_file_data(&mut reader, file)?;
+        audit_file(file, &data, &mut findings);
+        #[cfg(feature = "rules")]
+        apply_rules(file, &data, &options.rules, &mut findings);
+    }
+
+    let report = AuditReport {
+        mod_name: header.mod_name.clone(),
+        mod_version: header.mod_version.clone(),
+        fingerprint: header.fingerprint(),
+        files: file_entries.len(),
+        findings,
+    };
+
+    if options.format == OutputFormat::Text {
+        print_report(&report);
+    } else if options.format == OutputFormat::Github {
+        for finding in &report.findings {
+            println!(
+                "{}",
+                output::github_annotation(
+                    "warning",
+                    Some(&finding.entry),
+                    &format!("{} ({})", finding.message, finding.kind.label())
+                )
+            );
+        }
+    } else {
+        println!("{}", output::render(options.format, "audit", &report)?);
+    }
+
+    Ok(())
+}
+
+fn print_report(report: &AuditReport) {
+    println!(
+        "Audit of {} v{} ({})",
+        report.mod_name, report.mod_version, report.fingerprint
+    );
+    for finding in &report.findings {
+        println!(
+            "  [{}] {}: {}",
+            finding.kind.label(),
+            finding.entry,
+            finding.message
+        );
+    }
+    if report.findings.is_empty() {
+        println!("No issues found in {} files", report.files);
+    } else {
+        println!(
+            "{} issue(s) found in {} files",
+            report.findings.len(),
+            report.files
+        );
+    }
+}
+
+pub fn audit_file(file: &ModFile, data: &[u8], findings: &mut Vec<Finding>) {
+    let mut push = |kind, message: String| {
+        findings.push(Finding {
+            entry: file.name.clone(),
+            kind,
+            message,
+        })
+    };
+
+    let name = file.name.as_str();
+    if is_absolute(name) {
+        push(
+            FindingKind::AbsolutePath,
+            "entry name is an absolute path".to_string(),
+        );
+    }
+    if components(name).any(|c| c == "..") {
+        push(
+            FindingKind::ParentTraversal,
+            "entry name contains `..` components".to_string(),
+        );
+    }
+    if components(name).any(|c| c.starts_with('.') && c != "." && c != "..") {
+        push(
+            FindingKind::HiddenName,
+            "entry name contains a hidden (dot) component".to_string(),
+        );
+    }
+
+    let extension = extension(name);
+    if let Some(ext) = &extension {
+        if SCRIPT_EXTENSIONS.contains(&ext.as_str()) {
+            push(FindingKind::Script, format!("`.{}` script file", ext));
+        }
+    }
+
+    match executable_kind(data) {
+        Some(ExecutableKind::NativePe) => push(
+            FindingKind::NativeExecutable,
+            "native (non-.NET) Windows executable".to_string(),
+        ),
+        Some(ExecutableKind::Elf) => push(
+            FindingKind::NativeExecutable,
+            "ELF executable or shared library".to_string(),
+        ),
+        Some(ExecutableKind::MachO) => push(
+            FindingKind::NativeExecutable,
+            "Mach-O executable or shared library".to_string(),
+        ),
+        Some(ExecutableKind::ManagedPe) => {
+            if data.len() > LARGE_DLL_THRESHOLD {
+                push(
+                    FindingKind::LargeAssembly,
+                    format!("assembly is unusually large ({} bytes)", data.len()),
+                );
+            }
+        }
+        None => {
+            if let Some(ext) = &extension {
+                if NATIVE_EXTENSIONS.contains(&ext.as_str()) {
+                    push(
+                        FindingKind::NativeExecutable,
+                        format!("`.{}` file with an unrecognized format", ext),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rules")]
+pub fn apply_rules(file: &ModFile, data: &[u8], rules: &[Rule], findings: &mut Vec<Finding>) {
+    for rule in rules {
+        if !rule.matches(data) {
+            continue;
+        }
+        trace!("rule `{}` matched entry: {}", rule.name, file.name);
+        let message = match &rule.description {
+            Some(description) => format!("matched rule `{}` ({})", rule.name, description),
+            None => format!("matched rule `{}`", rule.name),
+        };
+        findings.push(Finding {
+            entry: file.name.clone(),
+            kind: FindingKind::Rule,
+            message,
+        });
+    }
+}
+
+enum ExecutableKind {
+    ManagedPe,
+    NativePe,
+    Elf,
+    MachO,
+}
+
+fn executable_kind(data: &[u8]) -> Option<ExecutableKind> {
+    if data.starts_with(b"\x7fELF") {
+        return Some(ExecutableKind::Elf);
+    }
+    if data.len() >= 4 {
+        let magic = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        if matches!(
+            magic,
+            0xfeedface | 0xfeedfacf | 0xcefaedfe | 0xcffaedfe | 0xcafebabe
+        ) {
+            return Some(ExecutableKind::MachO);
+        }
+    }
+    if !data.starts_with(b"MZ") {
+        return None;
+    }
+    // anything that starts with MZ is treated as a PE image, managed only if
+    // it has a CLR runtime header in its data directories
+    Some(match clr_header_present(data) {
+        Some(true) => ExecutableKind::ManagedPe,
+        _ => ExecutableKind::NativePe,
+    })
+}
+
+fn clr_header_present(data: &[u8]) -> Option<bool> {
+    let read_u16 = |at: usize| Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?));
+    let read_u32 = |at: usize| Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?));
+
+    let pe_offset = read_u32(0x3c)? as usize;
+    if data.get(pe_offset..pe_offset + 4)? != b"PE\0\0" {
+        return None;
+    }
+    let optional_header = pe_offset + 24;
+    let data_directories = match read_u16(optional_header)? {
+        0x10b => optional_header + 96,
+        0x20b => optional_header + 112,
+        _ => return None,
+    };
+    // the CLR runtime header is the 15th data directory
+    let clr_directory = data_directories + 14 * 8;
+    Some(read_u32(clr_directory)? != 0)
+}
@@ -0,0 +1,156 @@
+use std::time::{Duration, Instant};
+
+use binary_rw::BinaryReader;
+use log::{info, trace, warn};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::inflate::Backend;
+use crate::output::{self, OutputFormat};
+use crate::source;
+use crate::{format_size, read_entry_table, read_header, read_stored, TModError};
+
+pub struct BenchOptions {
+    pub rounds: u64,
+    pub format: OutputFormat,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            rounds: 3,
+            format: OutputFormat::Text,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct BackendResult {
+    pub backend: &'static str,
+    pub best_ms: f64,
+    pub mean_ms: f64,
+    // of decompressed output, using the best round
+    pub mib_per_sec: f64,
+    // whether the output is byte-identical to the first backend's
+    pub matches: bool,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct BenchReport {
+    pub mod_name: String,
+    pub mod_version: String,
+    pub entries: usize,
+    pub compressed_bytes: u64,
+    pub uncompressed_bytes: u64,
+    pub rounds: u64,
+    pub results: Vec<BackendResult>,
+}
+
+// decompresses every compressed entry with each backend compiled into this
+// build. the compressed data is read into memory first so only inflating is
+// timed.
+pub fn bench(path: String, options: &BenchOptions) -> Result<(), TModError> {
+    trace!("opening file: {}", path);
+    let mut stream = source::open(&path)?;
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+
+    let header = read_header(&mut reader, None)?;
+    let file_entries = read_entry_table(&mut reader)?;
+
+    let mut compressed = Vec::new();
+    for file in &file_entries {
+        let data = read_stored(&mut reader, file)?;
+        if file.compressed_len != file.uncompressed_len {
+            compressed.push((data, file.uncompressed_len as usize));
+        }
+    }
+    let compressed_bytes: u64 = compressed.iter().map(|(data, _)| data.len() as u64).sum();
+    let uncompressed_bytes: u64 = compressed.iter().map(|(_, len)| *len as u64).sum();
+    if compressed.is_empty() {
+        warn!("{} has no compressed entries, nothing to measure", path);
+    }
+
+    let rounds = options.rounds.max(1);
+    let mut reference: Option<Vec<Vec<u8>>> = None;
+    let mut results = Vec::new();
+    for backend in Backend::available() {
+        info!("Benchmarking {}", backend.label());
+        let mut times = Vec::new();
+        let mut outputs = Vec::new();
+        for _ in 0..rounds {
+            outputs.clear();
+            let start = Instant::now();
+            for (data, len) in &compressed {
+                outputs.push(backend.inflate(data, *len)?);
+            }
+            times.push(start.elapsed());
+        }
+
+        let best = times.iter().min().copied().unwrap_or_default();
+        let mean = times.iter().sum::<Duration>() / rounds as u32;
+        let matches = match &reference {
+            Some(reference) => *reference == outputs,
+            None => {
+                reference = Some(outputs);
+                true
+            }
+        };
+        if !matches {
+            warn!("{} produced different output", backend.label());
+        }
+
+        results.push(BackendResult {
+            backend: backend.label(),
+            best_ms: best.as_secs_f64() * 1000.0,
+            mean_ms: mean.as_secs_f64() * 1000.0,
+            mib_per_sec: uncompressed_bytes as f64 / (1024.0 * 1024.0) / best.as_secs_f64(),
+            matches,
+        });
+    }
+
+    let report = BenchReport {
+        mod_name: header.mod_name,
+        mod_version: header.mod_version,
+        entries: compressed.len(),
+        compressed_bytes,
+        uncompressed_bytes,
+        rounds,
+        results,
+    };
+
+    if options.format == OutputFormat::Text {
+        print_report(&report);
+    } else {
+        println!("{}", output::render(options.format, "bench", &report)?);
+    }
+
+    Ok(())
+}
+
+fn print_report(report: &BenchReport) {
+    println!(
+        "{} v{}: {} compressed entries, {} ({} compressed), best of {}",
+        report.mod_name,
+        report.mod_version,
+        report.entries,
+        format_size(report.uncompressed_bytes),
+        format_size(report.compressed_bytes),
+        report.rounds
+    );
+    for result in &report.results {
+        println!(
+            "  {:<12} {:>10.2} ms {:>10.1} MiB/s{}",
+            result.backend,
+            result.best_ms,
+            result.mib_per_sec,
+            if result.matches {
+                ""
+            } else {
+                "  (output differs)"
+            }
+        );
+    }
+}
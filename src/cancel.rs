@@ -0,0 +1,164 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use fs2::FileExt;
+use log::trace;
+
+use crate::TModError;
+
+// entries are written in chunks this size so cancellation is noticed even in
+// the middle of a big one
+const CHUNK_SIZE: usize = 256 * 1024;
+
+// a handle for stopping an extraction from another thread (a GUI's cancel
+// button, or the CLI's Ctrl-C handler). clones share the same flag.
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub fn check(&self) -> Result<(), TModError> {
+        if self.is_cancelled() {
+            return Err(TModError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+// writes `data` to a temporary file next to `path` and renames it into place
+// once complete, so cancelling (or failing) never leaves a truncated file
+// under the entry's real name
+pub fn write_atomically(path: &Path, data: &[u8], token: &CancellationToken) -> io::Result<()> {
+    write_partial(path, data.len() as u64, |file| {
+        for chunk in data.chunks(CHUNK_SIZE) {
+            check_io(token)?;
+            file.write_all(chunk)?;
+        }
+        Ok(data.len() as u64)
+    })
+}
+
+// like `write_atomically`, for contents that are only available as a stream
+// of (normally) `len` bytes
+pub fn write_atomically_from(
+    path: &Path,
+    data: &mut dyn Read,
+    len: u64,
+    token: &CancellationToken,
+) -> io::Result<()> {
+    write_partial(path, len, |file| {
+        let mut buf = vec![0; CHUNK_SIZE];
+        let mut written = 0;
+        loop {
+            check_io(token)?;
+            let read = data.read(&mut buf)?;
+            if read == 0 {
+                return Ok(written);
+            }
+            file.write_all(&buf[..read])?;
+            written += read as u64;
+        }
+    })
+}
+
+// `write` returns how many bytes it wrote
+fn write_partial(
+    path: &Path,
+    len: u64,
+    write: impl FnOnce(&mut File) -> io::Result<u64>,
+) -> io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let partial = path.with_file_name(format!(".{}.partial", file_name));
+
+    let result = (|| {
+        let mut file = create_partial(&partial)?;
+        preallocate(&file, len)?;
+        let written = write(&mut file)?;
+        // a stream that came up short would leave the rest of the
+        // preallocated space in the file as zeroes
+        if written != len {
+            file.set_len(written)?;
+        }
+        file.flush()?;
+        drop(file);
+        std::fs::rename(&partial, path)
+    })();
+
+    if result.is_err() {
+        trace!("removing partial file: {:?}", partial);
+        let _ = std::fs::remove_file(&partial);
+    }
+    result
+}
+
+// always a new file: a partial left over from an earlier run is removed
+// first, and `create_new` (and O_NOFOLLOW on Unix) refuses to follow a
+// symlink put in its place instead of writing through it into another file
+pub(crate) fn create_partial(partial: &Path) -> io::Result<File> {
+    match std::fs::remove_file(partial) {
+        Ok(()) => trace!("removed stale partial file: {:?}", partial),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        options.custom_flags(libc::O_NOFOLLOW);
+    }
+    options.open(partial)
+}
+
+// reserves the space for the whole file before writing it, which keeps big
+// entries from fragmenting and fails right away when the disk or the quota
+// is full. filesystems that can't preallocate are written to as they are.
+fn preallocate(file: &File, len: u64) -> io::Result<()> {
+    if len == 0 {
+        return Ok(());
+    }
+    match file.allocate(len) {
+        Err(e) if is_out_of_space(&e) => Err(e),
+        Err(e) => {
+            trace!("not preallocating {} bytes: {}", len, e);
+            Ok(())
+        }
+        Ok(()) => Ok(()),
+    }
+}
+
+fn is_out_of_space(e: &io::Error) -> bool {
+    let code = e.raw_os_error();
+    if cfg!(windows) {
+        // ERROR_HANDLE_DISK_FULL and ERROR_DISK_FULL
+        matches!(code, Some(39) | Some(112))
+    } else if cfg!(target_os = "linux") {
+        // ENOSPC and EDQUOT
+        matches!(code, Some(28) | Some(122))
+    } else {
+        // ENOSPC and EDQUOT on macOS and the BSDs
+        matches!(code, Some(28) | Some(69))
+    }
+}
+
+fn check_io(token: &CancellationToken) -> io::Result<()> {
+    if token.is_cancelled() {
+        return Err(io::Error::new(io::ErrorKind::Other, "extraction cancelled"));
+    }
+    Ok(())
+}
@@ -0,0 +1,158 @@
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::inflate::Backend;
+use crate::output::{self, OutputFormat};
+use crate::TModError;
+
+// what this particular build of the binary can do, for wrappers that want to
+// adapt to it instead of parsing `--help`
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Capabilities {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub subcommands: Vec<&'static str>,
+    pub input_formats: Vec<&'static str>,
+    pub output_formats: Vec<&'static str>,
+    // encodings `view` and `grep` decode text entries from
+    pub text_encodings: Vec<&'static str>,
+    pub converters: Vec<&'static str>,
+    // DEFLATE implementations compiled in, usable with `--inflate`
+    pub inflate_backends: Vec<&'static str>,
+    pub features: Vec<&'static str>,
+    pub limits: Limits,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Limits {
+    // both are stored as signed 32-bit integers in the archive
+    pub max_entries: u64,
+    pub max_entry_size: u64,
+}
+
+pub fn capabilities() -> Capabilities {
+    let mut features = vec![
+        "hash-check",
+        "signature-check",
+        "git-init",
+        "low-memory",
+        "legacy-format",
+    ];
+    if cfg!(feature = "rules") {
+        features.push("audit-rules");
+    }
+    if cfg!(feature = "archives") {
+        features.push("archive-output");
+    }
+    if cfg!(all(feature = "hardened", target_os = "linux")) {
+        features.push("sandbox");
+    }
+    if cfg!(feature = "serve") {
+        features.push("serve");
+    }
+    if cfg!(feature = "clipboard") {
+        features.push("clipboard");
+    }
+    if cfg!(feature = "http") {
+        features.push("http-input");
+    }
+    if cfg!(feature = "progress") {
+        features.push("progress");
+    }
+    if cfg!(feature = "images") {
+        features.push("png");
+    }
+    if cfg!(feature = "globs") {
+        features.push("globs");
+    }
+    if cfg!(feature = "mmap") {
+        features.push("mmap");
+    }
+    if cfg!(feature = "dedup") {
+        features.push("dedup");
+    }
+    if cfg!(feature = "schema") {
+        features.push("schema");
+    }
+    let mut converters = vec!["unpremultiply", "resize"];
+    if cfg!(feature = "images") {
+        converters.extend(["rawimg-png", "thumbnail"]);
+    }
+
+    Capabilities {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        subcommands: vec![
+            "unpack",
+            "fingerprint",
+            "info",
+            "list",
+            "layout",
+            "audit",
+            "check",
+            "verify",
+            "lint",
+            "orphans",
+            "stats",
+            "dedup",
+            "strings",
+            "diff",
+            "view",
+            "grep",
+            "verify-dir",
+            "bench",
+            "doctor",
+            "fetch",
+            "corpus",
+            "gen",
+            "gallery",
+            "scaffold",
+            "pack",
+            "register",
+            "unregister",
+            "explain",
+            "capabilities",
+            "schema",
+        ],
+        input_formats: vec!["tmod"],
+        output_formats: vec!["text", "json", "nuon", "powershell", "github"],
+        text_encodings: vec!["utf-8", "utf-8-bom", "utf-16le", "utf-16be"],
+        converters,
+        inflate_backends: Backend::available()
+            .iter()
+            .map(|backend| backend.label())
+            .collect(),
+        features,
+        limits: Limits {
+            max_entries: i32::MAX as u64,
+            max_entry_size: i32::MAX as u64,
+        },
+    }
+}
+
+pub fn print_capabilities(format: OutputFormat) -> Result<(), TModError> {
+    let capabilities = capabilities();
+    if format != OutputFormat::Text {
+        println!("{}", output::render(format, "capabilities", &capabilities)?);
+        return Ok(());
+    }
+
+    println!("{} v{}", capabilities.name, capabilities.version);
+    println!("subcommands: {}", capabilities.subcommands.join(", "));
+    println!("input formats: {}", capabilities.input_formats.join(", "));
+    println!("output formats: {}", capabilities.output_formats.join(", "));
+    println!("converters: {}", capabilities.converters.join(", "));
+    println!(
+        "inflate backends: {}",
+        capabilities.inflate_backends.join(", ")
+    );
+    println!("features: {}", capabilities.features.join(", "));
+    println!(
+        "limits: {} entries, {} bytes per entry",
+        capabilities.limits.max_entries, capabilities.limits.max_entry_size
+    );
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use binary_rw::{BinaryReader, SeekStream};
+use log::{info, trace};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::inflate;
+use crate::order::data_offsets;
+use crate::output::{self, OutputFormat};
+use crate::preflight::{self, SizeLimits};
+use crate::signature;
+use crate::stream::{DataHasher, HashingStream};
+use crate::{read_entry_table_unchecked, read_header, source, TModError};
+
+#[derive(Default)]
+pub struct CheckOptions {
+    // only look at stream headers and sizes instead of reading everything
+    pub fast: bool,
+    // also check the signature against this key
+    pub key: Option<signature::PublicKey>,
+    pub format: OutputFormat,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct CheckProblem {
+    // the entry the problem is in, `None` for the archive as a whole
+    pub entry: Option<String>,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct CheckReport {
+    pub mod_name: String,
+    pub mod_version: String,
+    pub fingerprint: String,
+    pub files: usize,
+    pub fast: bool,
+    pub problems: Vec<CheckProblem>,
+}
+
+// verifies an archive without extracting it. a full check reads everything:
+// it decompresses every entry and compares the hash of the data region. a
+// fast check seeks to the start of each compressed entry and only checks
+// that its sizes are possible and its first DEFLATE block header is valid,
+// so it reads a few bytes per entry no matter how big the mod is. raw
+// DEFLATE streams have no checksum of their own, so that is as far as a
+// check can go without decompressing.
+pub fn check(path: String, options: &CheckOptions) -> Result<(), TModError> {
+    trace!("opening file: {}", path);
+    let hasher = DataHasher::default();
+    let mut stream = HashingStream::new(source::open(&path)?, hasher.clone());
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+
+    let header = read_header(&mut reader, (!options.fast).then_some(&hasher))?;
+    let file_entries = read_entry_table_unchecked(&mut reader)?;
+    let table_end = reader.tell()?;
+
+    let mut problems = Vec::new();
+    let mut problem = |entry: Option<&str>, message: String| {
+        problems.push(CheckProblem {
+            entry: entry.map(str::to_string),
+            message,
+        })
+    };
+
+    if let Some(key) = &options.key {
+        if let Err(e) = signature::verify_header(&header, key) {
+            problem(None, e.to_string());
+        }
+    }
+
+    // lengths that can't be added up or read are all there is to say about
+    // the entries then
+    let size_problems = preflight::size_problems(&file_entries, &SizeLimits::default());
+    if !size_problems.is_empty() {
+        for (entry, message) in size_problems {
+            problem(entry, message);
+        }
+        return finish(&header, file_entries.len(), options, problems);
+    }
+
+    let declared_end = header.data_offset + header.file_data_len as usize;
+    let data_len = file_entries
+        .iter()
+        .try_fold(0u64, |len, f| len.checked_add(f.compressed_len as u64));
+    let data_len = match data_len {
+        Some(data_len) => data_len,
+        None => {
+            problem(
+                None,
+                "the entries take up more bytes than there can be".to_string(),
+            );
+            return finish(&header, file_entries.len(), options, problems);
+        }
+    };
+    if (table_end as u64).checked_add(data_len) != Some(declared_end as u64) {
+        problem(
+            None,
+            format!(
+                "the entries take up {} bytes, but the header declares {}",
+                data_len,
+                declared_end.saturating_sub(table_end)
+            ),
+        );
+    }
+    if reader.len()? < declared_end {
+        problem(
+            None,
+            format!(
+                "the file is truncated, {} bytes are missing",
+                declared_end - reader.len()?
+            ),
+        );
+        // entries past the end can't be read at all
+        return finish(&header, file_entries.len(), options, problems);
+    }
+
+    info!("Checking file entries");
+    let offsets = data_offsets(&file_entries, table_end)?;
+    for (file, offset) in file_entries.iter().zip(offsets) {
+        let compressed = file.compressed_len != file.uncompressed_len;
+        if options.fast {
+            if !compressed {
+                continue;
+            }
+            reader.seek(offset)?;
+            let len = inflate::HEADER_LEN.min(file.compressed_len as usize);
+            let start = reader.read_bytes(len)?;
+            if let Err(message) = inflate::check_header(
+                &start,
+                file.compressed_len as usize,
+                file.uncompressed_len as usize,
+            ) {
+                problem(Some(&file.name), message);
+            }
+            continue;
+        }
+
+        // read in table order, so the hasher sees the whole data region
+        let data = reader.read_bytes(file.compressed_len as usize)?;
+        if compressed {
+            match inflate::selected().inflate(&data, file.uncompressed_len as usize) {
+                Ok(contents) if contents.len() != file.uncompressed_len as usize => problem(
+                    Some(&file.name),
+                    format!(
+                        "decompressed to {} bytes, expected {}",
+                        contents.len(),
+                        file.uncompressed_len
+                    ),
+                ),
+                Ok(_) => {}
+                Err(e) => problem(Some(&file.name), e.to_string()),
+            }
+        }
+    }
+
+    if !options.fast {
+        // anything between the last entry and the declared end is hashed too
+        let position = reader.tell()?;
+        if position < declared_end {
+            reader.read_bytes(declared_end - position)?;
+        }
+        if let Some(computed) = hasher.finish() {
+            if computed != header.hash {
+                problem(
+                    None,
+                    format!(
+                        "hash mismatch: stored {}, computed {}",
+                        hex::encode(&header.hash),
+                        hex::encode(&computed)
+                    ),
+                );
+            }
+        }
+    }
+
+    finish(&header, file_entries.len(), options, problems)
+}
+
+fn finish(
+    header: &crate::ModHeader,
+    files: usize,
+    options: &CheckOptions,
+    problems: Vec<CheckProblem>,
+) -> Result<(), TModError> {
+    let report = CheckReport {
+        mod_name: header.mod_name.clone(),
+        mod_version: header.mod_version.clone(),
+        fingerprint: header.fingerprint(),
+        files,
+        fast: options.fast,
+        problems,
+    };
+
+    match options.format {
+        OutputFormat::Text => print_report(&report),
+        OutputFormat::Github => {
+            for problem in &report.problems {
+                println!(
+                    "{}",
+                    output::github_annotation("error", problem.entry.as_deref(), &problem.message)
+                );
+            }
+        }
+        format => println!("{}", output::render(format, "check", &report)?),
+    }
+
+    if !report.problems.is_empty() {
+        return Err(TModError::CheckFailed(report.problems.len()));
+    }
+    Ok(())
+}
+
+fn print_report(report: &CheckReport) {
+    println!(
+        "{} check of {} v{} ({})",
+        if report.fast { "Fast" } else { "Full" },
+        report.mod_name,
+        report.mod_version,
+        report.fingerprint
+    );
+    for problem in &report.problems {
+        match &problem.entry {
+            Some(entry) => println!("  {}: {}", entry, problem.message),
+            None => println!("  {}", problem.message),
+        }
+    }
+    if report.problems.is_empty() {
+        println!("No problems found in {} files", report.files);
+    } else {
+        println!(
+            "{} problem(s) found in {} files",
+            report.problems.len(),
+            report.files
+        );
+    }
+}
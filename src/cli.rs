@@ -0,0 +1,542 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use tmod_unpacker::output::OutputFormat;
+use tmod_unpacker::TModError;
+
+const AFTER_HELP: &str = "\
+Run `tmod-unpacker <command> --help` for the options of a command. Without a
+command, the arguments are those of `unpack`.
+
+Set the RUST_LOG environment variable to set the log level.";
+
+// `tmod-unpacker <input file> [output directory]` predates the subcommands
+// and still works, it's what dropping a file onto the executable runs
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Extracts the contents of a tModLoader mod file.",
+    after_help = AFTER_HELP,
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub unpack: UnpackArgs,
+    /// Print names with ASCII characters only, for terminals that can't show the others
+    #[arg(long, global = true)]
+    pub ascii_only: bool,
+}
+
+// only one is ever built, so the size of `Unpack` doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+pub enum Command {
+    /// Extract a mod into a directory or an archive
+    Unpack(UnpackArgs),
+    /// Show a mod's metadata and the totals of its entry table
+    Info(InfoArgs),
+    /// List every entry in a mod with its size
+    List(ListArgs),
+    /// Build a .tmod from a directory
+    Pack(PackArgs),
+    /// Check an archive for damage without extracting it
+    #[command(visible_alias = "verify")]
+    Check(CheckArgs),
+    /// Print a short identifier for this exact build of a mod
+    Fingerprint { input: String },
+    /// Print which field every byte of a mod belongs to, with offsets and lengths
+    Layout {
+        input: String,
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Explain an error code
+    Explain { code: String },
+    /// Describe what this build supports
+    Capabilities(FormatArgs),
+    /// Print the JSON Schema of an output, or list them without one
+    Schema {
+        kind: Option<String>,
+        /// Write every schema into this directory instead
+        #[arg(long, value_name = "DIRECTORY", conflicts_with = "kind")]
+        out_dir: Option<PathBuf>,
+    },
+    /// Print a single entry as text
+    View { input: String, entry: String },
+    /// Write a single entry to stdout as it is, for piping into other tools
+    Cat { input: String, entry: String },
+    /// Print every line of every text entry that contains a pattern
+    Grep {
+        input: String,
+        pattern: String,
+        #[arg(short, long)]
+        ignore_case: bool,
+    },
+    /// Compare an extracted directory against a manifest
+    VerifyDir {
+        dir: PathBuf,
+        /// The manifest written by `--emit-entries`
+        #[arg(long, value_name = "MANIFEST")]
+        against: String,
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Scan a mod for suspicious contents
+    Audit {
+        input: String,
+        /// Extra pattern rules, needs the `rules` feature
+        #[arg(long, value_name = "RULES")]
+        rules: Vec<String>,
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Check a mod against common packaging mistakes
+    Lint(LintArgs),
+    /// Print the text found in the mod's assemblies
+    Strings {
+        input: String,
+        /// The shortest string to print
+        #[arg(long, value_name = "N", default_value = "6")]
+        min_len: usize,
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// List unused assets and references to missing ones
+    Orphans {
+        input: String,
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Break a mod's size down by extension
+    Stats {
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        /// Compare several releases of one mod
+        #[arg(long)]
+        history: bool,
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Estimate what deduplicating a library of mods would save
+    Dedup {
+        /// Mods, or directories to search for them
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// The average chunk size to aim for
+        #[arg(long, value_name = "BYTES", default_value = "16384")]
+        avg_size: u32,
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Create the skeleton of a new mod modeled after an existing one
+    Scaffold {
+        #[arg(long, value_name = "INPUT")]
+        from: String,
+        #[arg(long)]
+        name: String,
+        /// Defaults to a directory named after the new mod
+        #[arg(short, long, value_name = "DIRECTORY")]
+        output: Option<PathBuf>,
+    },
+    /// Compare two versions of a mod
+    Diff {
+        old: String,
+        new: String,
+        /// Compare localization keys instead, as CSV
+        #[arg(long)]
+        localization: bool,
+        /// Where the CSV goes instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Write an HTML page with every image in a mod
+    Gallery {
+        input: String,
+        #[arg(short, long, value_name = "FILE", default_value = "gallery.html")]
+        output: PathBuf,
+    },
+    /// Open .tmod files with this binary
+    Register,
+    /// Undo `register`
+    Unregister,
+    /// Check that this machine can extract into a directory
+    Doctor {
+        /// Defaults to the current directory
+        target: Option<PathBuf>,
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Download a Steam Workshop mod by its id or link and extract it
+    Fetch {
+        #[arg(value_name = "ID OR URL")]
+        item: String,
+        /// Defaults to `<name>-extracted` in the current directory
+        #[arg(value_name = "OUTPUT DIRECTORY")]
+        out_dir: Option<PathBuf>,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+        /// Download the item again, even if a copy of it is already there
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// Check this build against a directory of fixture mods
+    Corpus {
+        #[command(subcommand)]
+        command: CorpusCommand,
+    },
+    /// Write a synthetic mod for tests and benchmarks, the same for the same seed
+    Gen {
+        output: PathBuf,
+        #[arg(long, value_name = "N", default_value = "100")]
+        entries: usize,
+        #[arg(long, value_name = "N", default_value = "0")]
+        seed: u64,
+        /// The smallest entry
+        #[arg(long, value_name = "BYTES", default_value = "0")]
+        min_size: usize,
+        /// The biggest entry
+        #[arg(long, value_name = "BYTES", default_value = "1048576")]
+        max_size: usize,
+        /// How sizes spread between the two: log (mostly small entries, like
+        /// a real mod) or uniform
+        #[arg(long, value_name = "DISTRIBUTION", default_value = "log", value_parser = ["log", "uniform"])]
+        distribution: String,
+        /// Add pathological entries: deep-paths, dup-names or huge-entries
+        #[arg(long = "with", value_name = "CASE", value_delimiter = ',', value_parser = ["deep-paths", "dup-names", "huge-entries"])]
+        with: Vec<String>,
+    },
+    /// Time reading and decompressing a mod
+    Bench {
+        input: String,
+        #[arg(long, value_name = "N")]
+        rounds: Option<u64>,
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Answer inspect and extract requests over HTTP, needs the `serve` feature
+    Serve {
+        #[arg(long, value_name = "ADDRESS", default_value = "127.0.0.1:8080")]
+        listen: String,
+        /// The biggest mod accepted
+        #[arg(long, value_name = "BYTES", default_value = "67108864")]
+        max_size: u64,
+        /// The most a mod may unpack to for `/extract`
+        #[arg(long, value_name = "BYTES", default_value = "1073741824")]
+        max_unpacked: u64,
+        /// How long a single request may take
+        #[arg(long, value_name = "SECS", default_value = "30")]
+        timeout: u64,
+        /// How many requests are handled at once
+        #[arg(long, value_name = "COUNT", default_value = "16")]
+        max_connections: usize,
+        /// Let `?url=` fetch mods from public http(s) urls, needs the `http` feature
+        #[arg(long)]
+        allow_fetch: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CorpusCommand {
+    /// Extract every mod in a directory and compare the outcome with its `.expected` file
+    Run {
+        dir: PathBuf,
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+}
+
+#[derive(Args)]
+pub struct FormatArgs {
+    /// Shorthand for `--format json`
+    #[arg(long)]
+    pub json: bool,
+    /// text, json, nuon, powershell or github
+    #[arg(long, value_name = "FORMAT", conflicts_with = "json")]
+    pub format: Option<String>,
+}
+
+impl FormatArgs {
+    pub fn resolve(&self) -> Result<OutputFormat, TModError> {
+        match &self.format {
+            Some(format) => OutputFormat::parse(format),
+            None if self.json => Ok(OutputFormat::Json),
+            None => Ok(OutputFormat::Text),
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct UnpackArgs {
+    /// The .tmod file, an http(s) url, `-` for stdin, or a Mods directory
+    pub input: Option<String>,
+    /// Defaults to `<name>-extracted` next to the input file
+    #[arg(value_name = "OUTPUT DIRECTORY")]
+    pub out_dir: Option<PathBuf>,
+    /// More mods to extract, the output directory goes last
+    #[arg(value_name = "MORE")]
+    pub more: Vec<String>,
+    /// Extract an installed mod, found by its name in the Mods and workshop folders
+    #[arg(long = "mod", value_name = "NAME")]
+    pub mod_name: Option<String>,
+    /// The output directory, every argument before it is then an input
+    #[arg(short, long, visible_alias = "out", value_name = "DIRECTORY")]
+    pub output: Option<PathBuf>,
+    /// Skip the confirmation prompt
+    #[arg(short, long)]
+    pub yes: bool,
+    /// Print the path and size of every file that would be written, without
+    /// decompressing or writing anything
+    #[arg(long, conflicts_with = "fifo_dir")]
+    pub dry_run: bool,
+    /// Overwrite existing files, the default, and replace symlinks in the way
+    #[arg(long)]
+    pub force: bool,
+    /// Keep existing files and only extract the entries that are missing
+    #[arg(long, conflicts_with_all = ["force", "prompt", "no_clobber", "to_zip", "to_tar"])]
+    pub skip_existing: bool,
+    /// Ask before overwriting each existing file
+    #[arg(long, conflicts_with_all = ["force", "no_clobber", "to_zip", "to_tar"])]
+    pub prompt: bool,
+    /// Fail before writing anything if a file would be overwritten
+    #[arg(long, conflicts_with_all = ["force", "to_zip", "to_tar"])]
+    pub no_clobber: bool,
+    /// Write entries with absolute or `..` names where they point
+    #[arg(long)]
+    pub allow_unsafe_paths: bool,
+    /// Convert the line endings of text entries
+    #[arg(long, value_name = "EOL", value_parser = ["lf", "crlf"])]
+    pub normalize_eol: Option<String>,
+    /// Remove UTF-8 byte order marks from text entries
+    #[arg(long)]
+    pub strip_bom: bool,
+    /// Fail when the hash of the data doesn't match the stored one
+    #[arg(long, conflicts_with = "sample")]
+    pub verify: bool,
+    /// Skip entries that can't be read or decompressed, extract the rest and
+    /// fail at the end with a list of what was skipped
+    #[arg(long)]
+    pub keep_going: bool,
+    /// Refuse to extract a mod that isn't signed by tModLoader's mod browser
+    #[arg(long)]
+    pub check_signature: bool,
+    /// Check the signature against this RSA key instead of the mod browser's
+    #[arg(long, value_name = "FILE")]
+    pub pubkey: Option<PathBuf>,
+    /// Retry writing an entry after a transient I/O error
+    #[arg(long, value_name = "N", default_value = "0")]
+    pub retries: u32,
+    /// Extract into a zip archive instead of a directory
+    #[arg(
+        long,
+        visible_alias = "zip",
+        value_name = "FILE",
+        conflicts_with = "to_tar"
+    )]
+    pub to_zip: Option<PathBuf>,
+    /// Create a FIFO for each entry instead, filled when it is first read
+    #[arg(
+        long,
+        value_name = "DIRECTORY",
+        conflicts_with_all = ["output", "to_zip", "to_tar", "route", "git_init", "mod_name"]
+    )]
+    pub fifo_dir: Option<PathBuf>,
+    /// Extract into a tar archive instead of a directory
+    #[arg(long, visible_alias = "tar", value_name = "FILE")]
+    pub to_tar: Option<PathBuf>,
+    /// Compress the tar archive, implied by a `.gz` or `.tgz` name
+    #[arg(long, requires = "to_tar")]
+    pub gzip: bool,
+    /// Abort when the whole run takes longer than this
+    #[arg(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+    /// Abort when a single entry takes longer than this
+    #[arg(long, value_name = "SECS")]
+    pub entry_timeout: Option<u64>,
+    /// Send entries matching a glob to another directory
+    #[arg(long, value_name = "GLOB=>DIR")]
+    pub route: Vec<String>,
+    /// Convert entries matching a glob, e.g. `**/*.rawimg=png:unpremultiply`
+    #[arg(long, value_name = "GLOB=STEPS")]
+    pub transform: Vec<String>,
+    /// Write .rawimg textures as PNG, shorthand for `--transform '**/*.rawimg=png'`
+    #[arg(long)]
+    pub convert_images: bool,
+    /// Also write every texture scaled to fit this size under `_thumbs/`
+    #[arg(long, value_name = "SIZE", value_parser = clap::value_parser!(u32).range(1..))]
+    pub thumbnails: Option<u32>,
+    /// Write the thumbnails instead of the full-size textures
+    #[arg(long, requires = "thumbnails")]
+    pub thumbnails_only: bool,
+    /// Write the archive path to ModSources path mapping
+    #[arg(long, value_name = "FILE")]
+    pub source_map: Option<PathBuf>,
+    /// table (default), alpha, size-desc or size-asc
+    #[arg(long)]
+    pub order: Option<String>,
+    /// Extract the entries matching these globs first
+    #[arg(long, value_name = "FILE")]
+    pub priority_list: Option<PathBuf>,
+    /// The deepest entry path to accept
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+    /// The longest entry path component to accept
+    #[arg(long, value_name = "BYTES")]
+    pub max_component_len: Option<usize>,
+    /// The most a single entry may unpack to [default: 1 GiB]
+    #[arg(long, value_name = "BYTES")]
+    pub max_size: Option<u64>,
+    /// The most all entries together may unpack to [default: 8 GiB]
+    #[arg(long, value_name = "BYTES")]
+    pub max_total_size: Option<u64>,
+    /// The decompression backend, see `capabilities` for this build's
+    #[arg(long, value_name = "BACKEND")]
+    pub inflate: Option<String>,
+    /// Memory-map the input instead of reading it
+    #[arg(long)]
+    pub mmap: bool,
+    /// Keep a copy of mods downloaded from a url here, reused for as long as
+    /// the hash in their header stays the same
+    #[arg(long, value_name = "DIRECTORY")]
+    pub cache_dir: Option<PathBuf>,
+    /// Stream entries to disk in small chunks instead of holding each one in
+    /// memory, for machines with little RAM
+    #[arg(long, conflicts_with_all = [
+        "transform", "convert_images", "thumbnails", "normalize_eol", "strip_bom", "lfs_threshold"
+    ])]
+    pub low_memory: bool,
+    /// Decompress and write entries on this many threads, 0 for one per core
+    #[arg(
+        short,
+        long,
+        value_name = "N",
+        default_value = "1",
+        conflicts_with = "low_memory"
+    )]
+    pub jobs: usize,
+    /// Commit the extracted files to a git repository in the output directory
+    #[arg(long, conflicts_with_all = ["to_zip", "to_tar", "route"])]
+    pub git_init: bool,
+    /// With `--git-init`, store entries bigger than this through Git LFS
+    #[arg(long, value_name = "BYTES", requires = "git_init")]
+    pub lfs_threshold: Option<u64>,
+    /// Wait for another invocation writing the same output
+    #[arg(long)]
+    pub wait_lock: bool,
+    /// Post a summary to a Discord or Slack webhook
+    #[arg(long, value_name = "URL")]
+    pub notify_webhook: Option<String>,
+    /// Write a JSON line per extracted entry
+    #[arg(long, value_name = "FILE")]
+    pub emit_entries: Option<PathBuf>,
+    /// Only extract the entries matching a glob
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+    /// Don't extract the entries matching a glob
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+    /// Print the progress after every entry as a JSON line on stderr
+    #[arg(long)]
+    pub progress_json: bool,
+    /// Don't show a progress bar, which is only shown on a terminal anyway
+    #[arg(long)]
+    pub no_progress: bool,
+    /// Only extract this many entries
+    #[arg(long, value_name = "N")]
+    pub sample: Option<u64>,
+    /// Pick the sample at random instead of from the start of the table
+    #[arg(long, requires = "sample")]
+    pub sample_random: bool,
+    /// Sample each extension separately
+    #[arg(long = "sample-per-ext", requires = "sample")]
+    pub sample_per_extension: bool,
+    /// The seed for `--sample-random`
+    #[arg(long, value_name = "N", requires = "sample")]
+    pub seed: Option<u64>,
+}
+
+#[derive(Args)]
+pub struct InfoArgs {
+    pub input: String,
+    /// Also put the output on the clipboard
+    #[arg(long)]
+    pub copy: bool,
+    #[command(flatten)]
+    pub format: FormatArgs,
+}
+
+#[derive(Args)]
+pub struct ListArgs {
+    #[command(flatten)]
+    pub info: InfoArgs,
+    /// Print every directory once, with its size and its entries indented below
+    #[arg(long)]
+    pub compact: bool,
+}
+
+#[derive(Args)]
+pub struct PackArgs {
+    /// The mod's directory, or with `--workspace` the workspace file or its
+    /// directory (the current one by default)
+    #[arg(required_unless_present = "workspace")]
+    pub input: Option<PathBuf>,
+    /// Pack every mod listed in a `tmodpack.toml`
+    #[arg(long, conflicts_with_all = ["name", "version", "output"])]
+    pub workspace: bool,
+    /// Defaults to the name of the directory
+    #[arg(long)]
+    pub name: Option<String>,
+    /// Defaults to the version in the directory's build.txt
+    #[arg(long)]
+    pub version: Option<String>,
+    /// The tModLoader version the mod was built for
+    #[arg(long, value_name = "VERSION", required_unless_present = "workspace")]
+    pub tml_version: Option<String>,
+    /// Defaults to `<name>.tmod` in the current directory
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+    /// Recompress PNG textures losslessly before packing them
+    #[arg(long)]
+    pub optimize_png: bool,
+    /// Convert uncompressed and high-bitrate audio to Ogg Vorbis at this
+    /// quality, e.g. `ogg:q5`, with oggenc or ffmpeg
+    #[arg(long, value_name = "ogg:q<QUALITY>")]
+    pub reencode_audio: Option<String>,
+}
+
+#[derive(Args)]
+pub struct CheckArgs {
+    pub input: String,
+    /// Only check sizes and stream headers
+    #[arg(long)]
+    pub fast: bool,
+    /// Also check the signature against tModLoader's mod browser key
+    #[arg(long)]
+    pub check_signature: bool,
+    /// Check the signature against this RSA key instead of the mod browser's
+    #[arg(long, value_name = "FILE")]
+    pub pubkey: Option<PathBuf>,
+    #[command(flatten)]
+    pub format: FormatArgs,
+}
+
+// the levels are applied in the order they were given, so they are read from
+// the matches by index rather than through these fields
+#[derive(Args)]
+pub struct LintArgs {
+    pub input: String,
+    #[arg(long, value_name = "LINT")]
+    pub allow: Vec<String>,
+    #[arg(long, value_name = "LINT")]
+    pub warn: Vec<String>,
+    /// A lint, or `warnings` to fail on any warning
+    #[arg(long, value_name = "LINT")]
+    pub deny: Vec<String>,
+    #[command(flatten)]
+    pub format: FormatArgs,
+}
@@ -0,0 +1,184 @@
+use std::{
+    borrow::Cow,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static ASCII_ONLY: AtomicBool = AtomicBool::new(false);
+
+// Russian and Ukrainian, romanized roughly like passports do it, from `А`
+// (U+0410) to `я` (U+044F)
+const CYRILLIC: &[&str] = &[
+    "A", "B", "V", "G", "D", "E", "Zh", "Z", "I", "Y", "K", "L", "M", "N", "O", "P", "R", "S", "T",
+    "U", "F", "Kh", "Ts", "Ch", "Sh", "Shch", "", "Y", "", "E", "Yu", "Ya", "a", "b", "v", "g",
+    "d", "e", "zh", "z", "i", "y", "k", "l", "m", "n", "o", "p", "r", "s", "t", "u", "f", "kh",
+    "ts", "ch", "sh", "shch", "", "y", "", "e", "yu", "ya",
+];
+
+// Windows consoles print in the legacy code page of the system's language
+// until told otherwise, so the output of the tools we run (git, steamcmd)
+// and of anything reading ours through a pipe turns into mojibake. switches
+// the console to UTF-8 for as long as the returned guard lives, and back to
+// what it was afterwards, since the setting outlives the process.
+pub fn init() -> ConsoleGuard {
+    ConsoleGuard(set_utf8())
+}
+
+pub struct ConsoleGuard(Option<u32>);
+
+impl Drop for ConsoleGuard {
+    fn drop(&mut self) {
+        if let Some(code_page) = self.0 {
+            restore(code_page);
+        }
+    }
+}
+
+// whether the console window was opened just for us, as it is when a file is
+// dropped onto the executable or an associated one is double-clicked, and
+// closes as soon as we exit. run from a terminal, the shell shares it.
+#[cfg(windows)]
+pub fn owns_window() -> bool {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetConsoleProcessList(process_list: *mut u32, count: u32) -> u32;
+    }
+
+    // only the count is wanted, which comes back even if it doesn't fit
+    let mut process = 0;
+    unsafe { GetConsoleProcessList(&mut process, 1) == 1 }
+}
+
+#[cfg(not(windows))]
+pub fn owns_window() -> bool {
+    false
+}
+
+// `--ascii-only`, for terminals that can't show anything else
+pub fn set_ascii_only(ascii_only: bool) {
+    ASCII_ONLY.store(ascii_only, Ordering::Relaxed);
+}
+
+// a name or other text from a mod, the way it should be printed: as it is,
+// or transliterated with `--ascii-only`. letters with a common romanization
+// get it, everything else is escaped like `\u{4e2d}` so names that differ
+// still look different.
+pub fn text(text: &str) -> Cow<'_, str> {
+    if !ASCII_ONLY.load(Ordering::Relaxed) || text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+    let mut ascii = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii() {
+            ascii.push(c);
+        } else if let Some(replacement) = transliterate(c) {
+            ascii.push_str(replacement);
+        } else {
+            ascii.push_str(&c.escape_unicode().to_string());
+        }
+    }
+    Cow::Owned(ascii)
+}
+
+// latin letters without their diacritics, Cyrillic romanized and
+// typographic punctuation as the plain kind
+fn transliterate(c: char) -> Option<&'static str> {
+    Some(match c {
+        '\u{410}'..='\u{44f}' => CYRILLIC[c as usize - 0x410],
+        'À'..='Å' | 'Ą' | 'Ă' | 'Ā' => "A",
+        'à'..='å' | 'ą' | 'ă' | 'ā' => "a",
+        'Æ' => "AE",
+        'æ' => "ae",
+        'Ç' | 'Ć' | 'Č' => "C",
+        'ç' | 'ć' | 'č' => "c",
+        'Ð' | 'Ď' | 'Đ' => "D",
+        'ð' | 'ď' | 'đ' => "d",
+        'È'..='Ë' | 'Ę' | 'Ě' | 'Ē' => "E",
+        'è'..='ë' | 'ę' | 'ě' | 'ē' => "e",
+        'Ğ' => "G",
+        'ğ' => "g",
+        'Ì'..='Ï' | 'İ' | 'Ī' => "I",
+        'ì'..='ï' | 'ı' | 'ī' => "i",
+        'Ł' => "L",
+        'ł' => "l",
+        'Ñ' | 'Ń' | 'Ň' => "N",
+        'ñ' | 'ń' | 'ň' => "n",
+        'Ò'..='Ö' | 'Ø' | 'Ő' | 'Ō' => "O",
+        'ò'..='ö' | 'ø' | 'ő' | 'ō' => "o",
+        'Œ' => "OE",
+        'œ' => "oe",
+        'Ř' => "R",
+        'ř' => "r",
+        'Ś' | 'Ş' | 'Š' => "S",
+        'ś' | 'ş' | 'š' => "s",
+        'ß' => "ss",
+        'Ť' | 'Ţ' => "T",
+        'ť' | 'ţ' => "t",
+        'Þ' => "Th",
+        'þ' => "th",
+        'Ù'..='Ü' | 'Ů' | 'Ű' | 'Ū' => "U",
+        'ù'..='ü' | 'ů' | 'ű' | 'ū' => "u",
+        'Ý' | 'Ÿ' => "Y",
+        'ý' | 'ÿ' => "y",
+        'Ź' | 'Ż' | 'Ž' => "Z",
+        'ź' | 'ż' | 'ž' => "z",
+        'Ё' => "Yo",
+        'ё' => "yo",
+        'Є' => "Ye",
+        'є' => "ye",
+        'І' => "I",
+        'і' => "i",
+        'Ї' => "Yi",
+        'ї' => "yi",
+        'Ґ' => "G",
+        'ґ' => "g",
+        '\u{a0}' => " ",
+        '‘' | '’' => "'",
+        '“' | '”' | '«' | '»' => "\"",
+        '–' | '—' => "-",
+        '…' => "...",
+        '·' => ".",
+        '×' => "x",
+        _ => return None,
+    })
+}
+
+// the code page to go back to, if the console was switched
+#[cfg(windows)]
+fn set_utf8() -> Option<u32> {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetConsoleOutputCP() -> u32;
+        fn SetConsoleOutputCP(code_page: u32) -> i32;
+    }
+    const CP_UTF8: u32 = 65001;
+
+    // 0 without a console, e.g. when started from a service
+    let previous = unsafe { GetConsoleOutputCP() };
+    if previous == 0 || previous == CP_UTF8 {
+        return None;
+    }
+    match unsafe { SetConsoleOutputCP(CP_UTF8) } {
+        0 => {
+            log::debug!("could not switch the console to UTF-8");
+            None
+        }
+        _ => Some(previous),
+    }
+}
+
+#[cfg(not(windows))]
+fn set_utf8() -> Option<u32> {
+    None
+}
+
+#[cfg(windows)]
+fn restore(code_page: u32) {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetConsoleOutputCP(code_page: u32) -> i32;
+    }
+    unsafe { SetConsoleOutputCP(code_page) };
+}
+
+#[cfg(not(windows))]
+fn restore(_code_page: u32) {}
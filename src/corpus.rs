@@ -0,0 +1,201 @@
+use std::path::{Path, PathBuf};
+
+use log::{debug, trace};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::explain::{explanation, normalize_code};
+use crate::output::{self, OutputFormat};
+use crate::verify_dir;
+use crate::{collect_mods, unpack, TModError, UnpackOptions};
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct CorpusCase {
+    // relative to the corpus directory
+    pub archive: String,
+    // `ok` or an error code, `None` when the expected outcome is missing or
+    // can't be read
+    pub expected: Option<String>,
+    pub actual: String,
+    pub passed: bool,
+    pub problems: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct CorpusReport {
+    pub passed: usize,
+    pub failed: usize,
+    pub cases: Vec<CorpusCase>,
+}
+
+// extracts every mod below `dir` the way `unpack --verify` would, into a
+// scratch directory, and compares the outcome with the one next to it:
+//
+// - `<name>.expected` holds `ok` or the error code the extraction must fail
+//   with, e.g. `E0044` for a damaged hash; blank lines and `#` comments are
+//   ignored
+// - `<name>.entries.jsonl`, optional, is a manifest written by
+//   `--emit-entries`, which a successful extraction must match exactly
+pub fn run(dir: &Path, format: OutputFormat) -> Result<(), TModError> {
+    let mut archives = Vec::new();
+    collect_mods(dir, &mut archives)?;
+    archives.sort();
+    if archives.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("no .tmod files in {}", dir.display()),
+        )
+        .into());
+    }
+
+    let scratch = std::env::temp_dir().join(format!("tmod-corpus-{}", std::process::id()));
+    std::fs::create_dir_all(&scratch)?;
+    let cases = archives
+        .iter()
+        .enumerate()
+        .map(|(index, archive)| run_case(dir, archive, &scratch.join(index.to_string())))
+        .collect::<Vec<_>>();
+    let _ = std::fs::remove_dir_all(&scratch);
+
+    let failed = cases.iter().filter(|case| !case.passed).count();
+    let report = CorpusReport {
+        passed: cases.len() - failed,
+        failed,
+        cases,
+    };
+    print_report(&report, format)?;
+
+    if report.failed > 0 {
+        return Err(TModError::CorpusFailed(report.failed, report.cases.len()));
+    }
+    Ok(())
+}
+
+fn run_case(dir: &Path, archive: &Path, out_dir: &Path) -> CorpusCase {
+    let name = archive
+        .strip_prefix(dir)
+        .unwrap_or(archive)
+        .to_string_lossy()
+        .replace('\\', "/");
+    trace!("running corpus case: {}", name);
+
+    let mut problems = Vec::new();
+    let expected = match read_expected(&archive.with_extension("expected")) {
+        Ok(expected) => Some(expected),
+        Err(problem) => {
+            problems.push(problem);
+            None
+        }
+    };
+
+    let options = UnpackOptions {
+        yes: true,
+        verify: true,
+        no_progress: true,
+        quiet: true,
+        ..Default::default()
+    };
+    let result = unpack(
+        archive.to_string_lossy().into_owned(),
+        Some(out_dir.to_path_buf()),
+        &options,
+    );
+    let actual = match &result {
+        Ok(()) => "ok".to_string(),
+        Err(e) => e.code().to_string(),
+    };
+    debug!("{}: {}", name, actual);
+
+    if let Some(expected) = &expected {
+        if *expected != actual {
+            problems.push(match &result {
+                Ok(()) => format!("expected {}, but it extracted", expected),
+                Err(e) => format!("expected {}, got {}: {}", expected, actual, e),
+            });
+        } else if result.is_ok() {
+            problems.extend(compare_manifest(
+                &archive.with_extension("entries.jsonl"),
+                out_dir,
+            ));
+        }
+    }
+    let _ = std::fs::remove_dir_all(out_dir);
+
+    CorpusCase {
+        archive: name,
+        expected,
+        actual,
+        passed: problems.is_empty(),
+        problems,
+    }
+}
+
+// the outcome in an `.expected` file, as `ok` or a known error code
+fn read_expected(path: &Path) -> Result<String, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("no expected outcome in {}: {}", display_name(path), e))?;
+    let outcome = contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .ok_or_else(|| format!("{} is empty", display_name(path)))?;
+    if outcome == "ok" {
+        return Ok(outcome.to_string());
+    }
+    let code = normalize_code(outcome);
+    match explanation(&code) {
+        Some(_) => Ok(code),
+        None => Err(format!(
+            "`{}` in {} is neither `ok` nor an error code",
+            outcome,
+            display_name(path)
+        )),
+    }
+}
+
+// every difference between the extraction and the manifest, if there is one
+fn compare_manifest(path: &Path, out_dir: &Path) -> Vec<String> {
+    if !path.is_file() {
+        return Vec::new();
+    }
+    let report = verify_dir::load_manifest(path)
+        .and_then(|manifest| verify_dir::verify_dir(out_dir, &manifest));
+    match report {
+        Ok(report) => report
+            .changes
+            .iter()
+            .map(|change| format!("{}: {}", change.path, change.change.label()))
+            .collect(),
+        Err(e) => vec![format!("could not compare {}: {}", display_name(path), e)],
+    }
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(|| PathBuf::from(path), PathBuf::from)
+        .display()
+        .to_string()
+}
+
+fn print_report(report: &CorpusReport, format: OutputFormat) -> Result<(), TModError> {
+    if format != OutputFormat::Text {
+        println!("{}", output::render(format, "corpus", report)?);
+        return Ok(());
+    }
+    for case in &report.cases {
+        println!(
+            "{} {} ({})",
+            if case.passed { "pass" } else { "FAIL" },
+            case.archive,
+            case.actual
+        );
+        for problem in &case.problems {
+            println!("  {}", problem);
+        }
+    }
+    println!("{} passed, {} failed", report.passed, report.failed);
+    Ok(())
+}
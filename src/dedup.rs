@@ -0,0 +1,210 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "dedup")]
+use fastcdc::v2020::FastCDC;
+use log::{info, warn};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+
+use crate::output::{self, OutputFormat};
+use crate::{collect_mods, format_size, TModError, TModReader};
+
+// the limits of FastCDC's normalized chunking
+const MIN_AVG_SIZE: u32 = 256;
+const MAX_AVG_SIZE: u32 = 4 * 1024 * 1024;
+
+pub struct DedupOptions {
+    // the average chunk size to aim for, chunks are between a quarter and
+    // four times this
+    pub avg_size: u32,
+    pub format: OutputFormat,
+}
+
+impl Default for DedupOptions {
+    fn default() -> Self {
+        Self {
+            avg_size: 16 * 1024,
+            format: OutputFormat::Text,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct DedupReport {
+    pub mods: usize,
+    // mods that couldn't be read and are left out
+    pub skipped: Vec<String>,
+    pub entries: usize,
+    pub total_size: u64,
+    // storing every distinct entry once
+    pub unique_entries: usize,
+    pub unique_entry_size: u64,
+    // storing every distinct chunk once
+    pub avg_chunk_size: u32,
+    pub chunks: usize,
+    pub unique_chunks: usize,
+    pub unique_chunk_size: u64,
+}
+
+// estimates what a content-addressed store would save on a library of mods,
+// both with whole entries as the unit and with content-defined chunks. chunk
+// boundaries depend only on the bytes around them, so the same texture or
+// assembly section lines up across mods and versions even when what comes
+// before it changed.
+pub fn dedup(inputs: Vec<PathBuf>, options: &DedupOptions) -> Result<(), TModError> {
+    let report = dedup_report(&inputs, options)?;
+    if options.format == OutputFormat::Text {
+        print_report(&report);
+    } else {
+        println!("{}", output::render(options.format, "dedup", &report)?);
+    }
+    Ok(())
+}
+
+pub(crate) fn dedup_report(
+    inputs: &[PathBuf],
+    options: &DedupOptions,
+) -> Result<DedupReport, TModError> {
+    if !cfg!(feature = "dedup") {
+        return Err(TModError::FeatureDisabled("dedup"));
+    }
+    if !(MIN_AVG_SIZE..=MAX_AVG_SIZE).contains(&options.avg_size) {
+        return Err(TModError::InvalidNumber(
+            "--avg-size",
+            options.avg_size.to_string(),
+        ));
+    }
+
+    let mut paths = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            collect_mods(input, &mut paths)?;
+        } else {
+            paths.push(input.clone());
+        }
+    }
+    paths.sort();
+
+    let mut report = DedupReport {
+        mods: 0,
+        skipped: Vec::new(),
+        entries: 0,
+        total_size: 0,
+        unique_entries: 0,
+        unique_entry_size: 0,
+        avg_chunk_size: options.avg_size,
+        chunks: 0,
+        unique_chunks: 0,
+        unique_chunk_size: 0,
+    };
+    let mut entry_hashes = HashSet::new();
+    let mut chunk_hashes = HashSet::new();
+    for path in &paths {
+        info!("Chunking {}", path.display());
+        if let Err(e) = add_mod(
+            path,
+            options,
+            &mut report,
+            &mut entry_hashes,
+            &mut chunk_hashes,
+        ) {
+            warn!("skipping {}: {}", path.display(), e);
+            report.skipped.push(path.display().to_string());
+        }
+    }
+    Ok(report)
+}
+
+fn add_mod(
+    path: &Path,
+    options: &DedupOptions,
+    report: &mut DedupReport,
+    entry_hashes: &mut HashSet<[u8; 20]>,
+    chunk_hashes: &mut HashSet<[u8; 20]>,
+) -> Result<(), TModError> {
+    let mut reader = TModReader::open(&path.to_string_lossy())?;
+    // read everything first, so a broken mod doesn't count halfway
+    let mut contents = Vec::new();
+    for index in 0..reader.entries().len() {
+        contents.push(reader.read_entry(index)?);
+    }
+
+    report.mods += 1;
+    for data in &contents {
+        report.entries += 1;
+        report.total_size += data.len() as u64;
+        if entry_hashes.insert(Sha1::digest(data).into()) {
+            report.unique_entries += 1;
+            report.unique_entry_size += data.len() as u64;
+        }
+        for chunk in chunks(data, options.avg_size) {
+            report.chunks += 1;
+            if chunk_hashes.insert(Sha1::digest(chunk).into()) {
+                report.unique_chunks += 1;
+                report.unique_chunk_size += chunk.len() as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "dedup")]
+fn chunks(data: &[u8], avg_size: u32) -> impl Iterator<Item = &[u8]> {
+    FastCDC::new(data, avg_size / 4, avg_size, avg_size * 4)
+        .map(move |chunk| &data[chunk.offset..chunk.offset + chunk.length])
+}
+
+// `dedup` refuses to run in builds without the `dedup` feature
+#[cfg(not(feature = "dedup"))]
+fn chunks(_data: &[u8], _avg_size: u32) -> std::iter::Empty<&[u8]> {
+    std::iter::empty()
+}
+
+fn print_saving(label: &str, unique_size: u64, units: String, total_size: u64) {
+    let saved = total_size - unique_size;
+    let percent = match total_size {
+        0 => 0.0,
+        total => saved as f64 * 100.0 / total as f64,
+    };
+    println!(
+        "  {}: {} unique in {}, saves {} ({:.1}%)",
+        label,
+        format_size(unique_size),
+        units,
+        format_size(saved),
+        percent
+    );
+}
+
+fn print_report(report: &DedupReport) {
+    println!(
+        "Dedup estimate for {} mods, {} entries, {}",
+        report.mods,
+        report.entries,
+        format_size(report.total_size)
+    );
+    print_saving(
+        "whole entries",
+        report.unique_entry_size,
+        format!("{} entries", report.unique_entries),
+        report.total_size,
+    );
+    print_saving(
+        &format!("{} chunks", format_size(report.avg_chunk_size as u64)),
+        report.unique_chunk_size,
+        format!("{} of {} chunks", report.unique_chunks, report.chunks),
+        report.total_size,
+    );
+    if !report.skipped.is_empty() {
+        println!(
+            "{} mod(s) couldn't be read and were skipped",
+            report.skipped.len()
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use binary_rw::BinaryReader;
+use log::{debug, trace};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+
+use crate::localization::{self, Keys};
+use crate::output::{self, OutputFormat};
+use crate::source;
+use crate::text;
+use crate::verify_dir::Change;
+use crate::{read_entry_table, read_file_data, read_header, ModHeader, TModError};
+
+#[derive(Default)]
+pub struct DiffOptions {
+    // compare localization keys instead of entries, as CSV
+    pub localization: bool,
+    // where the CSV goes instead of stdout
+    pub output: Option<PathBuf>,
+    pub format: OutputFormat,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct EntryChange {
+    pub name: String,
+    pub change: Change,
+    pub old_len: Option<i32>,
+    pub new_len: Option<i32>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct DiffReport {
+    pub old: String,
+    pub new: String,
+    pub unchanged: usize,
+    pub changes: Vec<EntryChange>,
+}
+
+struct Archive {
+    header: ModHeader,
+    // entry name to uncompressed length and content hash
+    entries: BTreeMap<String, (i32, [u8; 20])>,
+    // language to keys, only filled in for `--localization`
+    localization: BTreeMap<&'static str, Keys>,
+}
+
+pub fn diff(old: String, new: String, options: &DiffOptions) -> Result<(), TModError> {
+    let old = load(&old, options.localization)?;
+    let new = load(&new, options.localization)?;
+
+    if options.localization {
+        return write_localization_diff(&old, &new, options.output.as_deref());
+    }
+
+    let mut unchanged = 0;
+    let mut changes = Vec::new();
+    for (name, (old_len, old_hash)) in &old.entries {
+        match new.entries.get(name) {
+            Some((_, new_hash)) if new_hash == old_hash => unchanged += 1,
+            Some((new_len, _)) => changes.push(EntryChange {
+                name: name.clone(),
+                change: Change::Changed,
+                old_len: Some(*old_len),
+                new_len: Some(*new_len),
+            }),
+            None => changes.push(EntryChange {
+                name: name.clone(),
+                change: Change::Removed,
+                old_len: Some(*old_len),
+                new_len: None,
+            }),
+        }
+    }
+    for (name, (new_len, _)) in &new.entries {
+        if !old.entries.contains_key(name) {
+            changes.push(EntryChange {
+                name: name.clone(),
+                change: Change::Added,
+                old_len: None,
+                new_len: Some(*new_len),
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let report = DiffReport {
+        old: format!("{} v{}", old.header.mod_name, old.header.mod_version),
+        new: format!("{} v{}", new.header.mod_name, new.header.mod_version),
+        unchanged,
+        changes,
+    };
+    if options.format != OutputFormat::Text {
+        println!("{}", output::render(options.format, "diff", &report)?);
+        return Ok(());
+    }
+
+    for changed in &report.changes {
+        let size = |len: Option<i32>| len.map_or("-".to_string(), |len| len.to_string());
+        println!(
+            "{:<8} {} ({} -> {} bytes)",
+            changed.change.label(),
+            changed.name,
+            size(changed.old_len),
+            size(changed.new_len)
+        );
+    }
+    let count = |change| report.changes.iter().filter(|c| c.change == change).count();
+    println!(
+        "{} -> {}: {} unchanged, {} changed, {} removed, {} added",
+        report.old,
+        report.new,
+        report.unchanged,
+        count(Change::Changed),
+        count(Change::Removed),
+        count(Change::Added)
+    );
+    Ok(())
+}
+
+fn load(path: &str, localization: bool) -> Result<Archive, TModError> {
+    trace!("opening file: {}", path);
+    let mut stream = source::open(path)?;
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+
+    let header = read_header(&mut reader, None)?;
+    let file_entries = read_entry_table(&mut reader)?;
+
+    let mut archive = Archive {
+        header,
+        entries: BTreeMap::new(),
+        localization: BTreeMap::new(),
+    };
+    for file in &file_entries {
+        let data = read_file_data(&mut reader, file)?;
+        archive.entries.insert(
+            file.name.clone(),
+            (file.uncompressed_len, Sha1::digest(&data).into()),
+        );
+
+        if !localization {
+            continue;
+        }
+        let location = match localization::localization_file(&file.name) {
+            Some(location) => location,
+            None => continue,
+        };
+        let decoded = text::decode(&file.name, &data)
+            .ok_or_else(|| TModError::BinaryEntry(file.name.clone()))?;
+        debug!("{} is {} localization", file.name, location.language);
+        localization::parse_keys(
+            &file.name,
+            location.prefix.as_deref(),
+            &decoded.text,
+            archive.localization.entry(location.language).or_default(),
+        )?;
+    }
+    Ok(archive)
+}
+
+// one row per added, removed or changed key: `language,key,change,old,new`
+fn write_localization_diff(
+    old: &Archive,
+    new: &Archive,
+    output: Option<&Path>,
+) -> Result<(), TModError> {
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => Box::new(std::io::BufWriter::new(std::fs::File::create(path)?)),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    writeln!(out, "language,key,change,old,new")?;
+
+    let empty = Keys::new();
+    let mut languages: Vec<&str> = old.localization.keys().copied().collect();
+    languages.extend(new.localization.keys().copied());
+    languages.sort_unstable();
+    languages.dedup();
+
+    let mut rows = 0;
+    for language in languages {
+        let old_keys = old.localization.get(language).unwrap_or(&empty);
+        let new_keys = new.localization.get(language).unwrap_or(&empty);
+        let mut keys: Vec<&String> = old_keys.keys().chain(new_keys.keys()).collect();
+        keys.sort_unstable();
+        keys.dedup();
+
+        for key in keys {
+            let old_value = old_keys.get(key).map(String::as_str);
+            let new_value = new_keys.get(key).map(String::as_str);
+            let change = match (old_value, new_value) {
+                (Some(old_value), Some(new_value)) if old_value == new_value => continue,
+                (Some(_), Some(_)) => Change::Changed,
+                (Some(_), None) => Change::Removed,
+                (None, Some(_)) => Change::Added,
+                (None, None) => continue,
+            };
+            writeln!(
+                out,
+                "{},{},{},{},{}",
+                language,
+                csv_field(key),
+                change.label(),
+                csv_field(old_value.unwrap_or("")),
+                csv_field(new_value.unwrap_or(""))
+            )?;
+            rows += 1;
+        }
+    }
+    out.flush()?;
+    debug!("{} changed localization keys", rows);
+    Ok(())
+}
+
+// quotes a field if it needs it, RFC 4180 style
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
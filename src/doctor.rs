@@ -0,0 +1,257 @@
+use std::path::Path;
+
+use log::trace;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::locate;
+use crate::output::{self, OutputFormat};
+use crate::tools;
+use crate::{format_size, TModError};
+
+// below this much free space on the target, extracting a large mod may fail
+const LOW_SPACE_THRESHOLD: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    Ok,
+    Warning,
+    Problem,
+}
+
+impl Status {
+    fn label(&self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Warning => "warning",
+            Status::Problem => "problem",
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    // what to do about it, for anything that isn't ok
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn not_ok(
+        name: &'static str,
+        status: Status,
+        detail: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+// checks the environment for the usual reasons extraction fails, using
+// `target` as the directory that would be extracted into
+pub fn doctor(target: &Path, format: OutputFormat) -> Result<(), TModError> {
+    // the target doesn't have to exist yet, its closest existing ancestor is
+    // where it would be created
+    let existing = target
+        .ancestors()
+        .find(|path| path.is_dir())
+        .unwrap_or_else(|| Path::new("."));
+    trace!("checking {:?} (for {:?})", existing, target);
+
+    let mut checks = vec![
+        check_long_paths(),
+        check_disk_space(existing),
+        check_writable(existing),
+        check_locale(),
+        check_tmodloader_folder(),
+    ];
+    checks.extend(check_tools());
+
+    if format != OutputFormat::Text {
+        println!("{}", output::render(format, "doctor", &checks)?);
+        return Ok(());
+    }
+
+    for check in &checks {
+        println!(
+            "{:<8} {}: {}",
+            check.status.label(),
+            check.name,
+            check.detail
+        );
+        if let Some(fix) = &check.fix {
+            println!("         fix: {}", fix);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn check_long_paths() -> Check {
+    const NAME: &str = "long paths";
+
+    let output = std::process::Command::new("reg")
+        .args([
+            "query",
+            r"HKLM\SYSTEM\CurrentControlSet\Control\FileSystem",
+            "/v",
+            "LongPathsEnabled",
+        ])
+        .output();
+    match output {
+        Ok(output) if String::from_utf8_lossy(&output.stdout).contains("0x1") => {
+            Check::ok(NAME, "long path support is enabled")
+        }
+        Ok(_) => Check::not_ok(
+            NAME,
+            Status::Warning,
+            "long path support is disabled, entries deeper than 260 characters will fail",
+            "set HKLM\\SYSTEM\\CurrentControlSet\\Control\\FileSystem\\LongPathsEnabled to 1, \
+             or extract closer to the drive root",
+        ),
+        Err(e) => Check::not_ok(
+            NAME,
+            Status::Warning,
+            format!("could not query the registry: {}", e),
+            "check LongPathsEnabled in the registry manually",
+        ),
+    }
+}
+
+#[cfg(not(windows))]
+fn check_long_paths() -> Check {
+    Check::ok("long paths", "no path length limit beyond the filesystem's")
+}
+
+fn check_disk_space(dir: &Path) -> Check {
+    const NAME: &str = "disk space";
+
+    match fs2::available_space(dir) {
+        Ok(available) if available < LOW_SPACE_THRESHOLD => Check::not_ok(
+            NAME,
+            Status::Warning,
+            format!("only {} free on {}", format_size(available), dir.display()),
+            "free up space or extract somewhere else",
+        ),
+        Ok(available) => Check::ok(
+            NAME,
+            format!("{} free on {}", format_size(available), dir.display()),
+        ),
+        Err(e) => Check::not_ok(
+            NAME,
+            Status::Warning,
+            format!("could not determine free space on {}: {}", dir.display(), e),
+            "check the free space manually",
+        ),
+    }
+}
+
+fn check_writable(dir: &Path) -> Check {
+    const NAME: &str = "write permission";
+
+    let probe = dir.join(format!(".tmod-unpacker-doctor-{}", std::process::id()));
+    match std::fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            Check::ok(NAME, format!("{} is writable", dir.display()))
+        }
+        Err(e) => Check::not_ok(
+            NAME,
+            Status::Problem,
+            format!("cannot write to {}: {}", dir.display(), e),
+            "pick an output directory you own, or fix its permissions",
+        ),
+    }
+}
+
+#[cfg(unix)]
+fn check_locale() -> Check {
+    const NAME: &str = "locale";
+
+    // the first of these that is set decides the character encoding
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty());
+    match locale {
+        Some(locale) if is_utf8_locale(&locale) => Check::ok(NAME, locale),
+        Some(locale) => Check::not_ok(
+            NAME,
+            Status::Warning,
+            format!(
+                "{} is not a UTF-8 locale, non-ASCII entry names may display wrongly",
+                locale
+            ),
+            "set LANG to a UTF-8 locale such as en_US.UTF-8",
+        ),
+        None => Check::not_ok(
+            NAME,
+            Status::Warning,
+            "no locale is set, non-ASCII entry names may display wrongly",
+            "set LANG to a UTF-8 locale such as en_US.UTF-8",
+        ),
+    }
+}
+
+#[cfg(unix)]
+fn is_utf8_locale(locale: &str) -> bool {
+    let locale = locale.to_lowercase();
+    locale.contains("utf-8") || locale.contains("utf8")
+}
+
+#[cfg(not(unix))]
+fn check_locale() -> Check {
+    Check::ok("locale", "file names are stored as UTF-16")
+}
+
+fn check_tmodloader_folder() -> Check {
+    const NAME: &str = "tModLoader folder";
+
+    match locate::mods_dirs().into_iter().find(|dir| dir.is_dir()) {
+        Some(dir) => Check::ok(NAME, format!("mods are in {}", dir.display())),
+        None => Check::not_ok(
+            NAME,
+            Status::Warning,
+            "no tModLoader Mods folder found in the usual places",
+            "pass .tmod files by path; tModLoader keeps them in its Mods folder \
+             or in steamapps/workshop/content/1281930",
+        ),
+    }
+}
+
+// the external programs some features need. a missing one is only a warning,
+// nothing else needs it.
+fn check_tools() -> Vec<Check> {
+    tools::ALL
+        .iter()
+        .map(|tool| match tool.find() {
+            Some(path) => Check::ok(tool.name, format!("found at {}", path.display())),
+            None => Check::not_ok(
+                tool.name,
+                Status::Warning,
+                format!("not found, {} won't work", tool.needed_for),
+                format!("install it, or {}", tool.instead),
+            ),
+        })
+        .collect()
+}
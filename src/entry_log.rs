@@ -0,0 +1,64 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+use crate::{ModFile, TModError};
+
+// one line of `--emit-entries` output, written as soon as the entry is done
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct EntryRecord {
+    pub name: String,
+    pub compressed_len: i32,
+    pub uncompressed_len: i32,
+    // SHA1 of the extracted (decompressed) contents
+    pub sha1: String,
+    pub output_path: String,
+    pub duration_ms: f64,
+}
+
+impl EntryRecord {
+    pub fn new(file: &ModFile, data: &[u8], output_path: &Path, duration: Duration) -> Self {
+        Self::with_sha1(file, hex::encode(Sha1::digest(data)), output_path, duration)
+    }
+
+    // for entries that were streamed, and hashed on the way through
+    pub fn with_sha1(file: &ModFile, sha1: String, output_path: &Path, duration: Duration) -> Self {
+        Self {
+            name: file.name.clone(),
+            compressed_len: file.compressed_len,
+            uncompressed_len: file.uncompressed_len,
+            sha1,
+            output_path: output_path.display().to_string(),
+            duration_ms: duration.as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+// a JSON lines stream flushed after every record so it can be tailed
+pub struct EntryLog {
+    out: BufWriter<File>,
+}
+
+impl EntryLog {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, TModError> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    pub fn write(&mut self, record: &EntryRecord) -> Result<(), TModError> {
+        serde_json::to_writer(&mut self.out, record)?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,568 @@
+// long-form explanations for every error code, shown by `explain <code>`
+
+pub fn explanation(code: &str) -> Option<&'static str> {
+    Some(match code {
+        "E0001" => {
+            "No input file was given.
+
+The first argument must be the path to the `.tmod` file to operate on, e.g.
+
+    tmod-unpacker MyMod.tmod out/
+
+Mods installed through the Steam Workshop live in the workshop content folder
+for tModLoader (app id 1281930); locally built mods are in the `Mods` folder
+of your tModLoader data directory."
+        }
+        "E0002" => {
+            "No output directory was given, and none could be derived from the input.
+
+Without a second argument, a mod is extracted into `<name>-extracted` next to
+the input file. That needs an input path with a file name; otherwise give the
+directory to extract into explicitly. It will be created if it does not exist:
+
+    tmod-unpacker MyMod.tmod out/"
+        }
+        "E0003" => {
+            "The `TMOD` magic bytes could not be found.
+
+Every tModLoader mod file begins with the four bytes `TMOD`. A small amount of
+junk in front of them (up to 64 KiB) is skipped with a warning, but if they
+aren't there at all the file is either not a mod or was damaged. Common causes
+are:
+
+  - a download that saved an HTML error page instead of the mod
+  - pointing the tool at a different file (the `.cs` sources, a zip, ...)
+
+Try downloading the mod again, and check that the file size looks plausible."
+        }
+        "E0004" => {
+            "Fewer file entries were read than the archive declares.
+
+The entry table at the start of the archive lists how many files it contains.
+Reading stopped before all of them were found, which means the table itself is
+damaged. Download the mod again."
+        }
+        "E0005" => {
+            "An I/O error occurred while reading the input or writing output.
+
+The message includes the underlying operating system error. Check that:
+
+  - the input file exists and is readable
+  - the output directory is writable and the disk is not full
+  - no other program holds the output files open (common on Windows)"
+        }
+        "E0006" => {
+            "The archive ended before all of its data could be read.
+
+This almost always means the file is truncated, most likely by an interrupted
+download or copy. Compare its size with the original and download it again."
+        }
+        "E0007" => {
+            "A name stored in the archive is not valid UTF-8.
+
+tModLoader always writes names as UTF-8, so this indicates a damaged archive or
+one written by a different tool. Download the mod again."
+        }
+        "E0008" => {
+            "An option that takes a value was given without one.
+
+Options such as `--rules` or `--source-map` must be followed by their value:
+
+    tmod-unpacker audit MyMod.tmod --rules rules.yml"
+        }
+        "E0009" => {
+            "An argument was not recognized.
+
+Run `tmod-unpacker --help` to see every subcommand and option. Note that
+options only apply to the subcommands they are listed for."
+        }
+        "E0010" => {
+            "The rules file could not be parsed.
+
+Rules files are YAML documents with a top-level `rules` list:
+
+    rules:
+      - name: discord-webhook
+        regex: \"https://discord(app)?\\\\.com/api/webhooks/\"
+        description: hardcoded Discord webhook
+      - name: upx-packed
+        bytes: \"55 50 58 21\"
+
+The message includes the line and column where parsing failed."
+        }
+        "E0011" => {
+            "A rule in the rules file is invalid.
+
+Each rule needs a `name` and exactly one of `regex` (a regular expression) or
+`bytes` (hex encoded bytes, spaces allowed). The message says what is wrong
+with the named rule, e.g. a regex syntax error or invalid hex."
+        }
+        "E0012" => {
+            "The entry table failed validation, so nothing was extracted.
+
+Before writing anything, every entry is checked for:
+
+  - names that are empty, absolute, or contain `..` (they would escape the
+    output directory)
+  - names deeper than `--max-depth` components (default 64) or with a
+    component longer than `--max-component-len` bytes (default 255, the
+    limit of most filesystems)
+  - negative lengths
+  - entries that unpack to more than `--max-size` bytes (default 1 GiB), or
+    to more than `--max-total-size` (default 8 GiB) all together, which is
+    how decompression bombs give themselves away
+  - lengths that need more file data than the archive contains, which means
+    it was truncated
+
+Each problem is printed above the error. A truncated archive should be
+downloaded again; strange names usually mean the mod was crafted by hand and
+should be treated with suspicion (see `tmod-unpacker audit`).
+
+Commands that only read the entry table or single entries, like `info`,
+`list`, `cat`, `view`, `stats` or `layout`, fail with this error too when an
+entry's length is negative, since the lengths can't be added up and no entry
+after it can be found. `tmod-unpacker check` lists which ones."
+        }
+        "E0013" => {
+            "The error code passed to `explain` does not exist.
+
+Error codes look like `E0006` and are printed alongside every error."
+        }
+        "E0014" => {
+            "JSON output could not be produced.
+
+This is a bug in tmod-unpacker; please report it along with the command you
+ran at https://github.com/campbellcole/tmod-unpacker/issues"
+        }
+        "E0015" => {
+            "The value given to `--format` is not a known output format.
+
+Reports that support structured output accept:
+
+  - `text`: human readable (the default)
+  - `json`: pretty printed JSON
+  - `nuon`: Nushell object notation, e.g. `tmod-unpacker audit x.tmod
+    --format nuon | from nuon`
+  - `powershell`: JSON inside an envelope object, for `ConvertFrom-Json`
+  - `github`: GitHub Actions annotations for `lint`, `check` and `audit`, JSON
+    for other reports"
+        }
+        "E0016" => {
+            "The option needs a cargo feature that this build was compiled without.
+
+Builds made with `--no-default-features` leave out optional functionality to
+stay small. Reinstall with the named feature enabled, e.g.
+
+    cargo install tmod-unpacker --features rules"
+        }
+        "E0017" => {
+            "The value given to `--order` is not a known extraction order.
+
+Entries can be extracted in:
+
+  - `table`: the order they are stored in (the default, and the fastest since
+    the archive is read front to back)
+  - `alpha`: alphabetically by name, so logs are easy to diff between runs
+  - `size-desc`: biggest first, to fail fast when the disk is nearly full
+  - `size-asc`: smallest first"
+        }
+        "E0018" => {
+            "An option that expects a number was given something else.
+
+Options such as `--sample` and `--seed` take a non-negative whole number:
+
+    tmod-unpacker MyMod.tmod out/ --sample 50"
+        }
+        "E0019" => {
+            "The archive has no entry with the given name.
+
+Entry names are matched exactly, including case, and use `/` as the
+separator, e.g. `Localization/en-US.hjson`. Use `tmod-unpacker audit` or
+extract the mod to see every name it contains."
+        }
+        "E0020" => {
+            "The entry looks like binary data, so it can't be shown as text.
+
+Text is decoded as UTF-8 (with or without a byte order mark) or UTF-16, with
+invalid UTF-8 replaced rather than rejected. Entries containing NUL bytes that
+aren't UTF-16 are treated as binary; extract the file to inspect it instead."
+        }
+        "E0021" => {
+            "A `--route` rule is malformed.
+
+Routes are written as `<glob>=><directory>`, e.g.
+
+    --route 'Sounds/**=>/mnt/bulk/sounds' --route '**=>./out'
+
+Quote them so the shell doesn't interpret `>` or the glob."
+        }
+        "E0022" => {
+            "A glob pattern is invalid.
+
+Globs match entry names using `/` as the separator: `*` matches within a
+single directory, `**` matches any number of directories, `?` matches one
+character and `[abc]` a set of characters. The message describes the exact
+syntax error."
+        }
+        "E0023" => {
+            "An entry matched none of the `--route` rules and there is no default
+output directory to fall back to.
+
+Either pass an output directory as well, which receives every entry no route
+matches, or end the routes with a catch-all such as `--route '**=>./out'`."
+        }
+        "E0024" => {
+            "An entry's compressed data could not be decompressed.
+
+Entries are stored as raw DEFLATE streams. This error means the data is
+corrupt or truncated, or it decompresses to more or fewer bytes than the
+entry table says it should. The archive is most likely damaged; re-download it. Trying a
+different backend with `--inflate` can help tell a damaged archive from a bug
+in one implementation."
+        }
+        "E0025" => {
+            "The backend given to `--inflate` is unknown or wasn't compiled in.
+
+`miniz` is always available. `zlib-ng` and `libdeflate` need the cargo
+features of the same name. `tmod-unpacker capabilities` lists the backends in
+this build."
+        }
+        "E0026" => {
+            "A file about to be extracted already exists as a symlink.
+
+A link in the output can point anywhere, including outside the output
+directory, so it isn't replaced without asking. This usually happens when re-extracting into a directory that
+was modified since, or that someone else can write to. Check where the link
+points, then remove it or pass `--force` to replace links with regular files."
+        }
+        "E0027" => {
+            "Registering or unregistering the `.tmod` file association failed.
+
+On Windows the association is written to the current user's registry with
+`reg`; on Linux a desktop entry and a MIME type are installed under
+`$XDG_DATA_HOME` (usually `~/.local/share`). Other platforms aren't supported.
+The message says which step failed, usually a missing tool or a permission
+problem."
+        }
+        "E0028" => {
+            "A `.rawimg` texture could not be decoded.
+
+tModLoader stores textures as `.rawimg`: a 12-byte header (format version 1,
+width, height, all little-endian 32-bit integers) followed by width * height
+RGBA pixels. The entry is either damaged, truncated, or written by a newer
+tModLoader with a format this tool doesn't know yet."
+        }
+        "E0029" => {
+            "A localization file isn't valid HJSON.
+
+Localization files are `.hjson` files named after a culture, such as
+`Localization/en-US.hjson`, `en-US_Mods.MyMod.hjson` or
+`en-US/Mods.MyMod.hjson`. tModLoader would refuse to load this one as well;
+the message points at the syntax error."
+        }
+        "E0030" => {
+            "The extraction took longer than `--timeout` or one entry took longer
+than `--entry-timeout`, so it was aborted.
+
+This is meant for unattended runs, where a pathological archive or a stuck
+network mount would otherwise hang forever. The run is cancelled like with
+Ctrl-C: the file in progress is removed instead of left incomplete, and so
+is an unfinished archive. The message says how many files were extracted
+and which one was in progress. A write the system itself is stuck in can't
+be interrupted, so a run on a hung mount only stops once that write returns.
+Like every error, a timeout exits with status 1."
+        }
+        "E0031" => {
+            "The extraction was cancelled, usually with Ctrl-C.
+
+Entries are written to a temporary `.<name>.partial` file and renamed into
+place once complete, and the entry being written when the extraction was
+cancelled is removed, so every file in the output directory is complete.
+Pressing Ctrl-C a second time exits immediately without cleaning up."
+        }
+        "E0032" => {
+            "Two options were given that contradict each other.
+
+For example, `--to-zip` and `--to-tar` write everything into one archive, so
+they can't be combined with an output directory or with `--route`, which both
+decide where files go on disk."
+        }
+        "E0033" => {
+            "A mod given as an http(s) url couldn't be fetched.
+
+The message includes the status or connection error. The whole mod is
+downloaded when the server doesn't support range requests; otherwise only the
+parts that are needed are requested as they are read, so a connection that
+drops partway through fails the command like a read error would."
+        }
+        "E0034" => {
+            "`lint` found problems at the `error` level.
+
+Every lint is a warning by default. `--deny <lint>` turns one into an error and
+`--deny warnings` turns them all into errors, which is what CI usually wants:
+
+    tmod-unpacker lint MyMod.tmod --deny warnings
+
+Like every error, a failed lint exits with status 1 so the build fails. Use
+`--allow <lint>` to silence a lint that doesn't apply to your mod."
+        }
+        "E0035" => {
+            "`--allow`, `--warn` or `--deny` named a lint that doesn't exist.
+
+The lints are `missing-icon`, `icon-size`, `uncompressed-png`, `large-file`,
+`localization-newline`, `uncompressed-audio` and `audio-bitrate`. `warnings` is also accepted by `--deny`, and applies to
+every lint that would otherwise warn."
+        }
+        "E0036" => {
+            "The name given to `scaffold --name` can't be used for a mod.
+
+tModLoader uses a mod's internal name as its C# namespace and in file names,
+so it may only contain ASCII letters, digits and underscores. The display name
+shown in game can be anything; change it in the generated `build.txt`."
+        }
+        "E0037" => {
+            "The output directory already exists and has files in it.
+
+`scaffold` creates a new mod from scratch and won't mix its files into an
+existing directory. Pick a different directory with `-o`, or remove the
+existing one first."
+        }
+        "E0038" => {
+            "`--copy` couldn't put the output on the system clipboard.
+
+The output is still printed. On Linux this needs a running X11 or Wayland
+session (it fails over SSH or in a bare console), and the copied text only
+outlives the command if a clipboard manager picks it up."
+        }
+        "E0039" => {
+            "Another invocation is already extracting into the same output directory
+or archive.
+
+Each run takes an advisory lock on its outputs (through a `.<name>.lock` file
+next to them) so overlapping runs, such as a cron job that started before the
+previous one finished, don't interleave their files. Pass `--wait-lock` to wait
+for the other run to finish instead of failing."
+        }
+        "E0040" => {
+            "`check` found the archive to be damaged.
+
+The report lists every problem: entries that don't decompress to their
+declared size, a data region that doesn't match the header's length or hash,
+or, with `--fast`, sizes that are impossible and DEFLATE block headers that
+are invalid. A fast check only catches damage at the start of an entry, so an
+archive that passes it can still fail a full check.
+
+Like a failed lint, a failed check exits with status 1."
+        }
+        "E0041" => {
+            "`pack` found a file that can't be stored in a .tmod.
+
+Entry names are stored as UTF-8, so every file name below the directory has
+to be valid UTF-8. Entry sizes are 32-bit, so no single file can be bigger
+than 2 GiB, and the whole mod has to stay under 4 GiB."
+        }
+        "E0042" => {
+            "`schema` was given a kind of output it doesn't know.
+
+Kinds are named after the subcommand that writes them, plus `manifest` for
+the records of `--emit-entries` and `stats-history` for `stats --history`.
+Run `schema` without arguments to list them all."
+        }
+        "E0043" => {
+            "A `--transform` couldn't be parsed.
+
+Transforms are written as `<glob>=<step>:<step>...`, e.g.
+
+    --transform 'Items/**/*.rawimg=png:unpremultiply:resize=32x32'
+
+The steps are `png` (write the texture as PNG), `unpremultiply` and
+`resize=<width>x<height>`, each at most once. Whatever order they are given
+in, they run as decode, unpremultiply, resize, encode."
+        }
+        "E0044" => {
+            "The SHA1 hash of the mod's data doesn't match the one stored in its header.
+
+tModLoader hashes everything after the header when it builds a mod, so a
+mismatch means the file changed afterwards, almost always through a truncated
+or corrupted download. The extracted files can't be trusted; download the mod
+again.
+
+Without `--verify` a mismatch is only a warning. Like a failed check, it exits
+with status 1."
+        }
+        "E0045" => {
+            "The mod's signature doesn't check out against tModLoader's mod browser key
+(with `--check-signature`) or the key given with `--pubkey`.
+
+Either the mod isn't signed at all (its signature is all zeroes, which is
+normal for mods built locally and most mods from the Steam Workshop), or it
+was signed with a different key, or its hash was changed after it was signed.
+
+tModLoader signs the hash stored in the header, not the data itself, so pass
+`--verify` as well to make sure the data matches that hash. Like a failed
+check, this exits with status 1."
+        }
+        "E0046" => {
+            "The file given to `--pubkey` doesn't hold a usable RSA public key.
+
+Keys are read in the XML form .NET (and tModLoader's source) uses:
+
+    <RSAKeyValue><Modulus>...</Modulus><Exponent>AQAB</Exponent></RSAKeyValue>
+
+with the modulus and exponent base64 encoded."
+        }
+        "E0047" => {
+            "An entry's name would have been written outside the output directory.
+
+Names that are absolute (`/etc/passwd`, `C:\\Windows\\...`), contain `..`
+components or a drive prefix are joined onto the output directory as they
+are, so they could overwrite any file the user can write to. tModLoader never
+builds mods like that; an archive with such names was made by hand, usually
+with bad intentions.
+
+The pre-flight check rejects these before anything is written. For forensics,
+`--allow-unsafe-paths` writes them where they point anyway; run it in a
+sandbox or container."
+        }
+        "E0048" => {
+            "`--git-init` couldn't commit the extracted files.
+
+The message includes what git printed. Check that:
+
+  - `git` is installed and on the PATH
+  - the output directory isn't inside a repository in a broken state (an
+    unfinished merge or rebase, a stale `index.lock`)
+
+The files have been extracted either way; only the commit is missing."
+        }
+        "E0049" => {
+            "`pack --reencode-audio` couldn't convert a sound to Ogg Vorbis.
+
+Encoding is done by an external program: `oggenc` (from vorbis-tools) is
+tried first, then `ffmpeg` (which needs to be built with libvorbis). Install
+either one and make sure it is on the PATH. If the message includes the
+encoder's output, the sound itself may be damaged or in a format the encoder
+doesn't read."
+        }
+        "E0050" => {
+            "Some of the mods given to `unpack` at once couldn't be extracted.
+
+Each failure was reported as it happened, with its own error code; the other
+mods were extracted into their `<ModName>_<Version>` directories as usual.
+Fix or leave out the failing files and run it again. Mods that were already
+extracted are simply extracted over."
+        }
+        "E0051" => {
+            "`unpack --mod <name>` didn't find a .tmod file with that name.
+
+The name is the mod's internal name, which is also the name of its .tmod
+file (e.g. `CalamityMod`, not \"Calamity Mod\"), and it is case-sensitive.
+The local Mods folder of tModLoader is searched, and the tModLoader workshop
+folder (`steamapps/workshop/content/1281930`) of every Steam library. If the
+mod is somewhere else, pass the .tmod file by path instead; `doctor` shows
+which Mods folder was found."
+        }
+        "E0052" => {
+            "This build has the `hardened` feature, and the sandbox it puts every
+extraction in couldn't be set up.
+
+Once the input is open and the output exists, landlock limits the process to
+the output directories and seccomp stops it from opening sockets or running
+programs. That only works on Linux (landlock needs 5.13 or newer, and is
+skipped on older kernels) and can fail in containers whose own seccomp
+profile blocks the `seccomp` system call. Run the extraction where that is
+allowed, or use a build without the feature."
+        }
+        "E0053" => {
+            "Some of the mods in a `corpus run` didn't have the expected outcome.
+
+Every mod in the corpus directory is extracted like `unpack --verify` would,
+and the result is compared with `<name>.expected`, which holds `ok` or the
+error code the extraction must fail with, and with `<name>.entries.jsonl`, a
+manifest written by `--emit-entries`, if there is one. Each failing case is
+listed with what went wrong. A mod without an `.expected` file fails too."
+        }
+        "E0054" => {
+            "`fetch` couldn't get the Steam Workshop item.
+
+The item is given by its id or a link to its workshop page (the number after
+`?id=`). A copy Steam already downloaded is used first; otherwise, in builds
+with the `http` feature, the Steam Web API is asked for a direct download,
+and if there is none, `steamcmd` downloads the item anonymously. Check that
+the id belongs to a tModLoader mod and that `steamcmd` is installed and on
+the PATH, or download the mod with Steam and pass the .tmod file instead."
+        }
+        "E0055" => {
+            "A file about to be extracted already exists.
+
+With `--no-clobber`, an extraction that would overwrite any existing file
+fails before writing anything, as does `--prompt` when there is no terminal
+to ask on. Extract into an empty directory, or choose what happens to
+existing files: `--force` overwrites them, `--skip-existing` keeps them and
+extracts only what is missing, and `--prompt` asks about each one."
+        }
+        "E0056" => {
+            "A feature that runs an external program was asked for, but the program
+isn't installed.
+
+`--git-init` runs `git`, `pack --reencode-audio` runs `oggenc` or `ffmpeg`,
+and `fetch` runs `steamcmd` for workshop items Steam hasn't downloaded. They
+are looked for on the PATH before anything is done. Install the program (or
+add its directory to the PATH), or do without the feature as the message
+says. `tmod-unpacker doctor` shows which of them are available."
+        }
+        "E0057" => {
+            "Some entries couldn't be extracted with `--keep-going`.
+
+Without `--keep-going`, the first entry that can't be read, decompressed or
+written stops the extraction. With it, the entry is skipped, the reason is
+printed, and the extraction carries on with the next entry, which starts
+where the entry table says the skipped one ends. Every other entry is
+written as usual, and the run fails with this error at the end so scripts
+notice. Entries usually fail because the mod is damaged: a corrupted deflate
+stream, a name that isn't valid UTF-8, or a file cut short. `tmod-unpacker
+layout` shows where the file stops making sense. The hash of a mod with
+failed entries isn't checked, since it can't match."
+        }
+        "E0058" => {
+            "The workspace file given to `pack --workspace` couldn't be used.
+
+A workspace is a `tmodpack.toml` (or the file given instead) that lists the
+mods to pack as `[[mod]]` tables, each with the `path` of its directory and
+optionally a `name` and `version`. Above the first `[[mod]]` go the settings
+every mod shares: `tml-version`, `output-dir`, `optimize-png`,
+`reencode-audio` and `shared`, a list of directories whose files every mod
+gets unless it has a file of the same name. Paths are relative to the file.
+
+Only this much of TOML is understood: strings, `true`/`false` and arrays of
+strings on one line, and comments. The message names the line at fault."
+        }
+        "E0059" => {
+            "An entry's data goes past the end of the file.
+
+The archive was cut short, almost always by an interrupted download, so the
+entry table promises more data than there is. Rather than hand out the entry
+padded with zeroes, reading it fails. Download the mod again; `tmod-unpacker
+check` lists every entry that is affected."
+        }
+        "E0060" => {
+            "An entry, or the mod as a whole, unpacks to more than the size limits allow.
+
+Entries are inflated into memory, so the size the entry table claims is checked
+before any of it is: a crafted table could otherwise ask for gigabytes from a
+tiny file. `unpack` takes `--max-size` and `--max-total-size` to raise the
+limits for mods that really are this big; library callers pass their own
+`SizeLimits` to `TModReader::with_limits`."
+        }
+        _ => return None,
+    })
+}
+
+// accepts `E0006`, `e0006` and `6`
+pub fn normalize_code(code: &str) -> String {
+    let digits = code.trim_start_matches(['E', 'e']);
+    match digits.parse::<u32>() {
+        Ok(number) => format!("E{:04}", number),
+        Err(_) => code.to_string(),
+    }
+}
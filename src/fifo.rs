@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use crate::cancel::CancellationToken;
+use crate::filter::Filter;
+use crate::TModError;
+
+// `--fifo-dir`: instead of writing the entries, creates a named pipe for each
+// selected one and fills it once something opens it for reading, so a
+// consumer can stream a huge asset without it ever being written to disk.
+// every pipe is served once and removed as soon as its reader has it open;
+// the run ends when all of them have been read, or on Ctrl-C, which removes
+// the ones nobody read.
+#[cfg(unix)]
+pub fn serve(
+    input: &str,
+    dir: &Path,
+    filter: &Filter,
+    cancel: &CancellationToken,
+) -> Result<(), TModError> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt, path::PathBuf, thread, time::Duration};
+
+    use log::{debug, trace};
+
+    use crate::names::unsafe_reason;
+    use crate::TModReader;
+
+    // how often pipes without a reader are checked again
+    const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    // removes the pipes nobody read, however the run ends
+    struct Pending(Vec<(usize, PathBuf)>);
+
+    impl Drop for Pending {
+        fn drop(&mut self) {
+            for (_, path) in &self.0 {
+                trace!("removing unread FIFO: {:?}", path);
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    let mut reader = TModReader::open_mapped(input)?;
+    let mut pending = Pending(Vec::new());
+    for (index, file) in reader.entries().iter().enumerate() {
+        if !filter.matches(&file.name) {
+            continue;
+        }
+        if let Some(reason) = unsafe_reason(&file.name) {
+            return Err(TModError::UnsafePath(file.name.clone(), reason));
+        }
+        let path = dir.join(&file.name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        if unsafe { libc::mkfifo(c_path.as_ptr(), 0o644) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        pending.0.push((index, path));
+    }
+    println!(
+        "{} FIFOs in {}, each is filled when it is first read",
+        pending.0.len(),
+        dir.display()
+    );
+
+    let mut writers = Vec::new();
+    while !pending.0.is_empty() {
+        if cancel.is_cancelled() {
+            return Err(TModError::Cancelled);
+        }
+        let mut opened = false;
+        let mut i = 0;
+        while i < pending.0.len() {
+            let file = match open_for_writing(&pending.0[i].1) {
+                Ok(Some(file)) => file,
+                Ok(None) => {
+                    i += 1;
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            opened = true;
+            let (index, path) = pending.0.swap_remove(i);
+            // the reader has it open, so it doesn't need the name anymore
+            std::fs::remove_file(&path)?;
+            let name = reader.entries()[index].name.clone();
+            let data = reader.read_entry(index)?;
+            debug!("serving {} ({} bytes)", name, data.len());
+            // a slow reader shouldn't hold up the others
+            writers.push(thread::spawn(move || write_all(file, &name, &data)));
+        }
+        if !opened {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+    for writer in writers {
+        writer.join().expect("FIFO writer panicked")?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn serve(
+    _input: &str,
+    _dir: &Path,
+    _filter: &Filter,
+    _cancel: &CancellationToken,
+) -> Result<(), TModError> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "FIFOs are only supported on Unix",
+    )
+    .into())
+}
+
+// the write end of a FIFO, or `None` while nothing has it open for reading
+#[cfg(unix)]
+fn open_for_writing(path: &Path) -> std::io::Result<Option<std::fs::File>> {
+    use std::{fs::OpenOptions, os::unix::fs::OpenOptionsExt, os::unix::io::AsRawFd};
+
+    // without O_NONBLOCK, opening waits for a reader; with it, it fails
+    // right away instead
+    match OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+    {
+        Ok(file) => {
+            // the writes themselves should wait for the reader
+            let fd = file.as_raw_fd();
+            let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+            if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) } < 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(Some(file))
+        }
+        Err(e) if e.raw_os_error() == Some(libc::ENXIO) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// a reader that stops early (`head`, say) isn't an error
+#[cfg(unix)]
+fn write_all(mut file: std::fs::File, name: &str, data: &[u8]) -> Result<(), TModError> {
+    use std::io::Write;
+
+    match file.write_all(data) {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {
+            log::debug!("the reader of {} closed it early", name);
+            Ok(())
+        }
+        result => Ok(result?),
+    }
+}
@@ -0,0 +1,58 @@
+use crate::route::{compile_glob, GlobMatcher};
+use crate::TModError;
+
+// `--include` and `--exclude`: which entries to extract at all. an entry is
+// extracted when it matches any include (or there are none) and no exclude.
+#[derive(Default)]
+pub struct Filter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+// a pattern without a `/` also matches the file name alone, so `*.dll`
+// finds assemblies in any directory
+struct Pattern {
+    matcher: GlobMatcher,
+    file_name_only: bool,
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> Result<Self, TModError> {
+        Ok(Self {
+            matcher: compile_glob(pattern)?,
+            file_name_only: !pattern.contains('/'),
+        })
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.matcher.is_match(name)
+            || (self.file_name_only
+                && self
+                    .matcher
+                    .is_match(name.rsplit('/').next().unwrap_or(name)))
+    }
+}
+
+impl Filter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, TModError> {
+        let parse = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| Pattern::parse(pattern))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Self {
+            include: parse(include)?,
+            exclude: parse(exclude)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.matches(name)))
+            && !self.exclude.iter().any(|p| p.matches(name))
+    }
+}
@@ -0,0 +1,137 @@
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use base64::Engine;
+use binary_rw::BinaryReader;
+use log::{info, trace, warn};
+
+use crate::names::extension;
+use crate::source;
+use crate::{format_size, rawimg, read_entry_table, read_file_data, read_header, TModError};
+
+struct Texture {
+    name: String,
+    width: u32,
+    height: u32,
+    png: Vec<u8>,
+}
+
+// writes a self-contained HTML page with every texture in the mod, grouped by
+// directory. `.rawimg` textures are converted back to PNG.
+pub fn gallery(path: String, output: &Path) -> Result<(), TModError> {
+    trace!("opening file: {}", path);
+    let mut stream = source::open(&path)?;
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+
+    let header = read_header(&mut reader, None)?;
+    let file_entries = read_entry_table(&mut reader)?;
+
+    info!("Collecting textures");
+    let mut directories: BTreeMap<String, Vec<Texture>> = BTreeMap::new();
+    for file in &file_entries {
+        let data = read_file_data(&mut reader, file)?;
+        let texture = match extension(&file.name).as_deref() {
+            Some("png") => match png_size(&data) {
+                Some((width, height)) => Texture {
+                    name: file.name.clone(),
+                    width,
+                    height,
+                    png: data,
+                },
+                None => {
+                    warn!("skipping {}: not a valid PNG", file.name);
+                    continue;
+                }
+            },
+            Some("rawimg") => {
+                let image = match rawimg::parse(&file.name, &data) {
+                    Ok(image) => image,
+                    Err(e) => {
+                        warn!("skipping {}", e);
+                        continue;
+                    }
+                };
+                Texture {
+                    name: file.name.clone(),
+                    width: image.width,
+                    height: image.height,
+                    png: image.to_png()?,
+                }
+            }
+            _ => continue,
+        };
+        let directory = match texture.name.rfind('/') {
+            Some(split) => texture.name[..split].to_string(),
+            None => String::new(),
+        };
+        directories.entry(directory).or_default().push(texture);
+    }
+
+    let count: usize = directories.values().map(Vec::len).sum();
+    let title = format!("{} v{}", header.mod_name, header.mod_version);
+    let html = render(&title, &directories);
+    std::fs::write(output, &html)?;
+    println!(
+        "{}: {} textures in {} directories, {} written to {}",
+        title,
+        count,
+        directories.len(),
+        format_size(html.len() as u64),
+        output.display()
+    );
+    Ok(())
+}
+
+fn render(title: &str, directories: &BTreeMap<String, Vec<Texture>>) -> String {
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        escape(title),
+        STYLE,
+        escape(title)
+    );
+    for (directory, textures) in directories {
+        let heading = if directory.is_empty() { "/" } else { directory };
+        let _ = writeln!(html, "<h2>{}</h2>\n<div class=\"grid\">", escape(heading));
+        for texture in textures {
+            let file_name = texture.name.rsplit('/').next().unwrap_or(&texture.name);
+            let _ = writeln!(
+                html,
+                "<figure title=\"{name}\"><a href=\"data:image/png;base64,{data}\" download=\"{file}\"><img src=\"data:image/png;base64,{data}\" alt=\"{name}\"></a><figcaption>{file}<br>{w}&times;{h}</figcaption></figure>",
+                name = escape(&texture.name),
+                file = escape(file_name),
+                data = base64::engine::general_purpose::STANDARD.encode(&texture.png),
+                w = texture.width,
+                h = texture.height
+            );
+        }
+        html.push_str("</div>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+// terraria sprites are tiny pixel art, so scale them up without smoothing
+const STYLE: &str = "body { font-family: sans-serif; background: #1e1e1e; color: #ddd; }
+.grid { display: flex; flex-wrap: wrap; gap: 12px; }
+figure { margin: 0; width: 136px; text-align: center; font-size: 12px; word-break: break-all; }
+img { width: 128px; height: 128px; object-fit: contain; image-rendering: pixelated;
+  background: repeating-conic-gradient(#444 0 25%, #333 0 50%) 0 0 / 16px 16px; }
+";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// the size from a PNG's IHDR chunk, which always comes first
+pub fn png_size(data: &[u8]) -> Option<(u32, u32)> {
+    if data.len() < 24 || &data[..8] != b"\x89PNG\r\n\x1a\n" || &data[12..16] != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes([data[16], data[17], data[18], data[19]]);
+    let height = u32::from_be_bytes([data[20], data[21], data[22], data[23]]);
+    Some((width, height))
+}
@@ -0,0 +1,216 @@
+use std::{io::Write, path::PathBuf};
+
+use flate2::{write::DeflateEncoder, Compression};
+use log::{info, trace, warn};
+
+use crate::cancel::{self, CancellationToken};
+use crate::pack::{compress, ArchiveWriter};
+use crate::sample::SplitMix64;
+use crate::{format_size, TModError};
+
+// folders and extensions like the ones a real mod has, so the names look the
+// part and per-extension handling (PNGs aren't compressed) kicks in
+const FOLDERS: &[&str] = &[
+    "Items",
+    "NPCs",
+    "Projectiles",
+    "Tiles",
+    "Buffs",
+    "Sounds",
+    "Localization",
+];
+const EXTENSIONS: &[&str] = &["rawimg", "png", "cs", "hjson", "ogg", "txt"];
+const WORDS: &[&str] = &[
+    "the", "slime", "boss", "sword", "drops", "when", "moon", "lord", "is", "defeated", "and",
+    "tile", "frame", "of", "a", "crafted", "at", "an", "anvil", "with", "bars",
+];
+
+// what the header says the mod was built for, a recent stable release
+const TMODLOADER_VERSION: &str = "2022.9.47.0";
+
+// how deep the `deep-paths` entry is nested, deep enough for the full path
+// to be over Windows' 260 character limit
+const DEEP_PATH_DEPTH: usize = 64;
+
+// the size of the `huge-entries` entry. it is all zeroes, so it is tiny once
+// compressed and only gets big when it is extracted.
+const HUGE_ENTRY_SIZE: usize = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeDistribution {
+    // as many entries of every size
+    Uniform,
+    // as many entries of every order of magnitude, so mostly small ones and a
+    // few big ones, like a real mod
+    Log,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pathology {
+    DeepPaths,
+    DupNames,
+    HugeEntries,
+}
+
+impl Pathology {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "deep-paths" => Some(Pathology::DeepPaths),
+            "dup-names" => Some(Pathology::DupNames),
+            "huge-entries" => Some(Pathology::HugeEntries),
+            _ => None,
+        }
+    }
+}
+
+pub struct GenOptions {
+    pub output: PathBuf,
+    pub entries: usize,
+    pub seed: u64,
+    pub min_size: usize,
+    pub max_size: usize,
+    pub distribution: SizeDistribution,
+    // entries added on top of the others, to see how a tool copes
+    pub pathologies: Vec<Pathology>,
+}
+
+// writes a valid mod with made-up contents, for benchmarks and for testing
+// tools that read mods. everything comes from the seed, so the same options
+// always give a byte-identical file. half of the entries are text that
+// compresses well and half are random bytes that don't, so both stored and
+// compressed entries show up.
+pub fn generate(options: &GenOptions) -> Result<(), TModError> {
+    // an entry's length is an i32 in the entry table
+    if options.max_size > i32::MAX as usize {
+        return Err(TModError::InvalidNumber(
+            "--max-size",
+            options.max_size.to_string(),
+        ));
+    }
+    let mut rng = SplitMix64::new(options.seed);
+    let max_size = options.max_size.max(options.min_size);
+
+    let mut entries = Vec::with_capacity(options.entries + 4);
+    for index in 0..options.entries {
+        let folder = FOLDERS[below(&mut rng, FOLDERS.len())];
+        let extension = EXTENSIONS[below(&mut rng, EXTENSIONS.len())];
+        let name = format!("Content/{}/{}{}.{}", folder, folder, index, extension);
+        let size = entry_size(&mut rng, options.min_size, max_size, options.distribution);
+        entries.push((name, contents(&mut rng, size)));
+    }
+
+    let mut huge = false;
+    for pathology in &options.pathologies {
+        match pathology {
+            Pathology::DeepPaths => {
+                let dirs: Vec<String> = (0..DEEP_PATH_DEPTH)
+                    .map(|level| format!("level{:02}", level))
+                    .collect();
+                entries.push((format!("{}/deep.txt", dirs.join("/")), text(&mut rng, 64)));
+            }
+            Pathology::DupNames => {
+                // the same name twice, and two that only differ in case,
+                // which collide on Windows and macOS
+                entries.push(("Content/Dup.txt".to_string(), text(&mut rng, 64)));
+                entries.push(("Content/Dup.txt".to_string(), text(&mut rng, 64)));
+                entries.push(("Content/Case/Dup.txt".to_string(), text(&mut rng, 64)));
+                entries.push(("Content/case/dup.txt".to_string(), text(&mut rng, 64)));
+            }
+            Pathology::HugeEntries => huge = true,
+        }
+    }
+
+    let name = format!("Generated{}", options.seed);
+    let count = entries.len() + huge as usize;
+    let mut writer = ArchiveWriter::new(&name, "1.0", count);
+    let mut total = 0;
+    for (entry_name, contents) in &entries {
+        trace!(
+            "generating entry: {} ({} bytes)",
+            entry_name,
+            contents.len()
+        );
+        let stored = compress(entry_name, contents)?;
+        writer.add(
+            entry_name,
+            contents.len() as i32,
+            stored.as_deref().unwrap_or(contents),
+        );
+        total += contents.len() as u64;
+    }
+    if huge {
+        // compressed as it is generated, so it is never in memory as a whole
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        let chunk = vec![0; 1024 * 1024];
+        for _ in 0..HUGE_ENTRY_SIZE / chunk.len() {
+            encoder.write_all(&chunk)?;
+        }
+        writer.add(
+            "Content/Huge.bin",
+            HUGE_ENTRY_SIZE as i32,
+            &encoder.finish()?,
+        );
+        total += HUGE_ENTRY_SIZE as u64;
+    }
+    let file = writer.finish(TMODLOADER_VERSION, &options.output)?;
+
+    if options.output.exists() {
+        warn!("overwriting {:?}", options.output);
+    }
+    cancel::write_atomically(&options.output, &file, &CancellationToken::default())?;
+    info!("Generated {:?} with seed {}", options.output, options.seed);
+    println!(
+        "{} v1.0: {} entries, {} ({} on disk)",
+        name,
+        count,
+        format_size(total),
+        format_size(file.len() as u64)
+    );
+    Ok(())
+}
+
+// a number in `0..n`
+fn below(rng: &mut SplitMix64, n: usize) -> usize {
+    (rng.next() % n as u64) as usize
+}
+
+fn entry_size(
+    rng: &mut SplitMix64,
+    min: usize,
+    max: usize,
+    distribution: SizeDistribution,
+) -> usize {
+    match distribution {
+        SizeDistribution::Uniform => min + below(rng, max - min + 1),
+        SizeDistribution::Log => {
+            // uniform between the logarithms of both ends, shifted by one so
+            // zero works
+            let (low, high) = (((min + 1) as f64).ln(), ((max + 1) as f64).ln());
+            let fraction = (rng.next() >> 11) as f64 / (1u64 << 53) as f64;
+            let size = ((low + (high - low) * fraction).exp() as usize).saturating_sub(1);
+            size.clamp(min, max)
+        }
+    }
+}
+
+fn contents(rng: &mut SplitMix64, size: usize) -> Vec<u8> {
+    if rng.next() % 2 == 0 {
+        return text(rng, size);
+    }
+    let mut data = Vec::with_capacity(size + 8);
+    while data.len() < size {
+        data.extend_from_slice(&rng.next().to_le_bytes());
+    }
+    data.truncate(size);
+    data
+}
+
+fn text(rng: &mut SplitMix64, size: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(size + 16);
+    while data.len() < size {
+        data.extend_from_slice(WORDS[below(rng, WORDS.len())].as_bytes());
+        data.push(if below(rng, 12) == 0 { b'\n' } else { b' ' });
+    }
+    data.truncate(size);
+    data
+}
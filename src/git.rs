@@ -0,0 +1,109 @@
+use std::{
+    path::Path,
+    process::{Command, Output},
+};
+
+use log::{info, trace};
+
+use crate::{ModHeader, TModError};
+
+// media that bloats a repository's history, tracked through Git LFS
+const LFS_EXTENSIONS: &[&str] = &["png", "rawimg", "ogg", "wav", "mp3", "xnb", "dll", "pdb"];
+
+// written to the output directory the first time, and left alone after that
+// so they can be edited
+fn gitattributes() -> String {
+    // never touch line endings, the files are exactly what the mod contains
+    let mut attributes = String::from("* -text\n");
+    for ext in LFS_EXTENSIONS {
+        attributes.push_str(&format!("*.{} filter=lfs diff=lfs merge=lfs -text\n", ext));
+    }
+    attributes
+}
+
+const GITIGNORE: &str = "\
+# left behind by interrupted extractions
+.*.partial
+.tmod-unpacker.lock
+# generated by --thumbnails
+/_thumbs/
+";
+
+// `--git-init`: makes the output directory a repository (unless it already
+// is one) before anything is extracted into it. `commit_extraction` then
+// commits what was extracted, so extracting every release of a mod into the
+// same directory builds up its history.
+pub fn init(out_dir: &Path) -> Result<(), TModError> {
+    if !out_dir.join(".git").exists() {
+        info!("Initializing a git repository in {:?}", out_dir);
+        git(out_dir, &["init", "--quiet"])?;
+    }
+    for (name, contents) in [
+        (".gitattributes", gitattributes()),
+        (".gitignore", GITIGNORE.to_string()),
+    ] {
+        let path = out_dir.join(name);
+        if !path.exists() {
+            std::fs::write(&path, contents)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn commit_extraction(out_dir: &Path, header: &ModHeader) -> Result<(), TModError> {
+    git(out_dir, &["add", "--all"])?;
+    if run(out_dir, &["diff", "--cached", "--quiet"])?
+        .status
+        .success()
+    {
+        println!("Nothing changed since the last commit");
+        return Ok(());
+    }
+
+    let subject = format!("{} v{}", header.mod_name, header.mod_version);
+    let body = format!(
+        "tModLoader: {}\nHash: {}\nFingerprint: {}",
+        header.tmodloader_version,
+        hex::encode(&header.hash),
+        header.fingerprint()
+    );
+    let mut args = Vec::new();
+    // a commit needs an identity, which a fresh machine or container may not
+    // have configured
+    if !run(out_dir, &["config", "user.email"])?.status.success() {
+        args.extend([
+            "-c",
+            "user.name=tmod-unpacker",
+            "-c",
+            "user.email=tmod-unpacker@localhost",
+        ]);
+    }
+    args.extend(["commit", "--quiet", "-m", &subject, "-m", &body]);
+    git(out_dir, &args)?;
+    println!("Committed {} to {}", subject, out_dir.display());
+    Ok(())
+}
+
+fn run(dir: &Path, args: &[&str]) -> Result<Output, TModError> {
+    trace!("running git {:?}", args);
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| TModError::GitError(format!("could not run git: {}", e)))
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<(), TModError> {
+    let output = run(dir, args)?;
+    if !output.status.success() {
+        return Err(TModError::GitError(format!(
+            "git {} failed: {}",
+            args.iter()
+                .find(|arg| !arg.starts_with('-') && !arg.contains('='))
+                .unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::TModError;
+
+// entries are raw DEFLATE streams (no zlib header), and the table tells us
+// the exact uncompressed size up front
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Miniz,
+    #[cfg(feature = "zlib-ng")]
+    ZlibNg,
+    #[cfg(feature = "libdeflate")]
+    Libdeflate,
+}
+
+impl Backend {
+    pub fn parse(value: &str) -> Result<Self, TModError> {
+        Self::available()
+            .into_iter()
+            .find(|backend| backend.label() == value)
+            .ok_or_else(|| TModError::UnknownBackend(value.to_string()))
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Backend::Miniz => "miniz",
+            #[cfg(feature = "zlib-ng")]
+            Backend::ZlibNg => "zlib-ng",
+            #[cfg(feature = "libdeflate")]
+            Backend::Libdeflate => "libdeflate",
+        }
+    }
+
+    // every backend compiled into this build, slowest first
+    pub fn available() -> Vec<Self> {
+        vec![
+            Backend::Miniz,
+            #[cfg(feature = "zlib-ng")]
+            Backend::ZlibNg,
+            #[cfg(feature = "libdeflate")]
+            Backend::Libdeflate,
+        ]
+    }
+
+    fn index(&self) -> u8 {
+        Self::available()
+            .iter()
+            .position(|backend| backend == self)
+            .unwrap_or(0) as u8
+    }
+
+    pub fn inflate(&self, data: &[u8], uncompressed_len: usize) -> Result<Vec<u8>, TModError> {
+        match self {
+            Backend::Miniz => {
+                miniz_oxide::inflate::decompress_to_vec_with_limit(data, uncompressed_len)
+                    .map_err(|e| TModError::InflateError(self.label(), format!("{:?}", e.status)))
+            }
+            #[cfg(feature = "zlib-ng")]
+            Backend::ZlibNg => {
+                use std::io::Read;
+
+                // one byte more than expected is enough to tell it inflates
+                // to more, without inflating all of it
+                let mut decoder =
+                    flate2::read::DeflateDecoder::new(data).take(uncompressed_len as u64 + 1);
+                let mut out = Vec::with_capacity(uncompressed_len);
+                decoder.read_to_end(&mut out)?;
+                if out.len() > uncompressed_len {
+                    return Err(TModError::InflateError(
+                        self.label(),
+                        "more data than expected".to_string(),
+                    ));
+                }
+                Ok(out)
+            }
+            #[cfg(feature = "libdeflate")]
+            Backend::Libdeflate => {
+                let mut out = vec![0; uncompressed_len];
+                let written = libdeflater::Decompressor::new()
+                    .deflate_decompress(data, &mut out)
+                    .map_err(|e| TModError::InflateError(self.label(), format!("{:?}", e)))?;
+                out.truncate(written);
+                Ok(out)
+            }
+        }
+    }
+}
+
+impl Default for Backend {
+    // the fastest one compiled in
+    fn default() -> Self {
+        *Self::available().last().unwrap()
+    }
+}
+
+// the backend used for every entry read in this process; 0xff means the
+// default hasn't been overridden with `--inflate`
+static SELECTED: AtomicU8 = AtomicU8::new(u8::MAX);
+
+pub fn select(backend: Backend) {
+    SELECTED.store(backend.index(), Ordering::Relaxed);
+}
+
+pub fn selected() -> Backend {
+    let index = SELECTED.load(Ordering::Relaxed) as usize;
+    Backend::available().get(index).copied().unwrap_or_default()
+}
+
+// DEFLATE can't do better than about 1032:1
+const MAX_RATIO: u64 = 1032;
+
+// bytes of a stream `check_header` looks at
+pub const HEADER_LEN: usize = 8;
+
+// a quick plausibility check of a stream without decompressing it: the sizes
+// have to be possible, and the first block has to have a valid header. stored
+// blocks are checked completely, since they carry their own length.
+pub fn check_header(
+    header: &[u8],
+    compressed_len: usize,
+    uncompressed_len: usize,
+) -> Result<(), String> {
+    if compressed_len > uncompressed_len {
+        return Err(format!(
+            "compressed to {} bytes, more than its {} bytes of contents",
+            compressed_len, uncompressed_len
+        ));
+    }
+    if uncompressed_len as u64 > compressed_len as u64 * MAX_RATIO {
+        return Err(format!(
+            "{} bytes can't decompress to {} bytes",
+            compressed_len, uncompressed_len
+        ));
+    }
+    let first = *header.first().ok_or("empty stream")?;
+    let last_block = first & 1 == 1;
+    match (first >> 1) & 0b11 {
+        0 => {
+            // the length and its complement follow at the next byte boundary
+            let field = |at: usize| {
+                header
+                    .get(at..at + 2)
+                    .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            };
+            let (len, nlen) = match (field(1), field(3)) {
+                (Some(len), Some(nlen)) => (len, nlen),
+                _ => return Err("stored block header is truncated".to_string()),
+            };
+            if len != !nlen {
+                return Err("stored block length doesn't match its complement".to_string());
+            }
+            if len as usize > uncompressed_len || (last_block && len as usize != uncompressed_len) {
+                return Err(format!(
+                    "stored block of {} bytes in an entry of {} bytes",
+                    len, uncompressed_len
+                ));
+            }
+        }
+        1 => {}
+        2 => {
+            let bits = u32::from_le_bytes([
+                first,
+                *header.get(1).unwrap_or(&0),
+                *header.get(2).unwrap_or(&0),
+                0,
+            ]);
+            let literal_codes = ((bits >> 3) & 0b11111) + 257;
+            let distance_codes = ((bits >> 8) & 0b11111) + 1;
+            if literal_codes > 286 || distance_codes > 30 {
+                return Err(format!(
+                    "invalid dynamic block header ({} literal, {} distance codes)",
+                    literal_codes, distance_codes
+                ));
+            }
+        }
+        _ => return Err("first block has the reserved block type".to_string()),
+    }
+    Ok(())
+}
@@ -0,0 +1,261 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    io::{self, Write as _},
+};
+
+use binary_rw::BinaryReader;
+use log::trace;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::console;
+use crate::output::{self, OutputFormat};
+use crate::{format_size, read_entry_table, read_header, source, ModFile, ModHeader, TModError};
+
+#[derive(Default)]
+pub struct InfoOptions {
+    pub format: OutputFormat,
+    // also put the output on the clipboard
+    pub copy: bool,
+    // `list` prints every directory once, with its entries indented below
+    pub compact: bool,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ModInfo {
+    pub name: String,
+    pub version: String,
+    pub tmodloader_version: String,
+    pub fingerprint: String,
+    // SHA1 of the data region, hex encoded
+    pub hash: String,
+    // hex encoded, `None` when the mod isn't signed (all zeroes)
+    pub signature: Option<String>,
+    pub files: usize,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ListedEntry {
+    pub name: String,
+    pub uncompressed_len: i32,
+    pub compressed_len: i32,
+    // compressed over uncompressed size, 1 for stored entries
+    pub ratio: f64,
+}
+
+// the header of a mod and the totals of its entry table
+pub fn info(path: String, options: &InfoOptions) -> Result<(), TModError> {
+    trace!("opening file: {}", path);
+    let mut stream = source::open(&path)?;
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+
+    let header = read_header(&mut reader, None)?;
+    let file_entries = read_entry_table(&mut reader)?;
+
+    let info = mod_info(&header, &file_entries);
+
+    let text = if options.format == OutputFormat::Text {
+        let mut text = String::new();
+        let _ = writeln!(
+            text,
+            "{} v{}",
+            console::text(&info.name),
+            console::text(&info.version)
+        );
+        let _ = writeln!(text, "tModLoader: {}", info.tmodloader_version);
+        let _ = writeln!(text, "Fingerprint: {}", info.fingerprint);
+        let _ = writeln!(text, "Hash: {}", info.hash);
+        let _ = writeln!(
+            text,
+            "Signature: {}",
+            match &info.signature {
+                // the full 256 bytes are in the structured output
+                Some(signature) => format!("{}...", &signature[..16]),
+                None => "none".to_string(),
+            }
+        );
+        let _ = write!(
+            text,
+            "Files: {} ({} compressed, {} uncompressed)",
+            info.files,
+            format_size(info.compressed_size),
+            format_size(info.uncompressed_size)
+        );
+        text
+    } else {
+        output::render(options.format, "info", &info)?
+    };
+    emit(&text, options.copy)
+}
+
+// every entry in the mod with its sizes, in table order. only the entry table
+// is read, none of the data behind it.
+pub fn list(path: String, options: &InfoOptions) -> Result<(), TModError> {
+    trace!("opening file: {}", path);
+    let mut stream = source::open(&path)?;
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+
+    read_header(&mut reader, None)?;
+    let file_entries = read_entry_table(&mut reader)?;
+
+    let entries: Vec<ListedEntry> = file_entries.iter().map(listed_entry).collect();
+
+    let text = if options.format == OutputFormat::Text {
+        let mut lines = vec![format!(
+            "{:>12}  {:>10}  {:>5}  Name",
+            "Uncompressed", "Compressed", "Ratio"
+        )];
+        if options.compact {
+            compact_rows(&DirNode::new(&entries), 0, &mut lines);
+        } else {
+            lines.extend(entries.iter().map(|entry| {
+                row(
+                    entry.uncompressed_len as u64,
+                    entry.compressed_len as u64,
+                    0,
+                    &entry.name,
+                )
+            }));
+        }
+        lines.join("\n")
+    } else {
+        output::render(options.format, "list", &entries)?
+    };
+    emit(&text, options.copy)
+}
+
+pub fn mod_info(header: &ModHeader, file_entries: &[ModFile]) -> ModInfo {
+    ModInfo {
+        name: header.mod_name.clone(),
+        version: header.mod_version.clone(),
+        tmodloader_version: header.tmodloader_version.clone(),
+        fingerprint: header.fingerprint(),
+        hash: hex::encode(&header.hash),
+        signature: header
+            .signature
+            .iter()
+            .any(|&byte| byte != 0)
+            .then(|| hex::encode(&header.signature)),
+        files: file_entries.len(),
+        compressed_size: file_entries.iter().map(|f| f.compressed_len as u64).sum(),
+        uncompressed_size: file_entries.iter().map(|f| f.uncompressed_len as u64).sum(),
+    }
+}
+
+pub fn listed_entry(file: &ModFile) -> ListedEntry {
+    ListedEntry {
+        name: file.name.clone(),
+        uncompressed_len: file.uncompressed_len,
+        compressed_len: file.compressed_len,
+        ratio: ratio(file.compressed_len as u64, file.uncompressed_len as u64),
+    }
+}
+
+fn ratio(compressed: u64, uncompressed: u64) -> f64 {
+    match uncompressed {
+        0 => 1.0,
+        len => compressed as f64 / len as f64,
+    }
+}
+
+// one line of `list`, indented by `depth` levels
+fn row(uncompressed: u64, compressed: u64, depth: usize, name: &str) -> String {
+    format!(
+        "{:>12}  {:>10}  {:>4.0}%  {:indent$}{}",
+        format_size(uncompressed),
+        format_size(compressed),
+        ratio(compressed, uncompressed) * 100.0,
+        "",
+        console::text(name),
+        indent = depth * 2
+    )
+}
+
+// the entries of `list --compact` as a tree, each directory with the totals
+// of everything below it
+#[derive(Default)]
+struct DirNode<'a> {
+    dirs: BTreeMap<&'a str, DirNode<'a>>,
+    files: Vec<(&'a str, &'a ListedEntry)>,
+    uncompressed: u64,
+    compressed: u64,
+}
+
+impl<'a> DirNode<'a> {
+    fn new(entries: &'a [ListedEntry]) -> Self {
+        let mut root = DirNode::default();
+        for entry in entries {
+            let (dirs, file) = match entry.name.rsplit_once('/') {
+                Some((dirs, file)) => (Some(dirs), file),
+                None => (None, entry.name.as_str()),
+            };
+            let mut node = &mut root;
+            for dir in dirs.into_iter().flat_map(|dirs| dirs.split('/')) {
+                node = node.dirs.entry(dir).or_default();
+                node.uncompressed += entry.uncompressed_len as u64;
+                node.compressed += entry.compressed_len as u64;
+            }
+            node.files.push((file, entry));
+        }
+        root
+    }
+}
+
+// subdirectories first, then files, both by name. a chain of directories
+// that only hold one another is printed on one line, like `Assets/Textures/`.
+fn compact_rows(node: &DirNode, depth: usize, lines: &mut Vec<String>) {
+    for (name, mut child) in &node.dirs {
+        let mut label = format!("{}/", name);
+        while child.files.is_empty() && child.dirs.len() == 1 {
+            let (name, grandchild) = child.dirs.iter().next().expect("one directory");
+            let _ = write!(label, "{}/", name);
+            child = grandchild;
+        }
+        lines.push(row(child.uncompressed, child.compressed, depth, &label));
+        compact_rows(child, depth + 1, lines);
+    }
+    let mut files = node.files.clone();
+    files.sort_by_key(|(name, _)| *name);
+    for (name, entry) in files {
+        lines.push(row(
+            entry.uncompressed_len as u64,
+            entry.compressed_len as u64,
+            depth,
+            name,
+        ));
+    }
+}
+
+fn emit(text: &str, copy: bool) -> Result<(), TModError> {
+    let mut out = io::stdout().lock();
+    if let Err(e) = writeln!(out, "{}", text).and_then(|_| out.flush()) {
+        // `list ... | head` closing the pipe early isn't a failure
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            return Err(e.into());
+        }
+    }
+    drop(out);
+    if copy {
+        copy_to_clipboard(text)?;
+        eprintln!("Copied to the clipboard");
+    }
+    Ok(())
+}
+
+#[cfg(feature = "clipboard")]
+fn copy_to_clipboard(text: &str) -> Result<(), TModError> {
+    let clipboard_error = |e: arboard::Error| TModError::ClipboardError(e.to_string());
+    let mut clipboard = arboard::Clipboard::new().map_err(clipboard_error)?;
+    clipboard.set_text(text).map_err(clipboard_error)
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn copy_to_clipboard(_text: &str) -> Result<(), TModError> {
+    Err(TModError::FeatureDisabled("clipboard"))
+}
@@ -0,0 +1,322 @@
+use binary_rw::{BinaryReader, Endian, SeekStream};
+use log::trace;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::console;
+use crate::legacy;
+use crate::order::data_offsets;
+use crate::output::{self, OutputFormat};
+use crate::source;
+use crate::{find_magic, read_csharp_string, ModFile, TModError, TMOD_HEADER};
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Span {
+    pub offset: usize,
+    pub len: usize,
+    pub field: String,
+    // what the bytes say, for the fields short enough to show
+    pub value: Option<String>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Layout {
+    pub file_len: usize,
+    // in file order, without gaps
+    pub spans: Vec<Span>,
+}
+
+// maps every byte of a mod to the field it belongs to: the header, each
+// field of the entry table and each entry's data, plus anything before or
+// after them. the file is read as it is, so a legacy archive shows its
+// single deflated data region rather than the converted layout. a damaged
+// file is mapped up to the first field that can't be read, and the rest is
+// shown as one unreadable span with the reason.
+pub fn layout(path: String, format: OutputFormat) -> Result<(), TModError> {
+    trace!("opening file: {}", path);
+    let mut stream = source::open_raw(&path)?;
+    let mut reader = BinaryReader::new(&mut stream, Endian::Little);
+    let file_len = reader.len()?;
+
+    let mut spans = Vec::new();
+    if let Err(e) = map(&mut reader, file_len, &mut spans) {
+        let offset = spans.last().map_or(0, |span: &Span| span.offset + span.len);
+        spans.push(Span {
+            offset,
+            len: file_len.saturating_sub(offset),
+            field: "unreadable".to_string(),
+            value: Some(e.to_string()),
+        });
+    }
+    let layout = Layout { file_len, spans };
+
+    if format != OutputFormat::Text {
+        println!("{}", output::render(format, "layout", &layout)?);
+        return Ok(());
+    }
+    println!("{:>10}  {:>10}  {:<30}  Value", "Offset", "Length", "Field");
+    for span in &layout.spans {
+        println!(
+            "{:#010x}  {:>10}  {:<30}  {}",
+            span.offset,
+            span.len,
+            span.field,
+            console::text(span.value.as_deref().unwrap_or(""))
+        );
+    }
+    println!("{} bytes", layout.file_len);
+    Ok(())
+}
+
+fn map(reader: &mut BinaryReader, file_len: usize, spans: &mut Vec<Span>) -> Result<(), TModError> {
+    ensure(reader, file_len, TMOD_HEADER.len())?;
+    let magic = reader.read_bytes(TMOD_HEADER.len())?;
+    let mut start = 0;
+    if magic != TMOD_HEADER {
+        start = find_magic(reader)?.ok_or(TModError::InvalidHeader(magic, TMOD_HEADER))?;
+        reader.seek(start)?;
+        push(reader, file_len, spans, 0, "junk before the header", None)?;
+        reader.read_bytes(TMOD_HEADER.len())?;
+    }
+    push(
+        reader,
+        file_len,
+        spans,
+        start,
+        "magic",
+        Some("TMOD".to_string()),
+    )?;
+
+    let start = reader.tell()?;
+    let tmodloader_version = read_string(reader)?;
+    push(
+        reader,
+        file_len,
+        spans,
+        start,
+        "tModLoader version",
+        Some(tmodloader_version.clone()),
+    )?;
+    let start = reader.tell()?;
+    ensure(reader, file_len, 20)?;
+    let hash = reader.read_bytes(20)?;
+    push(
+        reader,
+        file_len,
+        spans,
+        start,
+        "hash",
+        Some(hex::encode(hash)),
+    )?;
+    let start = reader.tell()?;
+    ensure(reader, file_len, 256)?;
+    let signature = reader.read_bytes(256)?;
+    let signed = match signature.iter().all(|byte| *byte == 0) {
+        true => "none",
+        false => "present",
+    };
+    push(
+        reader,
+        file_len,
+        spans,
+        start,
+        "signature",
+        Some(signed.to_string()),
+    )?;
+    let start = reader.tell()?;
+    ensure(reader, file_len, 4)?;
+    let data_len = reader.read_u32()? as usize;
+    push(
+        reader,
+        file_len,
+        spans,
+        start,
+        "data length",
+        Some(data_len.to_string()),
+    )?;
+    let data_offset = reader.tell()?;
+    let declared_end = data_offset + data_len;
+
+    let data_end = if legacy::is_legacy(&tmodloader_version) {
+        let end = declared_end.min(file_len);
+        reader.seek(end)?;
+        push(
+            reader,
+            file_len,
+            spans,
+            data_offset,
+            "data region (legacy, deflated)",
+            None,
+        )?;
+        end
+    } else {
+        map_entries(reader, file_len, spans)?
+    };
+
+    if data_end < declared_end.min(file_len) {
+        reader.seek(declared_end.min(file_len))?;
+        push(
+            reader,
+            file_len,
+            spans,
+            data_end,
+            "unused, inside the data length",
+            None,
+        )?;
+    }
+    let end = data_end.max(declared_end).min(file_len);
+    if end < file_len {
+        reader.seek(file_len)?;
+        push(reader, file_len, spans, end, "after the data region", None)?;
+    }
+    if declared_end > file_len {
+        let missing = declared_end - file_len;
+        spans.push(Span {
+            offset: file_len,
+            len: 0,
+            field: "missing".to_string(),
+            value: Some(format!("{} bytes of the data length are missing", missing)),
+        });
+    }
+    Ok(())
+}
+
+// the span from `start` to where the reader is now
+fn push(
+    reader: &mut BinaryReader,
+    file_len: usize,
+    spans: &mut Vec<Span>,
+    start: usize,
+    field: &str,
+    value: Option<String>,
+) -> Result<(), TModError> {
+    let end = reader.tell()?;
+    // reads past the end come back empty instead of failing
+    if end == start || end > file_len {
+        return Err(truncated());
+    }
+    spans.push(Span {
+        offset: start,
+        len: end - start,
+        field: field.to_string(),
+        value,
+    });
+    Ok(())
+}
+
+// the mod name, version and entry table, then each entry's data. returns
+// where the data of the last entry ends.
+fn map_entries(
+    reader: &mut BinaryReader,
+    file_len: usize,
+    spans: &mut Vec<Span>,
+) -> Result<usize, TModError> {
+    for field in ["mod name", "mod version"] {
+        let start = reader.tell()?;
+        let value = read_string(reader)?;
+        push(reader, file_len, spans, start, field, Some(value))?;
+    }
+    let start = reader.tell()?;
+    ensure(reader, file_len, 4)?;
+    let count = reader.read_i32()?;
+    push(
+        reader,
+        file_len,
+        spans,
+        start,
+        "entry count",
+        Some(count.to_string()),
+    )?;
+
+    let mut files = Vec::with_capacity(count.max(0) as usize);
+    for index in 0..count.max(0) {
+        let start = reader.tell()?;
+        let name = read_string(reader)?;
+        push(
+            reader,
+            file_len,
+            spans,
+            start,
+            &format!("entry {} name", index),
+            Some(name.clone()),
+        )?;
+        let start = reader.tell()?;
+        ensure(reader, file_len, 4)?;
+        let uncompressed_len = reader.read_i32()?;
+        push(
+            reader,
+            file_len,
+            spans,
+            start,
+            &format!("entry {} uncompressed length", index),
+            Some(uncompressed_len.to_string()),
+        )?;
+        let start = reader.tell()?;
+        ensure(reader, file_len, 4)?;
+        let compressed_len = reader.read_i32()?;
+        push(
+            reader,
+            file_len,
+            spans,
+            start,
+            &format!("entry {} compressed length", index),
+            Some(compressed_len.to_string()),
+        )?;
+        files.push(ModFile {
+            name,
+            uncompressed_len,
+            compressed_len,
+        });
+    }
+
+    let table_end = reader.tell()?;
+    let mut end = table_end;
+    for (index, (file, offset)) in files
+        .iter()
+        .zip(data_offsets(&files, table_end)?)
+        .enumerate()
+    {
+        if offset >= file_len {
+            break;
+        }
+        end = (offset + file.compressed_len as usize).min(file_len);
+        let kind = match file.compressed_len == file.uncompressed_len {
+            true => "stored",
+            false => "deflated",
+        };
+        spans.push(Span {
+            offset,
+            len: end - offset,
+            field: format!("entry {} data", index),
+            value: Some(format!("{} ({})", file.name, kind)),
+        });
+    }
+    Ok(end)
+}
+
+// like `read_csharp_string`, which pads a string cut short by the end of
+// the file with zeroes
+fn read_string(reader: &mut BinaryReader) -> Result<String, TModError> {
+    let start = reader.tell()?;
+    let value = read_csharp_string(reader)?;
+    // at least one byte of the length prefix comes first
+    if reader.tell()? - start <= value.len() {
+        return Err(truncated());
+    }
+    Ok(value)
+}
+
+// fails unless `len` more bytes are left
+fn ensure(reader: &mut BinaryReader, file_len: usize, len: usize) -> Result<(), TModError> {
+    if reader.tell()? + len > file_len {
+        return Err(truncated());
+    }
+    Ok(())
+}
+
+fn truncated() -> TModError {
+    std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "the file ends here").into()
+}
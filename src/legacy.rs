@@ -0,0 +1,126 @@
+use std::io::Read;
+
+use binary_rw::{BinaryReader, Endian, SeekStream};
+use flate2::read::DeflateDecoder;
+use log::{info, warn};
+use sha1::{Digest, Sha1};
+
+use crate::pack::write_string;
+use crate::preflight::SizeLimits;
+use crate::source::SourceStream;
+use crate::{magic_offset, read_csharp_string, warn_skipped, TModError, TMOD_HEADER};
+
+const SIGNATURE_LEN: usize = 256;
+
+// tModLoader before 0.11 (the 1.3 era) wrote the same header, but then
+// deflated the whole data region as a single stream: the mod name, version,
+// entry count and every entry as a name and a length-prefixed blob
+pub fn is_legacy(tmodloader_version: &str) -> bool {
+    let mut parts = tmodloader_version
+        .trim_start_matches('v')
+        .split('.')
+        .map(|part| part.parse::<u32>());
+    matches!((parts.next(), parts.next()), (Some(Ok(0)), Some(Ok(minor))) if minor < 11)
+}
+
+// rewrites a legacy archive into the current layout in memory, with every
+// entry stored, so the rest of the program never has to know about it.
+// anything else is returned untouched. junk in front of the header is
+// skipped like it is for current archives, and left out of the result. the
+// hash is recomputed for the new layout if the original one matched, and
+// kept (so it fails to match again) if it didn't. the signature is zeroed,
+// which is what tModLoader writes for unsigned local builds: it was made
+// over the original hash, so no key could verify it on the new one. the
+// inflated region is held in memory, so it may be no bigger than
+// `sizes.max_total_size`.
+pub fn upgrade(mut source: SourceStream, sizes: &SizeLimits) -> Result<SourceStream, TModError> {
+    let mut reader = BinaryReader::new(&mut source, Endian::Little);
+    let header_at = match reader.len()? > TMOD_HEADER.len() {
+        true if reader.read_bytes(TMOD_HEADER.len())? == TMOD_HEADER => Some(0),
+        true => magic_offset(&mut reader)?,
+        false => None,
+    };
+    let mut legacy = false;
+    if let Some(header_at) = header_at {
+        reader.seek(header_at + TMOD_HEADER.len())?;
+        legacy = is_legacy(&read_csharp_string(&mut reader).unwrap_or_default());
+    }
+    if !legacy {
+        reader.seek(0)?;
+        return Ok(source);
+    }
+
+    let header_at = header_at.unwrap_or_default();
+    if header_at > 0 {
+        warn_skipped(&mut reader, header_at)?;
+    }
+    reader.seek(header_at + TMOD_HEADER.len())?;
+    let tmodloader_version = read_csharp_string(&mut reader)?;
+    info!(
+        "Legacy archive for tModLoader {}, converting it",
+        tmodloader_version
+    );
+    let hash = reader.read_bytes(20)?;
+    reader.read_bytes(SIGNATURE_LEN)?;
+    let data_len = reader.read_u32()? as usize;
+    // a truncated archive fails to inflate below
+    let available = reader.len()? - reader.tell()?;
+    let data = reader.read_bytes(data_len.min(available))?;
+    let hash_matches = Sha1::digest(&data)[..] == hash[..];
+    if !hash_matches {
+        warn!("the hash of the legacy data region doesn't match");
+    }
+
+    let limit = sizes.max_total_size;
+    let mut contents = Vec::new();
+    DeflateDecoder::new(data.as_slice())
+        .take(limit + 1)
+        .read_to_end(&mut contents)?;
+    if contents.len() as u64 > limit {
+        return Err(TModError::InflateError(
+            "miniz",
+            format!(
+                "the legacy data region inflates to more than {} bytes",
+                limit
+            ),
+        ));
+    }
+    let mut stream = SourceStream::from_bytes(contents);
+    let mut inner = BinaryReader::new(&mut stream, Endian::Little);
+    let mod_name = read_csharp_string(&mut inner)?;
+    let mod_version = read_csharp_string(&mut inner)?;
+    let count = inner.read_i32()?.max(0) as usize;
+
+    let mut table = Vec::new();
+    let mut entries = Vec::new();
+    write_string(&mut table, &mod_name);
+    write_string(&mut table, &mod_version);
+    table.extend_from_slice(&(count as i32).to_le_bytes());
+    for i in 0..count {
+        let name = read_csharp_string(&mut inner)?;
+        let len = inner.read_i32()?;
+        let remaining = inner.len()? - inner.tell()?;
+        if len < 0 || len as usize > remaining {
+            return Err(TModError::MissingFileEntries(i, count));
+        }
+        write_string(&mut table, &name);
+        table.extend_from_slice(&len.to_le_bytes());
+        table.extend_from_slice(&len.to_le_bytes());
+        entries.push(inner.read_bytes(len as usize)?);
+    }
+    for entry in entries {
+        table.extend(entry);
+    }
+
+    let mut file = Vec::with_capacity(table.len() + 512);
+    file.extend_from_slice(TMOD_HEADER);
+    write_string(&mut file, &tmodloader_version);
+    match hash_matches {
+        true => file.extend_from_slice(&Sha1::digest(&table)),
+        false => file.extend_from_slice(&hash),
+    }
+    file.extend_from_slice(&[0; SIGNATURE_LEN]);
+    file.extend_from_slice(&(table.len() as u32).to_le_bytes());
+    file.extend(table);
+    Ok(SourceStream::from_bytes(file))
+}
@@ -0,0 +1,166 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use log::trace;
+
+use crate::names::extension;
+use crate::TModError;
+
+// `--lfs-threshold`: entries bigger than the threshold are written as Git LFS
+// pointer files, with their contents stored in the repository's LFS object
+// store, so they are uploaded to the LFS server instead of bloating the
+// history the first time the extraction is pushed
+pub struct LfsStore {
+    out_dir: PathBuf,
+    threshold: u64,
+    // `.gitattributes` patterns already routed through LFS
+    tracked: HashSet<String>,
+}
+
+impl LfsStore {
+    pub fn new(out_dir: &Path, threshold: u64) -> Result<Self, TModError> {
+        let attributes =
+            std::fs::read_to_string(out_dir.join(".gitattributes")).unwrap_or_default();
+        let tracked = attributes
+            .lines()
+            .filter(|line| line.contains("filter=lfs"))
+            .filter_map(|line| line.split_whitespace().next())
+            .map(str::to_string)
+            .collect();
+        Ok(Self {
+            out_dir: out_dir.to_path_buf(),
+            threshold,
+            tracked,
+        })
+    }
+
+    // stores an entry over the threshold and returns the pointer to write
+    // in its place, `None` for entries that are written as they are
+    pub fn store(&mut self, name: &str, data: &[u8]) -> Result<Option<Vec<u8>>, TModError> {
+        if (data.len() as u64) <= self.threshold {
+            return Ok(None);
+        }
+
+        let oid = hex::encode(sha256(data));
+        let object = self
+            .out_dir
+            .join(".git/lfs/objects")
+            .join(&oid[0..2])
+            .join(&oid[2..4])
+            .join(&oid);
+        if !object.exists() {
+            trace!("storing LFS object for {}: {}", name, oid);
+            std::fs::create_dir_all(object.parent().expect("object has a parent"))?;
+            std::fs::write(&object, data)?;
+        }
+        self.track(name)?;
+
+        let pointer = format!(
+            "version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize {}\n",
+            oid,
+            data.len()
+        );
+        Ok(Some(pointer.into_bytes()))
+    }
+
+    // makes sure checkouts turn the pointer back into the file, by its
+    // extension where it has one
+    fn track(&mut self, name: &str) -> Result<(), TModError> {
+        let pattern = match extension(name) {
+            Some(ext) => format!("*.{}", ext),
+            // attribute patterns can't contain whitespace
+            None if !name.contains(char::is_whitespace) => format!("/{}", name),
+            None => return Ok(()),
+        };
+        if !self.tracked.insert(pattern.clone()) {
+            return Ok(());
+        }
+        let path = self.out_dir.join(".gitattributes");
+        let mut attributes = std::fs::read_to_string(&path).unwrap_or_default();
+        if !attributes.is_empty() && !attributes.ends_with('\n') {
+            attributes.push('\n');
+        }
+        attributes.push_str(&format!(
+            "{} filter=lfs diff=lfs merge=lfs -text\n",
+            pattern
+        ));
+        std::fs::write(&path, attributes)?;
+        Ok(())
+    }
+}
+
+// LFS names objects by their SHA-256, which none of our dependencies provide
+fn sha256(data: &[u8]) -> [u8; 32] {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+
+    // a copy of the data, padded and followed by its length in bits
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend((data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+
+    let mut digest = [0; 32];
+    for (chunk, value) in digest.chunks_exact_mut(4).zip(state) {
+        chunk.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
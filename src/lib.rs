@@ -0,0 +1,1468 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex},
+    time::{Duration, Instant},
+};
+
+use binary_rw::{BinaryReader, SeekStream};
+use is_terminal::IsTerminal;
+use log::{debug, info, trace, warn};
+use thiserror::Error;
+
+pub mod api;
+pub mod audio;
+pub mod audit;
+pub mod bench;
+pub mod cancel;
+pub mod capabilities;
+pub mod check;
+pub mod console;
+pub mod corpus;
+pub mod dedup;
+pub mod diff;
+pub mod doctor;
+pub mod entry_log;
+pub mod explain;
+pub mod fifo;
+pub mod filter;
+pub mod gallery;
+pub mod gen;
+pub mod git;
+pub mod inflate;
+pub mod info;
+pub mod layout;
+pub mod legacy;
+pub mod lfs;
+pub mod lint;
+pub mod localization;
+pub mod locate;
+pub mod lock;
+pub mod names;
+pub mod notify;
+pub mod optimize;
+pub mod order;
+pub mod orphans;
+pub mod output;
+pub mod pack;
+pub mod preflight;
+pub mod progress;
+pub mod rawimg;
+pub mod reader;
+pub mod register;
+pub mod retry;
+pub mod route;
+#[cfg(feature = "rules")]
+pub mod rules;
+pub mod sample;
+#[cfg(feature = "hardened")]
+pub mod sandbox;
+pub mod scaffold;
+pub mod schema;
+#[cfg(feature = "serve")]
+pub mod serve;
+pub mod signature;
+pub mod sink;
+pub mod source;
+pub mod source_map;
+pub mod stats;
+pub mod stream;
+pub mod strings;
+pub mod text;
+pub mod thumbnail;
+pub mod tools;
+pub mod transform;
+pub mod verify_dir;
+pub mod view;
+pub mod watchdog;
+pub mod workshop;
+pub mod workspace;
+
+#[cfg(test)]
+mod tests;
+
+pub use reader::TModReader;
+
+use order::ExtractOrder;
+use sample::Sample;
+use stream::{DataHasher, EntryReader, HashingStream};
+
+#[derive(Debug, Error)]
+pub enum TModError {
+    #[error("No input file given")]
+    NoInputFile,
+    #[error("No output directory given")]
+    NoOutputDirectory,
+    #[error("Invalid header: `{0:?}`, expected `{1:?}`")]
+    InvalidHeader(Vec<u8>, &'static [u8]),
+    #[error("Missing some file entries: have {0}, expected {1}")]
+    MissingFileEntries(usize, usize),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Read error: {0}")]
+    ReadError(#[from] binary_rw::BinaryError),
+    #[error("UTF8 error: {0}")]
+    Utf8Error(#[from] std::string::FromUtf8Error),
+    #[error("No value given for `{0}`")]
+    MissingValue(&'static str),
+    #[error("Unknown argument: `{0}`")]
+    UnknownArgument(String),
+    #[cfg(feature = "rules")]
+    #[error("Rules file error: {0}")]
+    RulesError(#[from] serde_yaml::Error),
+    #[cfg(feature = "rules")]
+    #[error("Invalid rule `{0}`: {1}")]
+    InvalidRule(String, String),
+    #[error("Entry table failed validation with {0} problem(s)")]
+    InvalidEntryTable(usize),
+    #[error("Unknown error code: `{0}`")]
+    UnknownErrorCode(String),
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Unknown output format: `{0}`, expected one of text, json, nuon, powershell, github")]
+    UnknownFormat(String),
+    #[error("Invalid route `{0}`: {1}")]
+    InvalidRoute(String, String),
+    #[error("Invalid transform `{0}`: {1}")]
+    InvalidTransform(String, String),
+    #[error("Invalid glob `{0}`: {1}")]
+    InvalidGlob(String, String),
+    #[error("No route matches `{0}` and no output directory was given")]
+    NoRoute(String),
+    #[error("Decompression with {0} failed: {1}")]
+    InflateError(&'static str, String),
+    #[error("Unknown decompression backend: {0}")]
+    UnknownBackend(String),
+    #[error("Refusing to write `{0}` outside the output directory: {1}")]
+    UnsafePath(String, &'static str),
+    #[error("Refusing to write through symlink {0:?}")]
+    SymlinkedOutput(PathBuf),
+    #[error("Could not update file associations: {0}")]
+    RegisterError(String),
+    #[error("{0} is not a valid rawimg: {1}")]
+    InvalidImage(String, String),
+    #[error("Could not parse localization file {0}: {1}")]
+    LocalizationError(String, String),
+    #[error("Timed out: {0}")]
+    Timeout(String),
+    #[error("Extraction was cancelled")]
+    Cancelled,
+    #[error("{1} can't be combined with {0}")]
+    ConflictingArguments(&'static str, &'static str),
+    #[error("Lint failed with {0} error(s)")]
+    LintFailed(usize),
+    #[error("Unknown lint: `{0}`, expected one of missing-icon, icon-size, uncompressed-png, large-file, localization-newline, uncompressed-audio, audio-bitrate")]
+    UnknownLint(String),
+    #[error("Invalid mod name `{0}`: only letters, digits and underscores are allowed")]
+    InvalidModName(String),
+    #[error("{0:?} already exists and isn't empty")]
+    OutputExists(PathBuf),
+    #[error("Hash mismatch: stored {0}, computed {1}. The file is damaged, download it again")]
+    HashMismatch(String, String),
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+    #[error("Could not read the public key in {0:?}: {1}")]
+    InvalidPublicKey(PathBuf, String),
+    #[error("Git error: {0}")]
+    GitError(String),
+    #[error("Could not re-encode `{0}`: {1}")]
+    ReencodeFailed(String, String),
+    #[error("{0} of {1} mods failed to extract")]
+    BatchFailed(usize, usize),
+    #[error("No installed mod named `{0}` in the tModLoader Mods or workshop folders")]
+    ModNotFound(String),
+    #[error("Could not sandbox the extraction: {0}")]
+    SandboxError(String),
+    #[error("{0} of {1} corpus cases failed")]
+    CorpusFailed(usize, usize),
+    #[error("Could not fetch workshop item {0}: {1}")]
+    WorkshopError(String, String),
+    #[error("{0:?} already exists")]
+    FileExists(PathBuf),
+    #[error("{0} is needed for {1} but isn't on the PATH: install it, or {2}")]
+    ToolMissing(&'static str, &'static str, &'static str),
+    #[error("{0} of {1} entries failed to extract, the others were written")]
+    EntriesFailed(usize, usize),
+    #[error("Invalid workspace file {0:?}: {1}")]
+    InvalidWorkspace(PathBuf, String),
+    #[error("Check failed with {0} problem(s)")]
+    CheckFailed(usize),
+    #[error("{0:?} is being written by another invocation")]
+    OutputLocked(PathBuf),
+    #[error("Can't pack {0:?}: {1}")]
+    InvalidEntry(PathBuf, &'static str),
+    #[error("Unknown output kind: `{0}`, run `schema` to list them")]
+    UnknownSchema(String),
+    #[cfg(feature = "clipboard")]
+    #[error("Could not copy to the clipboard: {0}")]
+    ClipboardError(String),
+    #[cfg(feature = "http")]
+    #[error("Could not fetch {0}: {1}")]
+    HttpError(String, String),
+    #[error("No entry named `{0}` in the archive")]
+    EntryNotFound(String),
+    #[error("Entry `{0}` is binary, not text")]
+    BinaryEntry(String),
+    #[error("Invalid number for `{0}`: `{1}`")]
+    InvalidNumber(&'static str, String),
+    #[error("`{0}` needs {1} bytes of data, but the archive ends after {2}")]
+    TruncatedEntry(String, usize, usize),
+    #[error("`{0}` unpacks to {1} bytes, more than the limit of {2}")]
+    TooLarge(String, u64, u64),
+    #[error("Unknown extraction order: `{0}`, expected one of table, alpha, size-desc, size-asc")]
+    UnknownOrder(String),
+    // only constructed when some feature is disabled
+    #[allow(dead_code)]
+    #[error("This build was compiled without the `{0}` feature")]
+    FeatureDisabled(&'static str),
+}
+
+impl TModError {
+    // stable identifiers for each kind of error, explained by `explain <code>`
+    pub fn code(&self) -> &'static str {
+        match self {
+            TModError::NoInputFile => "E0001",
+            TModError::NoOutputDirectory => "E0002",
+            TModError::InvalidHeader(..) => "E0003",
+            TModError::MissingFileEntries(..) => "E0004",
+            TModError::IoError(_) => "E0005",
+            TModError::ReadError(_) => "E0006",
+            TModError::Utf8Error(_) => "E0007",
+            TModError::MissingValue(_) => "E0008",
+            TModError::UnknownArgument(_) => "E0009",
+            #[cfg(feature = "rules")]
+            TModError::RulesError(_) => "E0010",
+            #[cfg(feature = "rules")]
+            TModError::InvalidRule(..) => "E0011",
+            TModError::InvalidEntryTable(_) => "E0012",
+            TModError::UnknownErrorCode(_) => "E0013",
+            TModError::JsonError(_) => "E0014",
+            TModError::UnknownFormat(_) => "E0015",
+            TModError::FeatureDisabled(_) => "E0016",
+            TModError::UnknownOrder(_) => "E0017",
+            TModError::InvalidNumber(..) => "E0018",
+            TModError::EntryNotFound(_) => "E0019",
+            TModError::BinaryEntry(_) => "E0020",
+            TModError::InvalidRoute(..) => "E0021",
+            TModError::InvalidGlob(..) => "E0022",
+            TModError::NoRoute(_) => "E0023",
+            TModError::InflateError(..) => "E0024",
+            TModError::UnknownBackend(_) => "E0025",
+            TModError::SymlinkedOutput(_) => "E0026",
+            TModError::RegisterError(_) => "E0027",
+            TModError::InvalidImage(..) => "E0028",
+            TModError::LocalizationError(..) => "E0029",
+            TModError::Timeout(_) => "E0030",
+            TModError::Cancelled => "E0031",
+            TModError::ConflictingArguments(..) => "E0032",
+            #[cfg(feature = "http")]
+            TModError::HttpError(..) => "E0033",
+            TModError::LintFailed(_) => "E0034",
+            TModError::UnknownLint(_) => "E0035",
+            TModError::InvalidModName(_) => "E0036",
+            TModError::OutputExists(_) => "E0037",
+            #[cfg(feature = "clipboard")]
+            TModError::ClipboardError(_) => "E0038",
+            TModError::OutputLocked(_) => "E0039",
+            TModError::CheckFailed(_) => "E0040",
+            TModError::InvalidEntry(..) => "E0041",
+            TModError::UnknownSchema(_) => "E0042",
+            TModError::InvalidTransform(..) => "E0043",
+            TModError::HashMismatch(..) => "E0044",
+            TModError::InvalidSignature(_) => "E0045",
+            TModError::InvalidPublicKey(..) => "E0046",
+            TModError::UnsafePath(..) => "E0047",
+            TModError::GitError(_) => "E0048",
+            TModError::ReencodeFailed(..) => "E0049",
+            TModError::BatchFailed(..) => "E0050",
+            TModError::ModNotFound(_) => "E0051",
+            TModError::SandboxError(_) => "E0052",
+            TModError::CorpusFailed(..) => "E0053",
+            TModError::WorkshopError(..) => "E0054",
+            TModError::FileExists(_) => "E0055",
+            TModError::ToolMissing(..) => "E0056",
+            TModError::EntriesFailed(..) => "E0057",
+            TModError::InvalidWorkspace(..) => "E0058",
+            TModError::TruncatedEntry(..) => "E0059",
+            TModError::TooLarge(..) => "E0060",
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub struct ModHeader {
+    // junk skipped before the magic, normally 0
+    pub prefix_len: usize,
+    pub tmodloader_version: String,
+    pub hash: Vec<u8>,
+    pub signature: Vec<u8>,
+    pub file_data_len: u32,
+    // position of the data region (everything covered by the hash)
+    pub data_offset: usize,
+    pub mod_name: String,
+    pub mod_version: String,
+}
+
+impl ModHeader {
+    // a short, stable identifier for this exact build of a mod, derived from
+    // the mod name, version and hash (64-bit FNV-1a, hex encoded)
+    pub fn fingerprint(&self) -> String {
+        let mut state: u64 = 0xcbf2_9ce4_8422_2325;
+        let parts: [&[u8]; 3] = [
+            self.mod_name.as_bytes(),
+            self.mod_version.as_bytes(),
+            &self.hash,
+        ];
+        for part in parts {
+            for byte in part.iter().chain(std::iter::once(&0)) {
+                state ^= *byte as u64;
+                state = state.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        format!("{:016x}", state)
+    }
+}
+
+#[derive(Default)]
+pub struct UnpackOptions {
+    // skip the confirmation prompt after the pre-flight summary
+    pub yes: bool,
+    // print what would be written where and stop before writing anything
+    pub dry_run: bool,
+    // what to do about files already where entries go
+    pub existing: sink::Existing,
+    // replace symlinks in the way of extracted files instead of refusing
+    pub force: bool,
+    // write entries whose names escape the output directory where they point
+    pub allow_unsafe_paths: bool,
+    // fail on a hash mismatch instead of warning about it
+    pub verify: bool,
+    // skip entries that fail to extract and fail at the end instead
+    pub keep_going: bool,
+    // refuse to extract a mod that isn't signed with this key
+    pub key: Option<signature::PublicKey>,
+    pub limits: preflight::PathLimits,
+    pub size_limits: preflight::SizeLimits,
+    // how often to retry writing an entry after a transient I/O error
+    pub retries: u32,
+    // abort the whole run, or a single entry, when it takes longer than this
+    pub timeout: Option<Duration>,
+    pub entry_timeout: Option<Duration>,
+    pub cancel: cancel::CancellationToken,
+    // memory-map the input instead of reading it
+    pub mmap: bool,
+    // keep a copy of a mod read from a url here, named after its hash
+    pub cache_dir: Option<PathBuf>,
+    // stream every entry from the archive to its destination in small
+    // chunks instead of holding it in memory whole
+    pub low_memory: bool,
+    // decompress and write entries on this many threads, 0 and 1 both mean
+    // on the calling thread
+    pub jobs: usize,
+    // write everything into a single archive instead of a directory
+    pub archive: Option<(sink::ArchiveFormat, PathBuf)>,
+    // where to write the archive path -> ModSources path mapping, if anywhere
+    pub source_map: Option<PathBuf>,
+    pub order: ExtractOrder,
+    // entries to extract before everything else
+    pub priority: Option<order::PriorityList>,
+    // only extract the entries matching `--include` and not `--exclude`
+    pub filter: filter::Filter,
+    // only extract a subset of the entries
+    pub sample: Option<Sample>,
+    // write a JSON line per extracted entry to this file
+    pub emit_entries: Option<PathBuf>,
+    // print a JSON line to stderr with the progress after every entry
+    pub progress_json: bool,
+    // don't draw the progress bar, even on a terminal
+    pub no_progress: bool,
+    // print nothing but errors, for callers that report on their own
+    pub quiet: bool,
+    // wait for another invocation writing to the same output instead of
+    // failing
+    pub wait_lock: bool,
+    // post a summary here when the run is over
+    pub notify_webhook: Option<String>,
+    // send entries matching a glob to other output roots, first match wins
+    pub routes: Vec<route::Route>,
+    // convert the contents of entries matching a glob, first match wins
+    pub transforms: Vec<transform::Transform>,
+    // also write textures scaled to fit this size under `_thumbs/`
+    pub thumbnails: Option<u32>,
+    // and skip the full-size textures
+    pub thumbnails_only: bool,
+    // line endings and byte order marks of text entries
+    pub normalize: text::Normalize,
+    // commit the extracted files to a git repository in the output directory
+    pub git_init: bool,
+    // with `git_init`, store entries bigger than this through Git LFS
+    pub lfs_threshold: Option<u64>,
+}
+
+pub struct ModFile {
+    pub name: String,
+    pub uncompressed_len: i32,
+    pub compressed_len: i32,
+}
+
+impl TModError {
+    // errors that come from how the program was invoked, which the CLI
+    // answers with its usage instead of an `explain` hint
+    pub fn is_usage_error(&self) -> bool {
+        matches!(
+            self,
+            TModError::NoInputFile | TModError::NoOutputDirectory | TModError::UnknownArgument(_)
+        )
+    }
+}
+
+pub fn print_error(e: &TModError) {
+    eprintln!("Error[{}]: {}", e.code(), e);
+    if !e.is_usage_error() {
+        eprintln!(
+            "For more information about this error, try `{} explain {}`",
+            env!("CARGO_PKG_NAME"),
+            e.code()
+        );
+    }
+}
+
+const TMOD_HEADER: &[u8] = b"TMOD";
+
+pub fn unpack(
+    path: String,
+    out_dir: Option<PathBuf>,
+    options: &UnpackOptions,
+) -> Result<(), TModError> {
+    let watchdog = watchdog::Watchdog::start(
+        options.timeout,
+        options.entry_timeout,
+        options.cancel.clone(),
+    );
+    let result = extract(path, out_dir, options, &watchdog);
+    // the watchdog stops a run that takes too long by cancelling it
+    match (result, watchdog.timed_out()) {
+        (Err(TModError::Cancelled), Some(summary)) => Err(TModError::Timeout(summary)),
+        (result, _) => result,
+    }
+}
+
+fn extract(
+    path: String,
+    out_dir: Option<PathBuf>,
+    options: &UnpackOptions,
+    watchdog: &watchdog::Watchdog,
+) -> Result<(), TModError> {
+    // with `--to-tar -` stdout is the archive, so everything said along the
+    // way goes to stderr
+    let to_stdout =
+        matches!(&options.archive, Some((_, archive_path)) if sink::is_stdout(archive_path));
+    macro_rules! say {
+        ($($arg:tt)*) => {
+            if options.quiet {
+            } else if to_stdout {
+                eprintln!($($arg)*)
+            } else {
+                println!($($arg)*)
+            }
+        };
+    }
+
+    trace!("opening file: {}", path);
+    let source = match &options.cache_dir {
+        Some(cache_dir) if source::is_url(&path) => {
+            source::open_cached(&path, cache_dir, &options.size_limits)?
+        }
+        _ if options.mmap => source::open_mapped(&path, &options.size_limits)?,
+        _ => source::open_limited(&path, &options.size_limits)?,
+    };
+    let hasher = DataHasher::default();
+    let mut stream = HashingStream::new(source, hasher.clone());
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+
+    let header = read_header(&mut reader, Some(&hasher))?;
+    info!("Fingerprint: {}", header.fingerprint());
+    if let Some(key) = &options.key {
+        signature::verify_header(&header, key)?;
+        info!("Signature verified with key {}", key.id());
+    }
+
+    // an entry with a name that isn't UTF-8 only fails itself with
+    // `--keep-going`, instead of the whole table
+    let (file_entries, mut invalid_names) = match options.keep_going {
+        true => read_entry_table_lossy(&mut reader)?,
+        false => (read_entry_table(&mut reader)?, HashMap::new()),
+    };
+
+    // the data region may extend past the end of a truncated file, in which
+    // case only what is actually there counts as available
+    let table_end = reader.tell()?;
+    let declared_end = header.data_offset + header.file_data_len as usize;
+    let data_end = declared_end.min(reader.len()?);
+    if reader.len()? > declared_end {
+        warn!(
+            "ignoring {} bytes of trailing data after the declared data length",
+            reader.len()? - declared_end
+        );
+    }
+    let available_data = data_end.saturating_sub(table_end) as u64;
+
+    info!("Validating file entries");
+    let mut preflight = preflight::check_entries(
+        &file_entries,
+        available_data,
+        &options.limits,
+        &options.size_limits,
+    );
+    if options.allow_unsafe_paths {
+        for unsafe_path in &preflight.unsafe_paths {
+            warn!("extracting outside the output directory: {}", unsafe_path);
+        }
+    } else {
+        preflight.problems.append(&mut preflight.unsafe_paths);
+    }
+    // with `--keep-going` the entries past the end fail one by one instead
+    if let (false, Some(missing_data)) = (options.keep_going, preflight.missing_data.take()) {
+        preflight.problems.push(missing_data);
+    }
+    if !preflight.problems.is_empty() {
+        for problem in &preflight.problems {
+            eprintln!("  {}", problem);
+        }
+        return Err(TModError::InvalidEntryTable(preflight.problems.len()));
+    }
+
+    say!(
+        "{} v{} ({}): {} files, {} ({} compressed)",
+        console::text(&header.mod_name),
+        console::text(&header.mod_version),
+        header.fingerprint(),
+        file_entries.len(),
+        format_size(preflight.total_uncompressed),
+        format_size(preflight.total_compressed)
+    );
+    for (first, second) in &preflight.conflicts {
+        say!(
+            "  conflict: `{}` and `{}` map to the same path",
+            console::text(first),
+            console::text(second)
+        );
+    }
+
+    let filtered: Vec<bool> = file_entries
+        .iter()
+        .map(|file| options.filter.matches(&file.name))
+        .collect();
+    if !options.filter.is_empty() {
+        say!(
+            "  filtering {} of {} files",
+            filtered.iter().filter(|f| **f).count(),
+            file_entries.len()
+        );
+    }
+    let mut selected = match &options.sample {
+        Some(sample) => sample.select(&file_entries, &filtered),
+        None => filtered,
+    };
+    let selected_count = selected.iter().filter(|s| **s).count();
+    if options.sample.is_some() {
+        say!(
+            "  sampling {} of {} files",
+            selected_count,
+            file_entries.len()
+        );
+    }
+
+    // resolve every entry's destination now, so a missing route fails before
+    // anything is written
+    let output_name = |file: &ModFile| match transform::find(&options.transforms, &file.name) {
+        Some(transform) => transform.output_name(&file.name),
+        None => file.name.clone(),
+    };
+    let mut destinations = Vec::with_capacity(file_entries.len());
+    if options.archive.is_none() {
+        for (file, selected) in file_entries.iter().zip(&selected) {
+            // routes see the name an entry is written under
+            let name = output_name(file);
+            let root = route::destination(&options.routes, &name, out_dir.as_deref());
+            if *selected && root.is_none() {
+                return Err(TModError::NoRoute(name));
+            }
+            destinations.push(root);
+        }
+    }
+    for route in &options.routes {
+        let routed = destinations
+            .iter()
+            .zip(&selected)
+            .filter(|(root, selected)| **selected && *root == &Some(route.root.as_path()))
+            .count();
+        say!(
+            "  route: {} files to {} ({})",
+            routed,
+            route.root.display(),
+            route.pattern
+        );
+    }
+
+    // the selected entries whose file is already there, settled before
+    // anything is written so skipped ones are never decompressed
+    let mut existing = Vec::new();
+    if options.existing != sink::Existing::Overwrite {
+        for (index, file) in file_entries.iter().enumerate() {
+            if let (true, Some(Some(root))) = (selected[index], destinations.get(index)) {
+                let path = root.join(output_name(file));
+                if std::fs::symlink_metadata(&path).is_ok() {
+                    existing.push((index, path));
+                }
+            }
+        }
+    }
+    let prompt = options.existing == sink::Existing::Prompt;
+    if let (sink::Existing::Fail | sink::Existing::Prompt, Some((_, path))) =
+        (options.existing, existing.first())
+    {
+        // there is no asking without a terminal
+        if !prompt || !std::io::stdin().is_terminal() {
+            return Err(TModError::FileExists(path.clone()));
+        }
+    }
+    if options.existing == sink::Existing::Skip && !existing.is_empty() {
+        for (index, _) in &existing {
+            selected[*index] = false;
+        }
+        say!("  skipping {} existing files", existing.len());
+    }
+    let selected_count = selected.iter().filter(|s| **s).count();
+
+    // only the entry table is needed for this, so nothing is decompressed.
+    // the sizes are the ones in the table, before any transform.
+    if options.dry_run {
+        let mut total = 0;
+        for (index, file) in file_entries.iter().enumerate() {
+            if !selected[index] {
+                continue;
+            }
+            let name = output_name(file);
+            let path = match (&options.archive, destinations.get(index)) {
+                (Some((_, archive_path)), _) => format!("{}:{}", archive_path.display(), name),
+                (None, Some(Some(root))) => root.join(&name).display().to_string(),
+                _ => name,
+            };
+            say!(
+                "{:>10}  {}",
+                format_size(file.uncompressed_len as u64),
+                console::text(&path)
+            );
+            total += file.uncompressed_len as u64;
+        }
+        say!(
+            "Would write {} files, {} (dry run, nothing was written)",
+            selected_count,
+            format_size(total)
+        );
+        return Ok(());
+    }
+
+    if !options.yes && std::io::stdin().is_terminal() && !confirm("Extract?", to_stdout)? {
+        say!("Aborted");
+        return Ok(());
+    }
+    if prompt && !existing.is_empty() {
+        // `a` and `s` answer for all the files still to ask about
+        let mut all = None;
+        for (index, path) in &existing {
+            let overwrite = match all {
+                Some(overwrite) => overwrite,
+                None => {
+                    let question = format!("Overwrite {}? [y/N/a/s]", path.display());
+                    match ask(&question, to_stdout)?.as_str() {
+                        "y" | "yes" => true,
+                        "a" | "all" => *all.insert(true),
+                        "s" | "skip" => *all.insert(false),
+                        _ => false,
+                    }
+                }
+            };
+            selected[*index] = overwrite;
+        }
+    }
+    let selected_count = selected.iter().filter(|s| **s).count();
+    watchdog.set_total(selected_count);
+
+    // every root this run writes to, locked in a fixed order so two waiting
+    // invocations can't deadlock
+    let mut targets: Vec<&Path> = match &options.archive {
+        Some((_, archive_path)) => vec![archive_path],
+        None => out_dir
+            .as_deref()
+            .into_iter()
+            .chain(options.routes.iter().map(|route| route.root.as_path()))
+            .collect(),
+    };
+    targets.retain(|target| !sink::is_stdout(target));
+    targets.sort();
+    targets.dedup();
+    let _locks = targets
+        .into_iter()
+        .map(|target| lock::lock(target, options.wait_lock))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if let Some(out_dir) = &out_dir {
+        trace!("checking if output directory exists: {:?}", out_dir);
+        if !out_dir.try_exists()? {
+            trace!("output directory does not exist, creating it");
+            std::fs::create_dir_all(out_dir)?;
+        }
+    }
+
+    let mut lfs = None;
+    if let (true, Some(out_dir)) = (options.git_init, &out_dir) {
+        git::init(out_dir)?;
+        if let Some(threshold) = options.lfs_threshold {
+            lfs = Some(lfs::LfsStore::new(out_dir, threshold)?);
+        }
+    }
+
+    let mut sink: Box<dyn sink::OutputSink> = match &options.archive {
+        Some((format, archive_path)) => sink::archive_sink(*format, archive_path)?,
+        None => Box::new(sink::DirSink {
+            out_dir: out_dir.as_deref(),
+            routes: &options.routes,
+            force: options.force,
+            allow_unsafe_paths: options.allow_unsafe_paths,
+            retries: options.retries,
+            cancel: options.cancel.clone(),
+        }),
+    };
+
+    if let Some(map_path) = &options.source_map {
+        source_map::write_source_map(map_path, &file_entries, &header.mod_name)?;
+    }
+
+    let mut entry_log = match &options.emit_entries {
+        Some(log_path) => Some(entry_log::EntryLog::create(log_path)?),
+        None => None,
+    };
+
+    // everything that is opened by path is open by now
+    #[cfg(feature = "hardened")]
+    {
+        let mut roots: Vec<&Path> = Vec::new();
+        match &options.archive {
+            // the archive is written next to its final name, then renamed
+            Some((_, archive_path)) => roots.push(
+                archive_path
+                    .parent()
+                    .filter(|parent| !parent.as_os_str().is_empty())
+                    .unwrap_or_else(|| Path::new(".")),
+            ),
+            None => {
+                roots.extend(out_dir.as_deref());
+                for route in &options.routes {
+                    // only existing paths can be allowed
+                    std::fs::create_dir_all(&route.root)?;
+                    roots.push(&route.root);
+                }
+            }
+        }
+        sandbox::restrict(&roots)?;
+    }
+
+    let selected_bytes = file_entries
+        .iter()
+        .zip(&selected)
+        .filter(|(_, selected)| **selected)
+        .map(|(file, _)| file.uncompressed_len as u64)
+        .sum();
+    let mut progress = progress::Progress::start(
+        selected_count,
+        selected_bytes,
+        options.progress_json,
+        !options.no_progress,
+    );
+
+    let mut arrangement = options.order.arrange(&file_entries);
+    if let Some(priority) = &options.priority {
+        priority.apply(&file_entries, &mut arrangement);
+    }
+    let in_table_order = arrangement.iter().enumerate().all(|(i, &index)| i == index);
+    // skipping entries means seeking past them
+    let sequential = in_table_order && selected.iter().all(|selected| *selected);
+    let offsets = order::data_offsets(&file_entries, table_end)?;
+    // the entries of the priority list still to go, to say when they are all
+    // out
+    let is_priority = |index: usize| match &options.priority {
+        Some(priority) => priority.rank(&file_entries[index].name).is_some(),
+        None => false,
+    };
+    let mut priority_left = (0..file_entries.len())
+        .filter(|&index| selected[index] && is_priority(index))
+        .count();
+
+    // the main thread keeps reading (and hashing) the archive while workers
+    // decompress and write what it has read. archives are a single file and
+    // LFS objects are deduplicated as they are stored, so those are written
+    // from one thread.
+    let jobs = match (&options.archive, options.low_memory, &lfs) {
+        (None, false, None) => options.jobs.max(1),
+        _ => 1,
+    };
+    if jobs < options.jobs {
+        debug!("extracting on a single thread");
+    }
+    let log = entry_log.is_some();
+    let mut finish_entry = |index: usize, records: Vec<entry_log::EntryRecord>| {
+        if let Some(entry_log) = &mut entry_log {
+            for record in &records {
+                entry_log.write(record)?;
+            }
+        }
+        watchdog.end(&file_entries[index].name);
+        if is_priority(index) {
+            priority_left -= 1;
+            if priority_left == 0 {
+                info!("Priority entries extracted");
+            }
+        }
+        progress.advance(file_entries[index].uncompressed_len as u64);
+        Ok::<_, TModError>(())
+    };
+    // with `--keep-going` an entry that fails is reported and skipped, with
+    // nothing to log for it, and the run carries on
+    let mut failures = Vec::new();
+    let mut skip_failed = |index: usize, result: Result<_, TModError>| match result {
+        Err(e) if options.keep_going && !matches!(e, TModError::Cancelled) => {
+            let name = &file_entries[index].name;
+            eprintln!("  skipped `{}`: {}", console::text(name), e);
+            failures.push((name, e));
+            Ok(Vec::new())
+        }
+        result => result,
+    };
+    // what keeps an entry from being read at all, found before reading it.
+    // only `--keep-going` lets either through the entry table and preflight.
+    let mut unreadable = |index: usize| {
+        if let Some(e) = invalid_names.remove(&index) {
+            return Err(TModError::Utf8Error(e));
+        }
+        let end = offsets[index] + file_entries[index].compressed_len as usize;
+        match end > data_end {
+            true => Err(TModError::IoError(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!(
+                    "the file ends {} bytes before the entry's data does",
+                    end - data_end
+                ),
+            ))),
+            false => Ok(()),
+        }
+    };
+    // where the next entry starts after one that failed, as far as the
+    // table says, to read on from there
+    let entry_end =
+        |index: usize| (offsets[index] + file_entries[index].compressed_len as usize).min(data_end);
+
+    info!("Extracting files");
+    if jobs > 1 {
+        debug!("extracting on {} threads", jobs);
+        // bounded, so reading doesn't run ahead of the workers and pile up
+        // entries in memory
+        let (job_sender, job_receiver) = mpsc::sync_channel::<(usize, Vec<u8>)>(jobs);
+        let job_receiver = Mutex::new(job_receiver);
+        let (done_sender, done_receiver) = mpsc::channel();
+        std::thread::scope(|scope| {
+            for _ in 0..jobs {
+                let job_receiver = &job_receiver;
+                let done_sender = done_sender.clone();
+                let file_entries = &file_entries;
+                let mut sink = sink::DirSink {
+                    out_dir: out_dir.as_deref(),
+                    routes: &options.routes,
+                    force: options.force,
+                    allow_unsafe_paths: options.allow_unsafe_paths,
+                    retries: options.retries,
+                    cancel: options.cancel.clone(),
+                };
+                scope.spawn(move || loop {
+                    let job = job_receiver.lock().unwrap().recv();
+                    let (index, stored) = match job {
+                        Ok(job) => job,
+                        Err(_) => return,
+                    };
+                    let started = Instant::now();
+                    let file = &file_entries[index];
+                    let result =
+                        extract_entry(file, stored, options, &mut sink, None, log, started);
+                    if done_sender.send((index, result)).is_err() {
+                        return;
+                    }
+                });
+            }
+            drop(done_sender);
+
+            for &index in &arrangement {
+                if !selected[index] {
+                    continue;
+                }
+                let file = &file_entries[index];
+                trace!("reading file: {}", file.name);
+                options.cancel.check()?;
+                watchdog.begin(&file.name);
+                let stored = unreadable(index).and_then(|_| {
+                    if !sequential {
+                        reader.seek(offsets[index])?;
+                    }
+                    Ok(reader.read_bytes(file.compressed_len as usize)?)
+                });
+                match stored {
+                    Ok(stored) => {
+                        if job_sender.send((index, stored)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        finish_entry(index, skip_failed(index, Err(e))?)?;
+                        reader.seek(entry_end(index))?;
+                    }
+                }
+                while let Ok((index, result)) = done_receiver.try_recv() {
+                    finish_entry(index, skip_failed(index, result)?)?;
+                }
+            }
+            drop(job_sender);
+            for (index, result) in done_receiver {
+                finish_entry(index, skip_failed(index, result)?)?;
+            }
+            Ok::<_, TModError>(())
+        })?;
+    } else {
+        for index in arrangement {
+            if !selected[index] {
+                continue;
+            }
+            let file = &file_entries[index];
+            trace!("extracting file: {}", file.name);
+            let started = Instant::now();
+            options.cancel.check()?;
+            watchdog.begin(&file.name);
+            let result = unreadable(index).and_then(|_| {
+                if !sequential {
+                    reader.seek(offsets[index])?;
+                }
+                if options.low_memory {
+                    let mut contents = EntryReader::new(&mut reader, file);
+                    let file_path = sink.write_stream(file, &mut contents)?;
+                    let sha1 = contents.finish()?;
+                    Ok(vec![entry_log::EntryRecord::with_sha1(
+                        file,
+                        sha1,
+                        &file_path,
+                        started.elapsed(),
+                    )])
+                } else {
+                    let stored = reader.read_bytes(file.compressed_len as usize)?;
+                    extract_entry(
+                        file,
+                        stored,
+                        options,
+                        sink.as_mut(),
+                        lfs.as_mut(),
+                        log,
+                        started,
+                    )
+                }
+            });
+            let failed = result.is_err();
+            finish_entry(index, skip_failed(index, result)?)?;
+            if failed {
+                reader.seek(entry_end(index))?;
+            }
+        }
+    }
+    sink.finish()?;
+    progress.finish();
+
+    // the data of the skipped entries wasn't all read, so there is no hash to
+    // check either
+    if !failures.is_empty() {
+        eprintln!("Skipped {} entries:", failures.len());
+        for (name, e) in &failures {
+            eprintln!("  `{}`: {}", console::text(name), e);
+        }
+        return Err(TModError::EntriesFailed(failures.len(), selected_count));
+    }
+
+    // seeking around the data region means the streamed hash is meaningless,
+    // so hash it again in one sequential pass instead. a sample is meant to be
+    // quick, so it isn't checked at all.
+    if options.sample.is_some() {
+        debug!("Not checking the hash of a sampled extraction");
+        hasher.finish();
+    } else if !sequential {
+        hash_data_region(&mut reader, &hasher, header.data_offset, data_end)?;
+    }
+
+    if let Some(computed_hash) = hasher.finish() {
+        if computed_hash == header.hash {
+            debug!("Hash verified: {}", hex::encode(&computed_hash));
+        } else if options.verify {
+            return Err(TModError::HashMismatch(
+                hex::encode(&header.hash),
+                hex::encode(&computed_hash),
+            ));
+        } else {
+            warn!(
+                "Hash mismatch: stored {}, computed {}",
+                hex::encode(&header.hash),
+                hex::encode(&computed_hash)
+            );
+        }
+    }
+
+    if let (true, Some(out_dir)) = (options.git_init, &out_dir) {
+        git::commit_extraction(out_dir, &header)?;
+    }
+
+    match (&options.archive, &out_dir) {
+        (Some((_, archive_path)), _) => info!("Done! Your files are in: {:?}", archive_path),
+        (None, Some(out_dir)) if options.routes.is_empty() => {
+            info!("Done! Your files are in: {:?}", out_dir)
+        }
+        _ => info!("Done! Your files were routed to their destinations"),
+    }
+
+    Ok(())
+}
+
+// decompresses, converts and writes out one entry once its stored bytes have
+// been read, returning its records for `--emit-entries` if `log` is set
+fn extract_entry(
+    file: &ModFile,
+    stored: Vec<u8>,
+    options: &UnpackOptions,
+    sink: &mut dyn sink::OutputSink,
+    mut lfs: Option<&mut lfs::LfsStore>,
+    log: bool,
+    started: Instant,
+) -> Result<Vec<entry_log::EntryRecord>, TModError> {
+    let mut file_data = decompress(file, stored)?;
+    if options.normalize.is_enabled() {
+        file_data = options.normalize.apply(file_data);
+    }
+    let renamed;
+    let mut file = file;
+    if let Some(transform) = transform::find(&options.transforms, &file.name) {
+        file_data = transform.apply(&file.name, file_data)?;
+        // the result is written as is, so it counts as stored
+        renamed = ModFile {
+            name: transform.output_name(&file.name),
+            uncompressed_len: file_data.len() as i32,
+            compressed_len: file_data.len() as i32,
+        };
+        file = &renamed;
+    }
+    let thumbnail = match options.thumbnails {
+        Some(size) => thumbnail::thumbnail(&file.name, &file_data, size)?.map(|data| {
+            let len = data.len() as i32;
+            let thumbnail_file = ModFile {
+                name: thumbnail::thumbnail_name(&file.name),
+                uncompressed_len: len,
+                compressed_len: len,
+            };
+            (thumbnail_file, data)
+        }),
+        None => None,
+    };
+
+    let mut written = Vec::with_capacity(2);
+    if !(options.thumbnails_only && thumbnail.is_some()) {
+        written.push((file, file_data.as_slice()));
+    }
+    if let Some((thumbnail_file, data)) = &thumbnail {
+        written.push((thumbnail_file, data.as_slice()));
+    }
+    let mut records = Vec::new();
+    for (file, data) in written {
+        let pointer = match &mut lfs {
+            Some(lfs) => lfs.store(&file.name, data)?,
+            None => None,
+        };
+        let data = pointer.as_deref().unwrap_or(data);
+        let file_path = sink.write_entry(file, data)?;
+        if log {
+            records.push(entry_log::EntryRecord::new(
+                file,
+                data,
+                &file_path,
+                started.elapsed(),
+            ));
+        }
+    }
+    Ok(records)
+}
+
+fn hash_data_region(
+    reader: &mut BinaryReader,
+    hasher: &DataHasher,
+    start: usize,
+    end: usize,
+) -> Result<(), TModError> {
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    trace!("rehashing data region");
+    reader.seek(start)?;
+    hasher.start();
+    let mut position = start;
+    while position < end {
+        let len = CHUNK_SIZE.min(end - position);
+        reader.read_bytes(len)?;
+        position += len;
+    }
+    Ok(())
+}
+
+// ask a yes/no question on the terminal, defaulting to no. it goes to
+// stderr when stdout is taken by the output
+fn confirm(question: &str, on_stderr: bool) -> Result<bool, TModError> {
+    let answer = ask(&format!("{} [y/N]", question), on_stderr)?;
+    Ok(matches!(answer.as_str(), "y" | "yes"))
+}
+
+// the answer to a question on the terminal, trimmed and in lowercase
+fn ask(question: &str, on_stderr: bool) -> Result<String, TModError> {
+    if on_stderr {
+        eprint!("{} ", question);
+    } else {
+        print!("{} ", question);
+        std::io::stdout().flush()?;
+    }
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_lowercase())
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+// every .tmod below `dir`, a Mods folder or a workshop download directory
+pub fn collect_mods(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), TModError> {
+    for dir_entry in std::fs::read_dir(dir)? {
+        let path = dir_entry?.path();
+        if path.is_dir() {
+            collect_mods(&path, paths)?;
+        } else if path.extension().map_or(false, |ext| ext == "tmod") {
+            trace!("found mod: {:?}", path);
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+// `<ModName>_<Version>`, the directory a mod gets when several are extracted
+// at once, so different versions of a mod don't overwrite each other
+pub fn batch_dir_name(path: &str) -> Result<String, TModError> {
+    let mut stream = source::open(path)?;
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+    let header = read_header(&mut reader, None)?;
+    // both come from the archive, so keep them to a single path component
+    let clean = |s: &str| s.replace(['/', '\\', ':'], "_").replace("..", "_");
+    Ok(format!(
+        "{}_{}",
+        clean(&header.mod_name),
+        clean(&header.mod_version)
+    ))
+}
+
+pub fn fingerprint(path: String) -> Result<(), TModError> {
+    trace!("opening file: {}", path);
+    let mut stream = source::open(&path)?;
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+
+    let header = read_header(&mut reader, None)?;
+    println!("{}", header.fingerprint());
+
+    Ok(())
+}
+
+// the entry table, refusing negative lengths: nothing after such an entry can
+// be found, and nothing should allocate what it claims
+pub fn read_entry_table(reader: &mut BinaryReader) -> Result<Vec<ModFile>, TModError> {
+    let file_entries = read_entry_table_unchecked(reader)?;
+    reject_negative_lengths(&file_entries)?;
+    Ok(file_entries)
+}
+
+// the table as it is written, for `check`, which reports bad lengths instead
+// of failing on them
+pub(crate) fn read_entry_table_unchecked(
+    reader: &mut BinaryReader,
+) -> Result<Vec<ModFile>, TModError> {
+    trace!("reading file count");
+    let file_count = reader.read_i32()?;
+    debug!("File count: {}", file_count);
+
+    let mut file_entries = Vec::with_capacity(file_count.max(0) as usize);
+
+    info!("Reading file entries");
+    for _ in 0..file_count {
+        file_entries.push(read_file_entry(reader)?);
+    }
+
+    if file_entries.len() != file_count.max(0) as usize {
+        return Err(TModError::MissingFileEntries(
+            file_entries.len(),
+            file_count as usize,
+        ));
+    }
+
+    Ok(file_entries)
+}
+
+// like `read_entry_table`, but a name that isn't valid UTF-8 doesn't fail the
+// whole table: it is decoded lossily, and those entries are returned by index
+// so they can be skipped (`--keep-going`)
+pub fn read_entry_table_lossy(
+    reader: &mut BinaryReader,
+) -> Result<(Vec<ModFile>, HashMap<usize, std::string::FromUtf8Error>), TModError> {
+    let file_count = reader.read_i32()?;
+    debug!("File count: {}", file_count);
+
+    let mut file_entries = Vec::with_capacity(file_count.max(0) as usize);
+    let mut invalid = HashMap::new();
+    for index in 0..file_count.max(0) as usize {
+        let name = read_csharp_bytes(reader)?;
+        let name = match String::from_utf8(name) {
+            Ok(name) => name,
+            Err(e) => {
+                let name = String::from_utf8_lossy(e.as_bytes()).into_owned();
+                invalid.insert(index, e);
+                name
+            }
+        };
+        file_entries.push(ModFile {
+            name,
+            uncompressed_len: reader.read_i32()?,
+            compressed_len: reader.read_i32()?,
+        });
+    }
+    reject_negative_lengths(&file_entries)?;
+    Ok((file_entries, invalid))
+}
+
+fn reject_negative_lengths(file_entries: &[ModFile]) -> Result<(), TModError> {
+    let problems = preflight::negative_lengths(file_entries);
+    if problems.is_empty() {
+        return Ok(());
+    }
+    for problem in &problems {
+        eprintln!("  {}", problem);
+    }
+    Err(TModError::InvalidEntryTable(problems.len()))
+}
+
+pub fn read_file_entry(reader: &mut BinaryReader) -> Result<ModFile, TModError> {
+    trace!("reading file entry name");
+    let file_name = read_csharp_string(reader)?;
+    trace!("File name: {}", file_name);
+
+    trace!("reading uncompressed length");
+    let uncompressed_len = reader.read_i32()?;
+    trace!("Uncompressed length: {}", uncompressed_len);
+
+    trace!("reading compressed length");
+    let compressed_len = reader.read_i32()?;
+    trace!("Compressed length: {}", compressed_len);
+
+    Ok(ModFile {
+        name: file_name,
+        uncompressed_len,
+        compressed_len,
+    })
+}
+
+// read a file's data from the data section, decompressing it if necessary
+pub fn read_file_data(reader: &mut BinaryReader, file: &ModFile) -> Result<Vec<u8>, TModError> {
+    let file_data = read_stored(reader, file)?;
+    decompress(file, file_data)
+}
+
+// a file's data as stored, without decompressing it
+pub fn read_stored(reader: &mut BinaryReader, file: &ModFile) -> Result<Vec<u8>, TModError> {
+    let len = usize::try_from(file.compressed_len).map_err(|_| TModError::InvalidEntryTable(1))?;
+    // checked before allocating, and because a short read would come back
+    // padded with zeroes
+    let available = reader.len()?.saturating_sub(reader.tell()?);
+    if available < len {
+        return Err(TModError::TruncatedEntry(file.name.clone(), len, available));
+    }
+    Ok(reader.read_bytes(len)?)
+}
+
+// an entry's contents from its stored bytes
+pub fn decompress(file: &ModFile, file_data: Vec<u8>) -> Result<Vec<u8>, TModError> {
+    if file.compressed_len == file.uncompressed_len {
+        trace!("file is not compressed: {}", file.name);
+        return Ok(file_data);
+    }
+    decompress_slice(file, &file_data)
+}
+
+// the same for stored bytes borrowed from a mapping or a buffer, which are
+// only copied if the entry isn't compressed
+pub fn decompress_slice(file: &ModFile, file_data: &[u8]) -> Result<Vec<u8>, TModError> {
+    if file.compressed_len == file.uncompressed_len {
+        trace!("file is not compressed: {}", file.name);
+        return Ok(file_data.to_vec());
+    }
+
+    trace!("decompressing file: {}", file.name);
+    let backend = inflate::selected();
+    let contents = backend.inflate(file_data, file.uncompressed_len as usize)?;
+    // a backend may stop short of the declared length without an error
+    if contents.len() != file.uncompressed_len as usize {
+        return Err(TModError::InflateError(
+            backend.label(),
+            format!(
+                "{} inflates to {} bytes, not the {} the entry table says",
+                file.name,
+                contents.len(),
+                file.uncompressed_len
+            ),
+        ));
+    }
+    Ok(contents)
+}
+
+// some downloads come with junk in front of the archive, typically an HTML
+// error page or a proxy's banner. look for the magic a little way in.
+fn find_magic(reader: &mut BinaryReader) -> Result<Option<usize>, TModError> {
+    let position = magic_offset(reader)?;
+    if let Some(position) = position {
+        warn_skipped(reader, position)?;
+    }
+    Ok(position)
+}
+
+pub(crate) fn warn_skipped(reader: &mut BinaryReader, position: usize) -> Result<(), TModError> {
+    reader.seek(0)?;
+    let kind = match reader.read_bytes(1)?.first() {
+        Some(b'<') => " (looks like an HTML page)",
+        _ => "",
+    };
+    warn!("skipped {} bytes before the TMOD header{}", position, kind);
+    Ok(())
+}
+
+// where the magic starts, without saying anything about it
+pub(crate) fn magic_offset(reader: &mut BinaryReader) -> Result<Option<usize>, TModError> {
+    const MAX_PREFIX: usize = 64 * 1024;
+
+    reader.seek(0)?;
+    let len = reader.len()?.min(MAX_PREFIX + TMOD_HEADER.len());
+    let mut start = Vec::with_capacity(len);
+    while start.len() < len {
+        let chunk = reader.read_bytes(len - start.len())?;
+        if chunk.is_empty() {
+            break;
+        }
+        start.extend(chunk);
+    }
+
+    Ok(start
+        .windows(TMOD_HEADER.len())
+        .position(|window| window == TMOD_HEADER))
+}
+
+// if a hasher is given, it is started right before the data region (the part
+// of the file covered by the stored hash) is read
+pub fn read_header(
+    reader: &mut BinaryReader,
+    hasher: Option<&DataHasher>,
+) -> Result<ModHeader, TModError> {
+    trace!("reading header");
+    let header = reader.read_bytes(4)?;
+    let mut prefix_len = 0;
+    if header != TMOD_HEADER {
+        prefix_len = find_magic(reader)?.ok_or(TModError::InvalidHeader(header, TMOD_HEADER))?;
+        reader.seek(prefix_len + TMOD_HEADER.len())?;
+    }
+    debug!("TMOD header found");
+
+    trace!("reading tmodloader version");
+    let tmodloader_version = read_csharp_string(reader)?;
+    info!("For tModLoader version: {}", tmodloader_version);
+
+    trace!("reading mod hash");
+    let hash = reader.read_bytes(20)?;
+    let hash_str = hex::encode(&hash);
+    debug!("Hash: {}", hash_str);
+
+    trace!("reading signature");
+    let signature = reader.read_bytes(256)?;
+    let signature_str = hex::encode(&signature);
+    debug!("Signature: {}", signature_str);
+
+    trace!("reading file data length");
+    let file_data_len = reader.read_u32()?;
+    debug!("File data length: {}", file_data_len);
+
+    let data_offset = reader.tell()?;
+
+    if let Some(hasher) = hasher {
+        trace!("hashing data region");
+        hasher.start();
+    }
+
+    trace!("reading mod name");
+    let mod_name = read_csharp_string(reader)?;
+    info!("Mod name: {}", mod_name);
+
+    trace!("reading mod version");
+    let mod_version = read_csharp_string(reader)?;
+    info!("Mod version: {}", mod_version);
+
+    Ok(ModHeader {
+        prefix_len,
+        tmodloader_version,
+        hash,
+        signature,
+        file_data_len,
+        data_offset,
+        mod_name,
+        mod_version,
+    })
+}
+
+// read a 7 bit encoded string length
+// then read that many bytes into a string
+pub fn read_csharp_string(reader: &mut BinaryReader) -> Result<String, TModError> {
+    Ok(String::from_utf8(read_csharp_bytes(reader)?)?)
+}
+
+// the same without checking the contents are UTF-8
+fn read_csharp_bytes(reader: &mut BinaryReader) -> Result<Vec<u8>, TModError> {
+    let mut string_len = 0;
+    let mut done = false;
+    let mut step = 0;
+    while !done {
+        let byte = reader.read_u8()?;
+        string_len |= ((byte & 0x7F) as u32) << (step * 7);
+        done = (byte & 0x80) == 0;
+        step += 1;
+    }
+    Ok(reader.read_bytes(string_len as usize)?)
+}
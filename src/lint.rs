@@ -0,0 +1,334 @@
+use binary_rw::BinaryReader;
+use log::{info, trace};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::audio::{self, AudioIssue};
+use crate::gallery::png_size;
+use crate::localization::localization_file;
+use crate::names::extension;
+use crate::output::{self, OutputFormat};
+use crate::source;
+use crate::{rawimg, read_entry_table, read_file_data, read_header, ModFile, TModError};
+
+// the mod browser shows icons at this size
+const ICON_SIZE: (u32, u32) = (80, 80);
+
+const LARGE_FILE_THRESHOLD: usize = 10 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Allow,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Allow => "allow",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum Lint {
+    MissingIcon,
+    IconSize,
+    UncompressedPng,
+    LargeFile,
+    LocalizationNewline,
+    UncompressedAudio,
+    AudioBitrate,
+}
+
+const LINTS: &[Lint] = &[
+    Lint::MissingIcon,
+    Lint::IconSize,
+    Lint::UncompressedPng,
+    Lint::LargeFile,
+    Lint::LocalizationNewline,
+    Lint::UncompressedAudio,
+    Lint::AudioBitrate,
+];
+
+impl Lint {
+    pub fn parse(value: &str) -> Result<Self, TModError> {
+        LINTS
+            .iter()
+            .copied()
+            .find(|lint| lint.label() == value)
+            .ok_or_else(|| TModError::UnknownLint(value.to_string()))
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Lint::MissingIcon => "missing-icon",
+            Lint::IconSize => "icon-size",
+            Lint::UncompressedPng => "uncompressed-png",
+            Lint::LargeFile => "large-file",
+            Lint::LocalizationNewline => "localization-newline",
+            Lint::UncompressedAudio => "uncompressed-audio",
+            Lint::AudioBitrate => "audio-bitrate",
+        }
+    }
+
+    pub fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct LintFinding {
+    pub lint: Lint,
+    pub severity: Severity,
+    // the entry the finding is about, if any
+    pub entry: Option<String>,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct LintReport {
+    pub mod_name: String,
+    pub mod_version: String,
+    pub fingerprint: String,
+    pub files: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub findings: Vec<LintFinding>,
+}
+
+#[derive(Default)]
+pub struct LintOptions {
+    pub format: OutputFormat,
+    // `--allow`, `--warn` and `--deny`, later ones win
+    pub levels: Vec<(Lint, Severity)>,
+    // `--deny warnings`: every warning counts as an error
+    pub deny_warnings: bool,
+}
+
+impl LintOptions {
+    fn severity(&self, lint: Lint) -> Severity {
+        let severity = self
+            .levels
+            .iter()
+            .rev()
+            .find(|(l, _)| *l == lint)
+            .map_or(lint.default_severity(), |(_, severity)| *severity);
+        if self.deny_warnings && severity == Severity::Warning {
+            Severity::Error
+        } else {
+            severity
+        }
+    }
+}
+
+pub fn lint(path: String, options: &LintOptions) -> Result<(), TModError> {
+    trace!("opening file: {}", path);
+    let mut stream = source::open(&path)?;
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+
+    let header = read_header(&mut reader, None)?;
+
+    let file_entries = read_entry_table(&mut reader)?;
+
+    info!("Linting file entries");
+    let mut findings = Vec::new();
+    let mut push = |lint: Lint, entry: Option<&str>, message: String| {
+        let severity = options.severity(lint);
+        if severity != Severity::Allow {
+            findings.push(LintFinding {
+                lint,
+                severity,
+                entry: entry.map(str::to_string),
+                message,
+            });
+        }
+    };
+
+    let mut has_icon = false;
+    for file in &file_entries {
+        let data = read_file_data(&mut reader, file)?;
+        let name = file.name.as_str();
+
+        if name == "icon.png" || name == "icon.rawimg" {
+            has_icon = true;
+            let size = if name == "icon.png" {
+                png_size(&data)
+            } else {
+                rawimg::parse(name, &data)
+                    .ok()
+                    .map(|image| (image.width, image.height))
+            };
+            match size {
+                Some(size) if size != ICON_SIZE => push(
+                    Lint::IconSize,
+                    Some(name),
+                    format!(
+                        "icon is {}x{}, expected {}x{}",
+                        size.0, size.1, ICON_SIZE.0, ICON_SIZE.1
+                    ),
+                ),
+                Some(_) => {}
+                None => push(
+                    Lint::IconSize,
+                    Some(name),
+                    "icon isn't a valid image".to_string(),
+                ),
+            }
+        }
+
+        if extension(name).as_deref() == Some("png") && stored_png(&data) {
+            push(
+                Lint::UncompressedPng,
+                Some(name),
+                "PNG image data is stored without compression".to_string(),
+            );
+        }
+
+        match audio::check(name, &data) {
+            Some(issue @ AudioIssue::UncompressedWav) => {
+                push(Lint::UncompressedAudio, Some(name), issue.message())
+            }
+            Some(issue @ AudioIssue::HighBitrate(_)) => {
+                push(Lint::AudioBitrate, Some(name), issue.message())
+            }
+            None => {}
+        }
+
+        if data.len() > LARGE_FILE_THRESHOLD {
+            push(
+                Lint::LargeFile,
+                Some(name),
+                format!("file is {} bytes", data.len()),
+            );
+        }
+
+        if localization_file(name).is_some() {
+            lint_localization(file, &data, &mut push);
+        }
+    }
+    if !has_icon {
+        push(
+            Lint::MissingIcon,
+            None,
+            "the mod has no icon.png".to_string(),
+        );
+    }
+
+    let count = |severity| findings.iter().filter(|f| f.severity == severity).count();
+    let report = LintReport {
+        mod_name: header.mod_name.clone(),
+        mod_version: header.mod_version.clone(),
+        fingerprint: header.fingerprint(),
+        files: file_entries.len(),
+        errors: count(Severity::Error),
+        warnings: count(Severity::Warning),
+        findings,
+    };
+
+    if options.format == OutputFormat::Text {
+        print_report(&report);
+    } else if options.format == OutputFormat::Github {
+        for finding in &report.findings {
+            println!(
+                "{}",
+                output::github_annotation(
+                    finding.severity.label(),
+                    finding.entry.as_deref(),
+                    &format!("{} ({})", finding.message, finding.lint.label())
+                )
+            );
+        }
+    } else {
+        println!("{}", output::render(options.format, "lint", &report)?);
+    }
+
+    if report.errors > 0 {
+        return Err(TModError::LintFailed(report.errors));
+    }
+    Ok(())
+}
+
+fn print_report(report: &LintReport) {
+    println!(
+        "Lint of {} v{} ({})",
+        report.mod_name, report.mod_version, report.fingerprint
+    );
+    for finding in &report.findings {
+        let entry = match &finding.entry {
+            Some(entry) => format!("{}: ", entry),
+            None => String::new(),
+        };
+        println!(
+            "  {}[{}] {}{}",
+            finding.severity.label(),
+            finding.lint.label(),
+            entry,
+            finding.message
+        );
+    }
+    println!(
+        "{} error(s), {} warning(s) in {} files",
+        report.errors, report.warnings, report.files
+    );
+}
+
+// `'''` strings keep their line breaks verbatim, which is rarely what was
+// meant for text shown in the game; tModLoader's own files use `\n` instead
+fn lint_localization(
+    file: &ModFile,
+    data: &[u8],
+    push: &mut impl FnMut(Lint, Option<&str>, String),
+) {
+    let text = String::from_utf8_lossy(data);
+    let mut in_multiline = false;
+    // the string may start on the line after its key
+    let mut key = "";
+    for (number, line) in text.lines().enumerate() {
+        if !in_multiline {
+            if let Some((name, _)) = line.split_once(':') {
+                key = name.trim();
+            }
+        }
+        let quotes = line.matches("'''").count();
+        if !in_multiline && quotes % 2 == 1 {
+            push(
+                Lint::LocalizationNewline,
+                Some(&file.name),
+                format!(
+                    "`{}` on line {} is a multi-line string with raw line breaks",
+                    key,
+                    number + 1
+                ),
+            );
+        }
+        if quotes % 2 == 1 {
+            in_multiline = !in_multiline;
+        }
+    }
+}
+
+// whether the first IDAT chunk starts with a deflate block that is stored
+// rather than compressed
+fn stored_png(data: &[u8]) -> bool {
+    let mut at = 8;
+    while let Some(header) = data.get(at..at + 8) {
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        if &header[4..8] == b"IDAT" {
+            // two bytes of zlib header, then the block type in bits 1 and 2
+            return matches!(data.get(at + 10), Some(byte) if (byte >> 1) & 0b11 == 0);
+        }
+        at += 12 + len;
+    }
+    false
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::names::extension;
+use crate::TModError;
+
+// the cultures tModLoader ships translations for
+const CULTURES: &[&str] = &[
+    "en-US", "de-DE", "it-IT", "fr-FR", "es-ES", "ru-RU", "zh-Hans", "pt-BR", "pl-PL",
+];
+
+// every key of one language, flattened to dotted paths like
+// `Mods.MyMod.Items.Foo.DisplayName`
+pub type Keys = BTreeMap<String, String>;
+
+// where a localization file's keys go: its language, and the prefix its file
+// name adds to every key (`en-US_Mods.MyMod.hjson` has the prefix
+// `Mods.MyMod`)
+pub struct LocalizationFile {
+    pub language: &'static str,
+    pub prefix: Option<String>,
+}
+
+// recognizes the layouts tModLoader accepts: `en-US.hjson`,
+// `en-US_Mods.MyMod.hjson` and `en-US/Mods.MyMod.hjson`, anywhere in the mod
+pub fn localization_file(name: &str) -> Option<LocalizationFile> {
+    if extension(name).as_deref() != Some("hjson") {
+        return None;
+    }
+    let mut parts: Vec<&str> = name.split('/').collect();
+    let file_name = parts.pop()?;
+    let stem = &file_name[..file_name.len() - ".hjson".len()];
+
+    for culture in CULTURES {
+        if stem == *culture {
+            return Some(LocalizationFile {
+                language: culture,
+                prefix: None,
+            });
+        }
+        let with_prefix = stem
+            .strip_prefix(culture)
+            .and_then(|rest| rest.strip_prefix('_'));
+        if let Some(prefix) = with_prefix {
+            return Some(LocalizationFile {
+                language: culture,
+                prefix: Some(prefix.to_string()),
+            });
+        }
+        if parts.contains(culture) {
+            return Some(LocalizationFile {
+                language: culture,
+                prefix: Some(stem.to_string()),
+            });
+        }
+    }
+    None
+}
+
+// parses one localization file and adds its keys to `keys`
+pub fn parse_keys(
+    name: &str,
+    prefix: Option<&str>,
+    text: &str,
+    keys: &mut Keys,
+) -> Result<(), TModError> {
+    let value = parse_value(name, text)?;
+    flatten(prefix.unwrap_or(""), &value, keys);
+    Ok(())
+}
+
+pub fn parse_value(name: &str, text: &str) -> Result<Value, TModError> {
+    // tModLoader's files usually leave out the braces around the root object,
+    // which deser-hjson only accepts when it knows the target type up front
+    Ok(match deser_hjson::from_str::<Value>(text) {
+        Ok(value @ Value::Object(_)) => value,
+        _ => deser_hjson::from_str(&format!("{{\n{}\n}}", text))
+            .map_err(|e| TModError::LocalizationError(name.to_string(), e.to_string()))?,
+    })
+}
+
+fn flatten(path: &str, value: &Value, keys: &mut Keys) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                flatten(&join(key), value, keys);
+            }
+        }
+        Value::Array(values) => {
+            for (index, value) in values.iter().enumerate() {
+                flatten(&join(&index.to_string()), value, keys);
+            }
+        }
+        Value::String(text) => {
+            keys.insert(path.to_string(), text.clone());
+        }
+        Value::Null => {
+            keys.insert(path.to_string(), String::new());
+        }
+        other => {
+            keys.insert(path.to_string(), other.to_string());
+        }
+    }
+}
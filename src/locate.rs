@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+
+use binary_rw::BinaryReader;
+use log::{debug, trace};
+
+use crate::stats::version_key;
+use crate::{collect_mods, read_header, source, TModError};
+
+// tModLoader's Steam app id, workshop mods are under this in every library
+pub const TMODLOADER_APP_ID: &str = "1281930";
+
+// every place tModLoader keeps mods on this platform, whether it exists or
+// not: the local Mods folder first, then the workshop folder of every Steam
+// library
+pub fn mods_dirs() -> Vec<PathBuf> {
+    let home = match std::env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" }) {
+        Some(home) => PathBuf::from(home),
+        None => return Vec::new(),
+    };
+    let (mods, steam_roots): (&str, Vec<PathBuf>) = if cfg!(windows) {
+        let program_files = std::env::var_os("ProgramFiles(x86)")
+            .map_or_else(|| PathBuf::from(r"C:\Program Files (x86)"), PathBuf::from);
+        (
+            r"Documents\My Games\Terraria\tModLoader\Mods",
+            vec![program_files.join("Steam")],
+        )
+    } else if cfg!(target_os = "macos") {
+        (
+            "Library/Application Support/Terraria/tModLoader/Mods",
+            vec![home.join("Library/Application Support/Steam")],
+        )
+    } else {
+        (
+            ".local/share/Terraria/tModLoader/Mods",
+            vec![
+                home.join(".steam/steam"),
+                home.join(".local/share/Steam"),
+                home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"),
+            ],
+        )
+    };
+
+    let mut dirs = vec![home.join(mods)];
+    let mut libraries = Vec::new();
+    for root in steam_roots {
+        libraries.extend(library_folders(&root));
+        libraries.push(root);
+    }
+    for library in libraries {
+        let dir = library
+            .join("steamapps")
+            .join("workshop")
+            .join("content")
+            .join(TMODLOADER_APP_ID);
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+// the other Steam libraries listed in a Steam root's `libraryfolders.vdf`,
+// as `"path"  "D:\\SteamLibrary"` lines
+fn library_folders(root: &Path) -> Vec<PathBuf> {
+    let vdf = match std::fs::read_to_string(root.join("steamapps/libraryfolders.vdf")) {
+        Ok(vdf) => vdf,
+        Err(_) => return Vec::new(),
+    };
+    vdf.lines()
+        .filter_map(|line| {
+            let mut fields = line.split('"').filter(|field| !field.trim().is_empty());
+            match (fields.next(), fields.next()) {
+                (Some("path"), Some(path)) => Some(PathBuf::from(path.replace("\\\\", "\\"))),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// the newest installed copy of a mod, by its internal name (the name of its
+// .tmod file)
+pub fn find_mod(name: &str) -> Result<PathBuf, TModError> {
+    let mut candidates = Vec::new();
+    for dir in mods_dirs().iter().filter(|dir| dir.is_dir()) {
+        trace!("searching for {}: {:?}", name, dir);
+        let mut found = Vec::new();
+        collect_mods(dir, &mut found)?;
+        candidates.extend(
+            found
+                .into_iter()
+                .filter(|path| path.file_stem().map_or(false, |stem| stem == name)),
+        );
+    }
+    newest(candidates).ok_or_else(|| TModError::ModNotFound(name.to_string()))
+}
+
+// the newest of several copies of a mod. the version in the header decides,
+// then the modification time, since the workshop folder keeps a copy for
+// every tModLoader release.
+pub fn newest(candidates: Vec<PathBuf>) -> Option<PathBuf> {
+    let mut versions = Vec::new();
+    for path in candidates {
+        let version = match mod_version(&path) {
+            Ok(version) => version,
+            Err(e) => {
+                debug!("skipping {:?}: {}", path, e);
+                continue;
+            }
+        };
+        let modified = std::fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or(std::time::UNIX_EPOCH);
+        versions.push(((version_key(&version), modified), path));
+    }
+    versions
+        .into_iter()
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, path)| path)
+}
+
+fn mod_version(path: &Path) -> Result<String, TModError> {
+    let mut stream = source::open(&path.to_string_lossy())?;
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+    Ok(read_header(&mut reader, None)?.mod_version)
+}
@@ -0,0 +1,51 @@
+use std::{
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+};
+
+use fs2::FileExt;
+use log::trace;
+
+use crate::TModError;
+
+// an advisory lock on an output directory or archive, held until dropped.
+// the lock file is left behind on purpose: removing it would let a waiting
+// invocation and a new one each lock a different file.
+pub struct OutputLock {
+    _file: File,
+}
+
+// `out/` is locked through `.out.lock` next to it, so the lock file doesn't
+// end up among the extracted files
+fn lock_path(target: &Path) -> PathBuf {
+    match target.file_name() {
+        Some(name) => target.with_file_name(format!(".{}.lock", name.to_string_lossy())),
+        None => target.join(".tmod-unpacker.lock"),
+    }
+}
+
+pub fn lock(target: &Path, wait: bool) -> Result<OutputLock, TModError> {
+    let path = lock_path(target);
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+    trace!("locking {:?}", path);
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)?;
+    if file.try_lock_exclusive().is_err() {
+        if !wait {
+            return Err(TModError::OutputLocked(target.to_path_buf()));
+        }
+        eprintln!(
+            "Waiting for another invocation writing to {} to finish",
+            target.display()
+        );
+        file.lock_exclusive()?;
+    }
+    Ok(OutputLock { _file: file })
+}
@@ -1,218 +1,702 @@
-#![allow(unused_imports)]
-use std::{io::Read, path::PathBuf};
-
-use binary_rw::{BinaryError, BinaryReader, FileStream};
-use log::{debug, error, info, trace, warn};
-use thiserror::Error;
-
-#[cfg(target_os = "linux")]
-use progress_bar::*;
-
-#[derive(Debug, Error)]
-enum TModError {
-    #[error("No input file given")]
-    NoInputFile,
-    #[error("No output directory given")]
-    NoOutputDirectory,
-    #[error("Invalid header: `{0:?}`, expected `{1:?}`")]
-    InvalidHeader(Vec<u8>, &'static [u8]),
-    #[error("Missing some file entries: have {0}, expected {1}")]
-    MissingFileEntries(usize, usize),
-    #[error("IO error: {0}")]
-    IoError(#[from] std::io::Error),
-    #[error("Read error: {0}")]
-    ReadError(#[from] binary_rw::BinaryError),
-    #[error("UTF8 error: {0}")]
-    Utf8Error(#[from] std::string::FromUtf8Error),
-}
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-struct ModFile {
-    pub name: String,
-    pub uncompressed_len: i32,
-    pub compressed_len: i32,
-}
+use clap::{CommandFactory, FromArgMatches};
+use is_terminal::IsTerminal;
+use log::warn;
+
+use tmod_unpacker::order::ExtractOrder;
+use tmod_unpacker::sample::Sample;
+use tmod_unpacker::*;
+
+mod cli;
+
+use cli::{Cli, Command, CorpusCommand, PackArgs, UnpackArgs};
 
 fn main() {
     env_logger::init();
 
-    if let Err(e) = unpack() {
-        eprintln!("Error: {}", e);
-        if matches!(e, TModError::NoInputFile | TModError::NoOutputDirectory) {
+    let console = console::init();
+    let own_window = console::owns_window();
+    let result = run();
+    if let Err(e) = &result {
+        print_error(e);
+        if e.is_usage_error() {
             println!();
             show_usage();
         }
     }
+
+    if own_window && std::io::stdin().is_terminal() {
+        if result.is_ok() {
+            println!("Done.");
+        }
+        print!("Press Enter to close this window");
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stdin().read_line(&mut String::new());
+    }
+    // `exit` skips destructors
+    drop(console);
+
+    // so scripts and CI can tell any failure from a clean run. usage errors
+    // exit like the ones clap reports itself.
+    match &result {
+        Ok(()) => {}
+        Err(e) if e.is_usage_error() => std::process::exit(2),
+        Err(_) => std::process::exit(1),
+    }
 }
 
 fn show_usage() {
-    println!(
-        "Usage: {} <input file> <output directory>",
-        env!("CARGO_PKG_NAME")
-    );
-    println!("Extracts the contents of a tModLoader mod file.\n");
-    println!("Set the RUST_LOG environment variable to set the log level.\n");
-    println!("{} v{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    let _ = Cli::command().print_help();
 }
 
-const TMOD_HEADER: &[u8] = b"TMOD";
-
-fn unpack() -> Result<(), TModError> {
-    let mut args = std::env::args();
-    let _ = args.next();
-
-    let path = args.next().ok_or(TModError::NoInputFile)?;
-    if path == "-h" || path == "--help" {
-        show_usage();
-        return Ok(());
+fn run() -> Result<(), TModError> {
+    // argument errors, `--help` and `--version` are printed by clap, which
+    // exits right away
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    console::set_ascii_only(cli.ascii_only);
+
+    let command = match cli.command {
+        Some(command) => command,
+        None => return unpack_command(cli.unpack),
+    };
+    match command {
+        Command::Unpack(args) => unpack_command(args),
+        Command::Info(args) => info::info(
+            args.input,
+            &info::InfoOptions {
+                format: args.format.resolve()?,
+                copy: args.copy,
+                compact: false,
+            },
+        ),
+        Command::List(args) => info::list(
+            args.info.input,
+            &info::InfoOptions {
+                format: args.info.format.resolve()?,
+                copy: args.info.copy,
+                compact: args.compact,
+            },
+        ),
+        Command::Pack(args) => pack_command(args),
+        Command::Check(args) => check::check(
+            args.input,
+            &check::CheckOptions {
+                fast: args.fast,
+                key: signature::PublicKey::select(args.check_signature, args.pubkey.as_deref())?,
+                format: args.format.resolve()?,
+            },
+        ),
+        Command::Fingerprint { input } => fingerprint(input),
+        Command::Layout { input, format } => layout::layout(input, format.resolve()?),
+        Command::Explain { code } => {
+            let code = explain::normalize_code(&code);
+            let explanation =
+                explain::explanation(&code).ok_or(TModError::UnknownErrorCode(code.clone()))?;
+            println!("{}\n\n{}", code, explanation);
+            Ok(())
+        }
+        Command::Capabilities(format) => capabilities::print_capabilities(format.resolve()?),
+        Command::Schema { kind, out_dir } => match out_dir {
+            Some(out_dir) => schema::write_schemas(&out_dir),
+            None => schema::print_schema(kind.as_deref()),
+        },
+        Command::View { input, entry } => view::view(input, &entry),
+        Command::Cat { input, entry } => view::cat(input, &entry),
+        Command::Grep {
+            input,
+            pattern,
+            ignore_case,
+        } => view::grep(input, &pattern, ignore_case),
+        Command::VerifyDir {
+            dir,
+            against,
+            format,
+        } => {
+            let format = format.resolve()?;
+            let manifest = verify_dir::load_manifest(against)?;
+            let report = verify_dir::verify_dir(&dir, &manifest)?;
+            verify_dir::print_report(&dir, &report, format)
+        }
+        Command::Audit {
+            input,
+            rules,
+            format,
+        } => {
+            #[cfg(not(feature = "rules"))]
+            if !rules.is_empty() {
+                return Err(TModError::FeatureDisabled("rules"));
+            }
+            let options = audit::AuditOptions {
+                format: format.resolve()?,
+                #[cfg(feature = "rules")]
+                rules: {
+                    let mut loaded = Vec::new();
+                    for rules_path in rules {
+                        loaded.extend(rules::load_rules(rules_path)?);
+                    }
+                    loaded
+                },
+            };
+            audit::audit(input, &options)
+        }
+        Command::Lint(args) => {
+            let mut options = lint::LintOptions {
+                format: args.format.resolve()?,
+                ..Default::default()
+            };
+            // later flags override earlier ones, so put them back in the
+            // order they were given
+            let lint_matches = matches.subcommand_matches("lint").expect("lint was parsed");
+            let mut levels = Vec::new();
+            for (id, severity, names) in [
+                ("allow", lint::Severity::Allow, &args.allow),
+                ("warn", lint::Severity::Warning, &args.warn),
+                ("deny", lint::Severity::Error, &args.deny),
+            ] {
+                let indices = lint_matches.indices_of(id).into_iter().flatten();
+                levels.extend(
+                    indices
+                        .zip(names)
+                        .map(|(index, name)| (index, name, severity)),
+                );
+            }
+            levels.sort_by_key(|(index, ..)| *index);
+            for (_, name, severity) in levels {
+                if name == "warnings" && severity == lint::Severity::Error {
+                    options.deny_warnings = true;
+                } else {
+                    options.levels.push((lint::Lint::parse(name)?, severity));
+                }
+            }
+            lint::lint(args.input, &options)
+        }
+        Command::Strings {
+            input,
+            min_len,
+            format,
+        } => strings::strings(
+            input,
+            &strings::StringsOptions {
+                min_len,
+                format: format.resolve()?,
+            },
+        ),
+        Command::Orphans { input, format } => orphans::orphans(input, format.resolve()?),
+        Command::Stats {
+            mut inputs,
+            history,
+            format,
+        } => {
+            // several mods only make sense as a history
+            if inputs.len() > 1 && !history {
+                return Err(TModError::UnknownArgument(inputs.swap_remove(1)));
+            }
+            stats::stats(
+                inputs,
+                &stats::StatsOptions {
+                    history,
+                    format: format.resolve()?,
+                },
+            )
+        }
+        Command::Dedup {
+            inputs,
+            avg_size,
+            format,
+        } => dedup::dedup(
+            inputs,
+            &dedup::DedupOptions {
+                avg_size,
+                format: format.resolve()?,
+            },
+        ),
+        Command::Scaffold { from, name, output } => {
+            scaffold::scaffold(&scaffold::ScaffoldOptions { from, name, output })
+        }
+        Command::Diff {
+            old,
+            new,
+            localization,
+            output,
+            format,
+        } => diff::diff(
+            old,
+            new,
+            &diff::DiffOptions {
+                localization,
+                output,
+                format: format.resolve()?,
+            },
+        ),
+        Command::Gallery { input, output } => gallery::gallery(input, &output),
+        Command::Register => register::register(),
+        Command::Unregister => register::unregister(),
+        Command::Doctor { target, format } => doctor::doctor(
+            &target.unwrap_or_else(|| PathBuf::from(".")),
+            format.resolve()?,
+        ),
+        Command::Fetch {
+            item,
+            out_dir,
+            yes,
+            refresh,
+        } => {
+            let input = workshop::resolve(&item, refresh)?;
+            // a url is read while extracting, after the sandbox is up
+            #[cfg(feature = "hardened")]
+            if source::is_url(&input) {
+                return Err(TModError::ConflictingArguments(
+                    "the `hardened` feature",
+                    "a workshop item without a local copy",
+                ));
+            }
+            let out_dir = match out_dir {
+                Some(out_dir) => out_dir,
+                None => {
+                    let name = input.rsplit(['/', '\\']).next().unwrap_or(&input);
+                    let stem = Path::new(name).file_stem().unwrap_or_default();
+                    PathBuf::from(format!("{}-extracted", stem.to_string_lossy()))
+                }
+            };
+            let options = UnpackOptions {
+                yes,
+                verify: true,
+                cache_dir: (!refresh).then(workshop::cache_dir),
+                ..Default::default()
+            };
+            handle_ctrlc(&options);
+            unpack(input, Some(out_dir), &options)
+        }
+        Command::Corpus {
+            command: CorpusCommand::Run { dir, format },
+        } => {
+            // each case is extracted in this process, and the sandbox of the
+            // first would keep the others from writing anywhere
+            #[cfg(feature = "hardened")]
+            {
+                let _ = (dir, format);
+                Err(TModError::ConflictingArguments(
+                    "the `hardened` feature",
+                    "corpus run",
+                ))
+            }
+            #[cfg(not(feature = "hardened"))]
+            corpus::run(&dir, format.resolve()?)
+        }
+        Command::Gen {
+            output,
+            entries,
+            seed,
+            min_size,
+            max_size,
+            distribution,
+            with,
+        } => gen::generate(&gen::GenOptions {
+            output,
+            entries,
+            seed,
+            min_size,
+            max_size,
+            distribution: match distribution.as_str() {
+                "uniform" => gen::SizeDistribution::Uniform,
+                _ => gen::SizeDistribution::Log,
+            },
+            pathologies: with
+                .iter()
+                .filter_map(|case| gen::Pathology::parse(case))
+                .collect(),
+        }),
+        Command::Bench {
+            input,
+            rounds,
+            format,
+        } => {
+            let mut options = bench::BenchOptions {
+                format: format.resolve()?,
+                ..Default::default()
+            };
+            if let Some(rounds) = rounds {
+                options.rounds = rounds;
+            }
+            bench::bench(input, &options)
+        }
+        #[cfg(feature = "serve")]
+        Command::Serve {
+            listen,
+            max_size,
+            max_unpacked,
+            timeout,
+            max_connections,
+            allow_fetch,
+        } => serve::serve(&serve::ServeOptions {
+            listen,
+            max_size,
+            max_unpacked,
+            timeout: Duration::from_secs(timeout),
+            max_connections,
+            allow_fetch,
+        }),
+        #[cfg(not(feature = "serve"))]
+        Command::Serve { .. } => Err(TModError::FeatureDisabled("serve")),
     }
+}
 
-    let out_dir = args.next().ok_or(TModError::NoOutputDirectory)?;
-    let out_dir = PathBuf::from(out_dir);
-
-    trace!("checking if output directory exists: {:?}", out_dir);
-    if !out_dir.try_exists()? {
-        trace!("output directory does not exist, creating it");
-        std::fs::create_dir_all(&out_dir)?;
+fn pack_command(args: PackArgs) -> Result<(), TModError> {
+    if args.workspace {
+        return workspace::pack_workspace(&workspace::WorkspaceOptions {
+            path: args.input.unwrap_or_else(|| PathBuf::from(".")),
+            tmodloader_version: args.tml_version,
+            optimize_png: args.optimize_png,
+            reencode_audio: args.reencode_audio,
+        });
     }
+    pack::pack(&pack::PackOptions {
+        input: args.input.ok_or(TModError::NoInputFile)?,
+        name: args.name,
+        version: args.version,
+        tmodloader_version: args
+            .tml_version
+            .ok_or(TModError::MissingValue("--tml-version"))?,
+        output: args.output,
+        output_dir: None,
+        shared: Vec::new(),
+        optimize_png: args.optimize_png,
+        reencode_audio: match args.reencode_audio.as_deref() {
+            Some(reencode) => {
+                tools::AUDIO_ENCODER.require()?;
+                Some(audio::Reencode::parse(reencode)?)
+            }
+            None => None,
+        },
+    })
+}
 
-    trace!("opening file: {}", path);
-    let file = std::fs::File::open(path)?;
-    let mut stream = FileStream::new(file);
-    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
-
-    trace!("reading header");
-    let header = reader.read_bytes(4)?;
-    if header != TMOD_HEADER {
-        return Err(TModError::InvalidHeader(header, TMOD_HEADER));
+fn unpack_command(args: UnpackArgs) -> Result<(), TModError> {
+    let mut options = UnpackOptions {
+        yes: args.yes,
+        dry_run: args.dry_run,
+        existing: match (args.skip_existing, args.prompt, args.no_clobber) {
+            (true, _, _) => sink::Existing::Skip,
+            (_, true, _) => sink::Existing::Prompt,
+            (_, _, true) => sink::Existing::Fail,
+            _ => sink::Existing::Overwrite,
+        },
+        force: args.force,
+        allow_unsafe_paths: args.allow_unsafe_paths,
+        verify: args.verify,
+        keep_going: args.keep_going,
+        thumbnails: args.thumbnails,
+        thumbnails_only: args.thumbnails_only,
+        retries: args.retries,
+        timeout: args.timeout.map(Duration::from_secs),
+        entry_timeout: args.entry_timeout.map(Duration::from_secs),
+        mmap: args.mmap,
+        cache_dir: args.cache_dir,
+        low_memory: args.low_memory,
+        jobs: match args.jobs {
+            0 => std::thread::available_parallelism().map_or(1, |cores| cores.get()),
+            jobs => jobs,
+        },
+        wait_lock: args.wait_lock,
+        git_init: args.git_init,
+        lfs_threshold: args.lfs_threshold,
+        source_map: args.source_map,
+        emit_entries: args.emit_entries,
+        progress_json: args.progress_json,
+        no_progress: args.no_progress,
+        ..Default::default()
+    };
+    if let Some(zip_path) = args.to_zip {
+        options.archive = Some((sink::ArchiveFormat::Zip, zip_path));
     }
-    debug!("TMOD header found");
-
-    trace!("reading tmodloader version");
-    let tmodloader_version = read_csharp_string(&mut reader)?;
-    info!("For tModLoader version: {}", tmodloader_version);
-
-    trace!("reading mod hash");
-    let hash = reader.read_bytes(20)?;
-    let hash_str = hex::encode(hash);
-    debug!("Hash: {}", hash_str);
-
-    trace!("reading signature");
-    let signature = reader.read_bytes(256)?;
-    let signature_str = hex::encode(signature);
-    debug!("Signature: {}", signature_str);
-
-    trace!("reading file data length");
-    let file_data_len = reader.read_u32()?;
-    debug!("File data length: {}", file_data_len);
-
-    trace!("reading mod name");
-    let mod_name = read_csharp_string(&mut reader)?;
-    info!("Mod name: {}", mod_name);
-
-    trace!("reading mod version");
-    let mod_version = read_csharp_string(&mut reader)?;
-    info!("Mod version: {}", mod_version);
-
-    trace!("reading file count");
-    let file_count = reader.read_i32()?;
-    debug!("File count: {}", file_count);
-
-    let mut file_entries = Vec::with_capacity(file_count as usize);
-
-    #[cfg(target_os = "linux")]
-    {
-        init_progress_bar(file_count as usize * 2);
-        set_progress_bar_action("Reading", Color::Blue, Style::Bold);
+    if let Some(tar_path) = args.to_tar {
+        let format = match args.gzip {
+            true => sink::ArchiveFormat::TarGz,
+            false => sink::ArchiveFormat::tar_for(&tar_path),
+        };
+        options.archive = Some((format, tar_path));
     }
-
-    info!("Reading file entries");
-    for _ in 0..file_count {
-        trace!("reading file entry name");
-        let file_name = read_csharp_string(&mut reader)?;
-        trace!("File name: {}", file_name);
-
-        trace!("reading uncompressed length");
-        let uncompressed_len = reader.read_i32()?;
-        trace!("Uncompressed length: {}", uncompressed_len);
-
-        trace!("reading compressed length");
-        let compressed_len = reader.read_i32()?;
-        trace!("Compressed length: {}", compressed_len);
-
-        file_entries.push(ModFile {
-            name: file_name,
-            uncompressed_len,
-            compressed_len,
-        });
-        #[cfg(target_os = "linux")]
-        inc_progress_bar();
+    if args.git_init {
+        tools::GIT.require()?;
     }
-
-    #[cfg(target_os = "linux")]
+    // a dry run has nothing to report
+    if let (Some(url), false) = (args.notify_webhook, args.dry_run) {
+        notify::check_available()?;
+        options.notify_webhook = Some(url);
+    }
+    options.normalize = text::Normalize {
+        eol: args.normalize_eol.as_deref().map(|eol| match eol {
+            "crlf" => text::LineEnding::CrLf,
+            _ => text::LineEnding::Lf,
+        }),
+        strip_bom: args.strip_bom,
+    };
+    options.key = signature::PublicKey::select(args.check_signature, args.pubkey.as_deref())?;
+    if let Some(max_depth) = args.max_depth {
+        options.limits.max_depth = max_depth;
+    }
+    if let Some(max_component_len) = args.max_component_len {
+        options.limits.max_component_len = max_component_len;
+    }
+    if let Some(max_size) = args.max_size {
+        options.size_limits.max_entry_size = max_size;
+    }
+    if let Some(max_total_size) = args.max_total_size {
+        options.size_limits.max_total_size = max_total_size;
+    }
+    if let Some(order) = &args.order {
+        options.order = ExtractOrder::parse(order)?;
+    }
+    if let Some(list_path) = &args.priority_list {
+        options.priority = Some(order::PriorityList::load(list_path)?);
+    }
+    for route in &args.route {
+        options.routes.push(route::Route::parse(route)?);
+    }
+    for transform in &args.transform {
+        options
+            .transforms
+            .push(transform::Transform::parse(transform)?);
+    }
+    // last, so explicit transforms of some textures win
+    if args.convert_images {
+        options
+            .transforms
+            .push(transform::Transform::parse("**/*.rawimg=png")?);
+    }
+    if let Some(backend) = &args.inflate {
+        inflate::select(inflate::Backend::parse(backend)?);
+    }
+    options.filter = filter::Filter::new(&args.include, &args.exclude)?;
+    options.sample = args.sample.map(|count| Sample {
+        count: count as usize,
+        random: args.sample_random,
+        per_extension: args.sample_per_extension,
+        seed: args.seed,
+    });
+
+    // `-o` makes every positional argument an input; otherwise the last of
+    // several is the output directory, like `cp`
+    let mut inputs = Vec::new();
+    if let Some(name) = &args.mod_name {
+        let path = locate::find_mod(name)?;
+        println!("Found {} in {}", name, path.display());
+        inputs.push(path.to_string_lossy().into_owned());
+    }
+    inputs.extend(args.input);
+    inputs.extend(args.out_dir.map(|dir| dir.to_string_lossy().into_owned()));
+    inputs.extend(args.more);
+    let mut out_dir = match args.output {
+        Some(output) => Some(output),
+        None if inputs.len() > 1 => inputs.pop().map(PathBuf::from),
+        None => None,
+    };
+    if inputs.is_empty() {
+        return Err(TModError::NoInputFile);
+    }
+    let batch = inputs.len() > 1 || Path::new(&inputs[0]).is_dir();
+    if let Some(fifo_dir) = &args.fifo_dir {
+        if out_dir.is_some() {
+            return Err(TModError::ConflictingArguments(
+                "an output directory",
+                "--fifo-dir",
+            ));
+        }
+        if batch {
+            return Err(TModError::ConflictingArguments(
+                "several mods",
+                "--fifo-dir",
+            ));
+        }
+        handle_ctrlc(&options);
+        return fifo::serve(&inputs[0], fifo_dir, &options.filter, &options.cancel);
+    }
+    // the sandbox can't be lifted again, and it rules out running programs,
+    // the network and writing anywhere but the output
+    #[cfg(feature = "hardened")]
     {
-        print_progress_bar_info("Success", "reading file entries", Color::Green, Style::Bold);
-        set_progress_bar_action("Extracting", Color::Blue, Style::Bold);
+        let conflicts = [
+            (batch, "several mods"),
+            (source::is_url(&inputs[0]), "an http(s) url"),
+            (options.git_init, "--git-init"),
+            (options.notify_webhook.is_some(), "--notify-webhook"),
+            (options.allow_unsafe_paths, "--allow-unsafe-paths"),
+        ];
+        if let Some((_, flag)) = conflicts.iter().find(|(conflict, _)| *conflict) {
+            return Err(TModError::ConflictingArguments(
+                "the `hardened` feature",
+                flag,
+            ));
+        }
+    }
+    if batch {
+        if options.archive.is_some() || !options.routes.is_empty() {
+            let flag = match &options.archive {
+                Some((sink::ArchiveFormat::Zip, _)) => "--to-zip",
+                Some((sink::ArchiveFormat::Tar | sink::ArchiveFormat::TarGz, _)) => "--to-tar",
+                None => "--route",
+            };
+            return Err(TModError::ConflictingArguments("several mods", flag));
+        }
+        let out_dir = out_dir.ok_or(TModError::NoOutputDirectory)?;
+        return unpack_batch(inputs, out_dir, &options);
     }
 
-    if file_entries.len() != file_count as usize {
-        return Err(TModError::MissingFileEntries(
-            file_entries.len(),
-            file_count as usize,
-        ));
+    let path = inputs.remove(0);
+    // not next to an installed mod, that would be inside the Mods folder
+    let archive_or_routes = options.archive.is_some() || !options.routes.is_empty();
+    if let (Some(name), None, false) = (&args.mod_name, &out_dir, archive_or_routes) {
+        out_dir = Some(PathBuf::from(format!("{}-extracted", name)));
+    }
+    // with routes the output directory is only the fallback for entries no
+    // route matches, and may be left out entirely
+    if let Some((format, _)) = &options.archive {
+        let flag = match format {
+            sink::ArchiveFormat::Zip => "--to-zip",
+            sink::ArchiveFormat::Tar | sink::ArchiveFormat::TarGz => "--to-tar",
+        };
+        if out_dir.is_some() {
+            return Err(TModError::ConflictingArguments("an output directory", flag));
+        }
+        if !options.routes.is_empty() {
+            return Err(TModError::ConflictingArguments("--route", flag));
+        }
+    } else if out_dir.is_none() && options.routes.is_empty() {
+        // e.g. a mod dropped onto the executable: extract next to it
+        let default = default_out_dir(&path).ok_or(TModError::NoOutputDirectory)?;
+        println!("Extracting into {}", default.display());
+        out_dir = Some(default);
     }
 
-    info!("Extracting files");
-    for file in file_entries {
-        trace!("extracting file: {}", file.name);
-        let mut file_data = reader.read_bytes(file.compressed_len as usize)?;
+    handle_ctrlc(&options);
+
+    let started = std::time::Instant::now();
+    let total_size = std::fs::metadata(&path).map_or(0, |metadata| metadata.len());
+    let result = unpack(path.clone(), out_dir, &options);
+    if let Some(url) = &options.notify_webhook {
+        let failures = match &result {
+            Ok(()) => Vec::new(),
+            Err(e) => vec![format!("{}: {}", path, e)],
+        };
+        notify::notify(
+            url,
+            &notify::Summary {
+                mods: 1,
+                failures,
+                total_size,
+                duration: started.elapsed(),
+            },
+        );
+    }
+    result
+}
 
-        if file.compressed_len != file.uncompressed_len {
-            trace!("decompressing file: {}", file.name);
-            let mut decoder = flate2::read::DeflateDecoder::new(&file_data[..]);
-            let mut uncompressed_data = Vec::with_capacity(file.uncompressed_len as usize);
-            decoder.read_to_end(&mut uncompressed_data)?;
-            file_data = uncompressed_data;
+// several mods, or the ones in a directory, each into its own
+// `<ModName>_<Version>` directory. a mod that fails doesn't stop the others.
+fn unpack_batch(
+    inputs: Vec<String>,
+    out_dir: PathBuf,
+    options: &UnpackOptions,
+) -> Result<(), TModError> {
+    let mut paths = Vec::new();
+    for input in inputs {
+        if input == "-" {
+            return Err(TModError::ConflictingArguments("several mods", "stdin"));
+        }
+        if Path::new(&input).is_dir() {
+            let mut found = Vec::new();
+            collect_mods(Path::new(&input), &mut found)?;
+            found.sort();
+            paths.extend(found.iter().map(|path| path.to_string_lossy().into_owned()));
         } else {
-            trace!("file is not compressed: {}", file.name);
-        }
-        let file_path = out_dir.join(&file.name);
-        if let Some(parent) = file_path.parent() {
-            trace!("checking if file's parent directory exists: {:?}", parent);
-            if !parent.try_exists()? {
-                trace!("creating parent directory: {:?}", parent);
-                std::fs::create_dir_all(parent)?;
-            }
+            paths.push(input);
         }
-        trace!("writing file: {:?}", file_path);
-        std::fs::write(file_path, file_data)?;
-
-        #[cfg(target_os = "linux")]
-        inc_progress_bar();
     }
+    println!(
+        "{} {} mods into {}",
+        if options.dry_run {
+            "Would extract"
+        } else {
+            "Extracting"
+        },
+        paths.len(),
+        out_dir.display()
+    );
 
-    #[cfg(target_os = "linux")]
-    {
-        print_progress_bar_info("Success", "extracting files", Color::Green, Style::Bold);
-        finalize_progress_bar();
+    handle_ctrlc(options);
+
+    let started = std::time::Instant::now();
+    let mut total_size = 0;
+    let mut failures = Vec::new();
+    for path in &paths {
+        total_size += std::fs::metadata(path).map_or(0, |metadata| metadata.len());
+        let result = batch_dir_name(path).and_then(|name| {
+            let dir = out_dir.join(name);
+            println!("{} -> {}", path, dir.display());
+            unpack(path.clone(), Some(dir), options)
+        });
+        match result {
+            Ok(()) => {}
+            Err(TModError::Cancelled) => return Err(TModError::Cancelled),
+            Err(e) => {
+                print_error(&e);
+                failures.push(format!("{}: {}", path, e));
+            }
+        }
     }
 
-    info!("Done! Your files are in: {:?}", out_dir);
-
+    let failed = failures.len();
+    if let Some(url) = &options.notify_webhook {
+        notify::notify(
+            url,
+            &notify::Summary {
+                mods: paths.len(),
+                failures,
+                total_size,
+                duration: started.elapsed(),
+            },
+        );
+    }
+    if failed > 0 {
+        return Err(TModError::BatchFailed(failed, paths.len()));
+    }
     Ok(())
 }
 
-// read a 7 bit encoded string length
-// then read that many bytes into a string
-fn read_csharp_string(reader: &mut BinaryReader) -> Result<String, TModError> {
-    let mut string_len = 0;
-    let mut done = false;
-    let mut step = 0;
-    while !done {
-        let byte = reader.read_u8()?;
-        string_len |= ((byte & 0x7F) as u32) << (step * 7);
-        done = (byte & 0x80) == 0;
-        step += 1;
-    }
-    let buf = reader.read_bytes(string_len as usize)?;
-    Ok(String::from_utf8(buf)?)
+// the first Ctrl-C stops after cleaning up the entry being written, a
+// second one exits right away
+fn handle_ctrlc(options: &UnpackOptions) {
+    let token = options.cancel.clone();
+    let handler = ctrlc::set_handler(move || {
+        if token.is_cancelled() {
+            std::process::exit(130);
+        }
+        eprintln!("\nCancelling, press Ctrl-C again to exit immediately");
+        token.cancel();
+    });
+    if let Err(e) = handler {
+        warn!("could not install the Ctrl-C handler: {}", e);
+    }
+}
+
+// `<name>-extracted` next to the input file
+fn default_out_dir(path: &str) -> Option<PathBuf> {
+    // there's nothing to put the directory next to, so use the current one
+    if source::is_url(path) {
+        let name = path.rsplit('/').next()?;
+        let stem = Path::new(name).file_stem()?.to_string_lossy();
+        return Some(PathBuf::from(format!("{}-extracted", stem)));
+    }
+    if path == "-" {
+        return None;
+    }
+    let path = Path::new(path);
+    let stem = path.file_stem()?.to_string_lossy();
+    Some(path.with_file_name(format!("{}-extracted", stem)))
 }
@@ -0,0 +1,34 @@
+// helpers for inspecting entry names, which always use `/` as the separator
+// in archives built by tModLoader but may contain anything in hostile ones
+
+pub fn is_absolute(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    name.starts_with('/')
+        || name.starts_with('\\')
+        || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+}
+
+// why joining a name onto the output directory could land outside of it, if
+// it could. drive prefixes are caught anywhere in the name, since Windows
+// resolves `a/C:b` relative to the current directory of drive C.
+pub fn unsafe_reason(name: &str) -> Option<&'static str> {
+    if is_absolute(name) {
+        Some("name is an absolute path")
+    } else if components(name).any(|c| c == "..") {
+        Some("name contains `..` components")
+    } else if components(name).any(|c| c.contains(':')) {
+        Some("name contains a drive prefix or stream separator")
+    } else {
+        None
+    }
+}
+
+pub fn components(name: &str) -> impl Iterator<Item = &str> {
+    name.split(['/', '\\'])
+}
+
+pub fn extension(name: &str) -> Option<String> {
+    let file_name = components(name).last()?;
+    let (_, ext) = file_name.rsplit_once('.')?;
+    Some(ext.to_ascii_lowercase())
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use log::{trace, warn};
+
+use crate::{format_size, TModError};
+
+pub struct Summary {
+    pub mods: usize,
+    pub failures: Vec<String>,
+    pub total_size: u64,
+    pub duration: Duration,
+}
+
+impl Summary {
+    pub fn message(&self) -> String {
+        let mut message = format!(
+            "{} finished: {} mod(s) processed, {} failed, {} in {:.1}s",
+            env!("CARGO_PKG_NAME"),
+            self.mods,
+            self.failures.len(),
+            format_size(self.total_size),
+            self.duration.as_secs_f64()
+        );
+        for failure in &self.failures {
+            message.push_str("\n- ");
+            message.push_str(failure);
+        }
+        message
+    }
+}
+
+// `--notify-webhook` is checked up front, so a long run doesn't find out at
+// the end that it can't send anything
+pub fn check_available() -> Result<(), TModError> {
+    if cfg!(feature = "http") {
+        Ok(())
+    } else {
+        Err(TModError::FeatureDisabled("http"))
+    }
+}
+
+// posts the summary to a Discord or Slack webhook. the two read different
+// fields (`content` and `text`), and both ignore the other one. a failed post
+// only warns, since the work itself is done by then.
+pub fn notify(url: &str, summary: &Summary) {
+    trace!("posting summary to webhook: {}", url);
+    if let Err(e) = post(url, &summary.message()) {
+        warn!("could not notify the webhook: {}", e);
+    }
+}
+
+#[cfg(feature = "http")]
+fn post(url: &str, message: &str) -> Result<(), String> {
+    let body = serde_json::json!({ "content": message, "text": message });
+    ureq::post(url)
+        .set("Content-Type", "application/json")
+        .send_string(&body.to_string())
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "http"))]
+fn post(_url: &str, _message: &str) -> Result<(), String> {
+    Err("this build was compiled without the `http` feature".to_string())
+}
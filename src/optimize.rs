@@ -0,0 +1,111 @@
+#[cfg(feature = "images")]
+use png::{AdaptiveFilterType, BitDepth, ColorType, FilterType};
+
+// `pack --optimize-png`: recompresses a PNG without changing a single pixel.
+// the image is stored with the fewest channels that hold it exactly (no
+// alpha channel when everything is opaque, gray when every pixel is), then
+// deflated at the highest level with every filter strategy, keeping the
+// smallest. returns `None` when nothing beats the original, and for PNGs
+// this doesn't handle (palettes, 16-bit channels, animations, anything that
+// doesn't decode).
+#[cfg(feature = "images")]
+pub fn optimize_png(data: &[u8]) -> Option<Vec<u8>> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::IDENTITY);
+    let mut reader = decoder.read_info().ok()?;
+    let info = reader.info();
+    if info.bit_depth != BitDepth::Eight
+        || info.color_type == ColorType::Indexed
+        || info.animation_control.is_some()
+    {
+        return None;
+    }
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut pixels).ok()?;
+    pixels.truncate(frame.buffer_size());
+    let (color_type, pixels) = reduce(frame.color_type, pixels);
+
+    let mut best: Option<Vec<u8>> = None;
+    for (filter, adaptive) in [
+        (FilterType::NoFilter, AdaptiveFilterType::NonAdaptive),
+        (FilterType::Sub, AdaptiveFilterType::NonAdaptive),
+        (FilterType::Up, AdaptiveFilterType::NonAdaptive),
+        (FilterType::Avg, AdaptiveFilterType::NonAdaptive),
+        (FilterType::Paeth, AdaptiveFilterType::NonAdaptive),
+        (FilterType::Sub, AdaptiveFilterType::Adaptive),
+    ] {
+        let encoded = encode(
+            frame.width,
+            frame.height,
+            color_type,
+            &pixels,
+            filter,
+            adaptive,
+        )?;
+        if best
+            .as_ref()
+            .map_or(true, |best| encoded.len() < best.len())
+        {
+            best = Some(encoded);
+        }
+    }
+    best.filter(|best| best.len() < data.len())
+}
+
+// `pack` refuses `--optimize-png` in builds without the `images` feature
+#[cfg(not(feature = "images"))]
+pub fn optimize_png(_data: &[u8]) -> Option<Vec<u8>> {
+    None
+}
+
+// drops the channels an image doesn't need
+#[cfg(feature = "images")]
+fn reduce(color_type: ColorType, pixels: Vec<u8>) -> (ColorType, Vec<u8>) {
+    let channels = color_type.samples();
+    let has_alpha = matches!(color_type, ColorType::Rgba | ColorType::GrayscaleAlpha);
+    let opaque = !has_alpha
+        || pixels
+            .chunks_exact(channels)
+            .all(|pixel| pixel[channels - 1] == 255);
+    let gray = matches!(color_type, ColorType::Grayscale | ColorType::GrayscaleAlpha)
+        || pixels
+            .chunks_exact(channels)
+            .all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2]);
+
+    let (reduced, keep): (ColorType, &[usize]) = match (gray, opaque) {
+        (true, true) => (ColorType::Grayscale, &[0]),
+        (true, false) => (ColorType::GrayscaleAlpha, &[0, channels - 1]),
+        (false, true) => (ColorType::Rgb, &[0, 1, 2]),
+        (false, false) => return (color_type, pixels),
+    };
+    if reduced == color_type {
+        return (color_type, pixels);
+    }
+    let pixels = pixels
+        .chunks_exact(channels)
+        .flat_map(|pixel| keep.iter().map(move |&channel| pixel[channel]))
+        .collect();
+    (reduced, pixels)
+}
+
+#[cfg(feature = "images")]
+fn encode(
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    pixels: &[u8],
+    filter: FilterType,
+    adaptive: AdaptiveFilterType,
+) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width, height);
+    encoder.set_color(color_type);
+    encoder.set_depth(BitDepth::Eight);
+    encoder.set_compression(png::Compression::Best);
+    encoder.set_filter(filter);
+    encoder.set_adaptive_filter(adaptive);
+    let mut writer = encoder.write_header().ok()?;
+    writer.write_image_data(pixels).ok()?;
+    writer.finish().ok()?;
+    Some(out)
+}
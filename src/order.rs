@@ -0,0 +1,96 @@
+use std::path::Path;
+
+use crate::route::{compile_glob, GlobMatcher};
+use crate::{ModFile, TModError};
+
+// the order entries are extracted in. anything other than `Table` seeks
+// around the data region instead of streaming through it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExtractOrder {
+    // the order the entries are stored in, which reads the file sequentially
+    #[default]
+    Table,
+    Alpha,
+    SizeDesc,
+    SizeAsc,
+}
+
+impl ExtractOrder {
+    pub fn parse(value: &str) -> Result<Self, TModError> {
+        Ok(match value {
+            "table" => ExtractOrder::Table,
+            "alpha" => ExtractOrder::Alpha,
+            "size-desc" => ExtractOrder::SizeDesc,
+            "size-asc" => ExtractOrder::SizeAsc,
+            _ => return Err(TModError::UnknownOrder(value.to_string())),
+        })
+    }
+
+    // indices into `files` in extraction order. sorts are stable so entries
+    // that compare equal keep their table order.
+    pub fn arrange(&self, files: &[ModFile]) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..files.len()).collect();
+        match self {
+            ExtractOrder::Table => {}
+            ExtractOrder::Alpha => indices.sort_by(|&a, &b| files[a].name.cmp(&files[b].name)),
+            ExtractOrder::SizeDesc => {
+                indices.sort_by_key(|&i| std::cmp::Reverse(files[i].uncompressed_len))
+            }
+            ExtractOrder::SizeAsc => indices.sort_by_key(|&i| files[i].uncompressed_len),
+        }
+        indices
+    }
+}
+
+// `--priority-list`: globs, one per line, for entries to extract before all
+// others. entries matching an earlier line come first, and within each group
+// (and for everything left over) the `--order` still applies.
+pub struct PriorityList {
+    patterns: Vec<GlobMatcher>,
+}
+
+impl PriorityList {
+    pub fn load(path: &Path) -> Result<Self, TModError> {
+        let text = std::fs::read_to_string(path)?;
+        let patterns = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(compile_glob)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { patterns })
+    }
+
+    // the group an entry belongs to, `None` for entries on no line
+    pub fn rank(&self, name: &str) -> Option<usize> {
+        self.patterns
+            .iter()
+            .position(|pattern| pattern.is_match(name))
+    }
+
+    pub fn apply(&self, files: &[ModFile], indices: &mut [usize]) {
+        indices.sort_by_key(|&i| self.rank(&files[i].name).unwrap_or(self.patterns.len()));
+    }
+}
+
+// the absolute position of each entry's data, given where the data section
+// starts (right after the entry table). a table with negative lengths has no
+// such positions, nor one whose lengths add up past what an offset can hold;
+// the error counts the entries at fault.
+pub fn data_offsets(files: &[ModFile], data_start: usize) -> Result<Vec<usize>, TModError> {
+    let negative = files.iter().filter(|file| file.compressed_len < 0).count();
+    if negative > 0 {
+        return Err(TModError::InvalidEntryTable(negative));
+    }
+    let mut offset = data_start;
+    files
+        .iter()
+        .map(|file| {
+            let start = offset;
+            offset = offset
+                .checked_add(file.compressed_len as usize)
+                .ok_or(TModError::InvalidEntryTable(1))?;
+            Ok(start)
+        })
+        .collect()
+}
@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use binary_rw::BinaryReader;
+use log::{info, trace};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::names::extension;
+use crate::output::{self, OutputFormat};
+use crate::{read_entry_table, read_file_data, read_header, source, strings, TModError};
+
+const ASSET_EXTENSIONS: &[&str] = &["png", "rawimg", "xnb", "wav", "ogg", "mp3", "fxc"];
+
+// entries whose text may mention asset paths
+const TEXT_EXTENSIONS: &[&str] = &["cs", "hjson", "json", "txt"];
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct MissingReference {
+    // the path as written, e.g. `MyMod/Items/Sword`
+    pub reference: String,
+    // the entry it was found in
+    pub entry: String,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct OrphanReport {
+    pub mod_name: String,
+    pub mod_version: String,
+    pub fingerprint: String,
+    pub assets: usize,
+    pub orphans: Vec<String>,
+    pub missing: Vec<MissingReference>,
+}
+
+// cross-references the assets in a mod against the paths its code and
+// localization mention. an asset counts as used when its path (without the
+// extension) appears in any string, or when the assembly has a type that
+// tModLoader would autoload it for: `Items/Sword.png` and `Items/Sword_Glow.png`
+// both belong to a type `Sword` in the namespace `MyMod.Items`.
+pub fn orphans(path: String, format: OutputFormat) -> Result<(), TModError> {
+    trace!("opening file: {}", path);
+    let mut stream = source::open(&path)?;
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+
+    let header = read_header(&mut reader, None)?;
+    let file_entries = read_entry_table(&mut reader)?;
+    let mod_name = header.mod_name.as_str();
+
+    info!("Collecting references");
+    // every string, and which entry it came from
+    let mut texts: Vec<(String, String)> = Vec::new();
+    // identifiers from assemblies, matched exactly for autoloading
+    let mut identifiers = BTreeSet::new();
+    let mut assets = Vec::new();
+    let mut entries = BTreeSet::new();
+    for file in &file_entries {
+        let data = read_file_data(&mut reader, file)?;
+        let name = file.name.as_str();
+        entries.insert(without_extension(name).to_string());
+        match extension(name).as_deref() {
+            // tModLoader reads the icons itself
+            Some(ext) if ASSET_EXTENSIONS.contains(&ext) && !name.starts_with("icon") => {
+                assets.push(name.to_string());
+            }
+            Some(ext) if TEXT_EXTENSIONS.contains(&ext) => {
+                texts.push((
+                    name.to_string(),
+                    String::from_utf8_lossy(&data).into_owned(),
+                ));
+            }
+            Some("dll") => {
+                for string in strings::printable(&data, 2) {
+                    identifiers.insert(string.clone());
+                    texts.push((name.to_string(), string));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let orphans: Vec<String> = assets
+        .iter()
+        .filter(|asset| {
+            let key = without_extension(asset);
+            !texts.iter().any(|(_, text)| text.contains(key))
+                && !autoloaded(mod_name, key, &identifiers)
+        })
+        .cloned()
+        .collect();
+
+    let mut missing = BTreeMap::new();
+    let prefix = format!("{}/", mod_name);
+    for (entry, text) in &texts {
+        for (at, _) in text.match_indices(&prefix) {
+            let reference: String = text[at..]
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '/' | '.' | '-'))
+                .collect();
+            let path = without_extension(&reference[prefix.len()..]);
+            // paths built at runtime (`"MyMod/Items/Sword_" + i`) can't be
+            // checked
+            if path.is_empty() || path.ends_with(['/', '_']) || entries.contains(path) {
+                continue;
+            }
+            missing
+                .entry(reference.clone())
+                .or_insert_with(|| entry.clone());
+        }
+    }
+
+    let report = OrphanReport {
+        mod_name: header.mod_name.clone(),
+        mod_version: header.mod_version.clone(),
+        fingerprint: header.fingerprint(),
+        assets: assets.len(),
+        orphans,
+        missing: missing
+            .into_iter()
+            .map(|(reference, entry)| MissingReference { reference, entry })
+            .collect(),
+    };
+
+    if format == OutputFormat::Text {
+        print_report(&report);
+    } else {
+        println!("{}", output::render(format, "orphans", &report)?);
+    }
+
+    Ok(())
+}
+
+fn print_report(report: &OrphanReport) {
+    println!(
+        "Asset references in {} v{} ({})",
+        report.mod_name, report.mod_version, report.fingerprint
+    );
+    for orphan in &report.orphans {
+        println!("  unused: {}", orphan);
+    }
+    for missing in &report.missing {
+        println!("  missing: {} (in {})", missing.reference, missing.entry);
+    }
+    println!(
+        "{} of {} assets unused, {} references to missing entries",
+        report.orphans.len(),
+        report.assets,
+        report.missing.len()
+    );
+}
+
+fn without_extension(name: &str) -> &str {
+    let file_name = name.rfind('/').map_or(0, |split| split + 1);
+    match name[file_name..].rfind('.') {
+        Some(dot) if dot > 0 => &name[..file_name + dot],
+        _ => name,
+    }
+}
+
+fn autoloaded(mod_name: &str, key: &str, identifiers: &BTreeSet<String>) -> bool {
+    let (namespace, stem) = match key.rsplit_once('/') {
+        Some((directory, stem)) => (
+            format!("{}.{}", mod_name, directory.replace('/', ".")),
+            stem,
+        ),
+        None => (mod_name.to_string(), key),
+    };
+    if !identifiers.contains(&namespace) {
+        return false;
+    }
+    // `Sword_Glow`, `Sword_Head` and friends are extra textures of `Sword`
+    let base = stem.split('_').next().unwrap_or(stem);
+    identifiers.contains(stem) || identifiers.contains(base)
+}
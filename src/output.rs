@@ -0,0 +1,140 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::TModError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    // Nushell object notation, so output can be piped straight into `from nuon`
+    Nuon,
+    // JSON wrapped in an envelope object, since PowerShell's `ConvertFrom-Json`
+    // unrolls top-level arrays and loses empty ones
+    PowerShell,
+    // GitHub Actions annotations for reports that find problems in entries,
+    // JSON for everything else
+    Github,
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Result<Self, TModError> {
+        Ok(match value {
+            "text" => OutputFormat::Text,
+            "json" => OutputFormat::Json,
+            "nuon" => OutputFormat::Nuon,
+            "powershell" | "pwsh" => OutputFormat::PowerShell,
+            "github" => OutputFormat::Github,
+            _ => return Err(TModError::UnknownFormat(value.to_string())),
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    kind: &'a str,
+    version: &'a str,
+    data: &'a T,
+}
+
+// renders any structured (non-text) format. `kind` names the report for the
+// PowerShell envelope.
+pub fn render<T: Serialize>(
+    format: OutputFormat,
+    kind: &str,
+    value: &T,
+) -> Result<String, TModError> {
+    Ok(match format {
+        OutputFormat::Text | OutputFormat::Json | OutputFormat::Github => {
+            serde_json::to_string_pretty(value)?
+        }
+        OutputFormat::Nuon => to_nuon(&serde_json::to_value(value)?),
+        OutputFormat::PowerShell => serde_json::to_string_pretty(&Envelope {
+            kind,
+            version: env!("CARGO_PKG_VERSION"),
+            data: value,
+        })?,
+    })
+}
+
+// a workflow command the Actions runner turns into an inline annotation on
+// `file`, e.g. `::error file=Items/Foo.png::icon is 16x16`
+pub fn github_annotation(level: &str, file: Option<&str>, message: &str) -> String {
+    let escape = |text: &str| {
+        text.replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    };
+    match file {
+        Some(file) => format!(
+            "::{} file={}::{}",
+            level,
+            escape(file).replace(':', "%3A").replace(',', "%2C"),
+            escape(message)
+        ),
+        None => format!("::{}::{}", level, escape(message)),
+    }
+}
+
+fn to_nuon(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        // nuon strings use the same escapes as JSON
+        Value::String(s) => Value::String(s.clone()).to_string(),
+        Value::Array(items) => match table_columns(items) {
+            Some(columns) => {
+                let header: Vec<String> = columns.iter().map(|c| nuon_key(c)).collect();
+                let rows: Vec<String> = items
+                    .iter()
+                    .map(|item| {
+                        let cells: Vec<String> =
+                            columns.iter().map(|c| to_nuon(&item[c.as_str()])).collect();
+                        format!("[{}]", cells.join(", "))
+                    })
+                    .collect();
+                format!("[[{}]; {}]", header.join(", "), rows.join(", "))
+            }
+            None => {
+                let items: Vec<String> = items.iter().map(to_nuon).collect();
+                format!("[{}]", items.join(", "))
+            }
+        },
+        Value::Object(fields) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(key, value)| format!("{}: {}", nuon_key(key), to_nuon(value)))
+                .collect();
+            format!("{{{}}}", fields.join(", "))
+        }
+    }
+}
+
+// a non-empty list of records that all have the same keys is written using
+// nuon's table literal syntax
+fn table_columns(items: &[Value]) -> Option<Vec<String>> {
+    let first = items.first()?.as_object()?;
+    let columns: Vec<String> = first.keys().cloned().collect();
+    let uniform = items.iter().all(|item| {
+        item.as_object()
+            .map(|fields| {
+                fields.len() == columns.len() && columns.iter().all(|c| fields.contains_key(c))
+            })
+            .unwrap_or(false)
+    });
+    uniform.then_some(columns)
+}
+
+fn nuon_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        Value::String(key.to_string()).to_string()
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    collections::HashSet,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::DeflateEncoder, Compression};
+use log::{debug, info, trace, warn};
+use sha1::{Digest, Sha1};
+
+use crate::audio;
+use crate::cancel::{self, CancellationToken};
+use crate::names::extension;
+use crate::optimize;
+use crate::{format_size, TModError, TMOD_HEADER};
+
+// tModLoader only tries to compress entries bigger than this
+const MIN_COMPRESS_SIZE: usize = 1 << 10;
+
+// and keeps the compressed data only if it saves at least a tenth
+const COMPRESSION_TRADEOFF: f64 = 0.9;
+
+// formats that are compressed already
+const UNCOMPRESSED_EXTENSIONS: &[&str] = &["png", "mp3", "ogg"];
+
+pub struct PackOptions {
+    pub input: PathBuf,
+    // defaults to the name of the input directory
+    pub name: Option<String>,
+    // defaults to the `version` in the input's build.txt
+    pub version: Option<String>,
+    pub tmodloader_version: String,
+    // defaults to `<name>.tmod` in `output_dir`
+    pub output: Option<PathBuf>,
+    // the current directory by default
+    pub output_dir: Option<PathBuf>,
+    // more directories whose files are packed, unless the input has a file
+    // of the same name. the first one with a name wins.
+    pub shared: Vec<PathBuf>,
+    // recompress PNG textures losslessly before packing them
+    pub optimize_png: bool,
+    // convert uncompressed and high-bitrate audio to Ogg Vorbis
+    pub reencode_audio: Option<audio::Reencode>,
+}
+
+// builds a .tmod from a directory, the reverse of unpacking. entries are
+// stored in name order and compressed the way tModLoader does it. the
+// signature is left empty, which is what tModLoader writes for mods that
+// weren't published through the mod browser.
+pub fn pack(options: &PackOptions) -> Result<(), TModError> {
+    let name = match &options.name {
+        Some(name) => name.clone(),
+        None => options
+            .input
+            .canonicalize()?
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    };
+    if options.optimize_png && !cfg!(feature = "images") {
+        return Err(TModError::FeatureDisabled("images"));
+    }
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(TModError::InvalidModName(name));
+    }
+    let version = match &options.version {
+        Some(version) => version.clone(),
+        None => build_version(&options.input)?.ok_or(TModError::MissingValue("--version"))?,
+    };
+    let output = options.output.clone().unwrap_or_else(|| {
+        let file_name = format!("{}.tmod", name);
+        match &options.output_dir {
+            Some(output_dir) => output_dir.join(file_name),
+            None => PathBuf::from(file_name),
+        }
+    });
+
+    info!("Packing {} v{}", name, version);
+    let mut paths = Vec::new();
+    collect_files(&options.input, &options.input, &mut paths)?;
+    let mut names: HashSet<String> = paths.iter().map(|(name, _)| name.clone()).collect();
+    for shared in &options.shared {
+        let mut shared_paths = Vec::new();
+        collect_files(shared, shared, &mut shared_paths)?;
+        for (entry_name, path) in shared_paths {
+            if names.insert(entry_name.clone()) {
+                paths.push((entry_name, path));
+            } else {
+                debug!("{} overrides the shared {:?}", entry_name, path);
+            }
+        }
+    }
+    paths.sort();
+    // an earlier pack written into the directory it packs
+    if let Ok(output) = output.canonicalize() {
+        paths.retain(|(_, path)| path.canonicalize().ok().as_ref() != Some(&output));
+    }
+
+    let mut writer = ArchiveWriter::new(&name, &version, paths.len());
+    let mut compressed_count = 0;
+    let mut png_savings = 0;
+    for (entry_name, path) in &paths {
+        trace!("packing file: {}", entry_name);
+        let mut contents = std::fs::read(path)?;
+        if options.optimize_png && extension(entry_name).as_deref() == Some("png") {
+            if let Some(optimized) = optimize::optimize_png(&contents) {
+                let saved = contents.len() - optimized.len();
+                println!(
+                    "  {}: {} -> {} ({:.1}% smaller)",
+                    entry_name,
+                    format_size(contents.len() as u64),
+                    format_size(optimized.len() as u64),
+                    saved as f64 * 100.0 / contents.len() as f64
+                );
+                png_savings += saved as u64;
+                contents = optimized;
+            }
+        }
+        let mut entry_name = entry_name.as_str();
+        let renamed;
+        if let Some(issue) = audio::check(entry_name, &contents) {
+            let ogg_name = audio::ogg_name(entry_name);
+            let taken = paths.iter().any(|(name, _)| *name == ogg_name);
+            match &options.reencode_audio {
+                Some(reencode) if !taken => {
+                    let encoded = reencode.encode(entry_name, &contents)?;
+                    if encoded.len() < contents.len() {
+                        println!(
+                            "  {}: re-encoded as {}, {} -> {}",
+                            entry_name,
+                            ogg_name,
+                            format_size(contents.len() as u64),
+                            format_size(encoded.len() as u64)
+                        );
+                        renamed = ogg_name;
+                        entry_name = &renamed;
+                        contents = encoded;
+                    } else {
+                        println!("  {}: re-encoding doesn't make it smaller", entry_name);
+                    }
+                }
+                Some(_) => println!(
+                    "  {}: not re-encoding, {} already exists",
+                    entry_name, ogg_name
+                ),
+                None => println!("  {}: {}", entry_name, issue.message()),
+            }
+        }
+        let uncompressed_len = entry_len(path, contents.len())?;
+        let stored = match compress(entry_name, &contents)? {
+            Some(compressed) => {
+                compressed_count += 1;
+                compressed
+            }
+            None => contents,
+        };
+        debug!(
+            "{}: {} bytes, stored as {}",
+            entry_name,
+            uncompressed_len,
+            stored.len()
+        );
+        writer.add(entry_name, uncompressed_len, &stored);
+    }
+    if options.optimize_png {
+        println!("Optimizing PNGs saved {}", format_size(png_savings));
+    }
+    let file = writer.finish(&options.tmodloader_version, &options.input)?;
+
+    if output.exists() {
+        warn!("overwriting {:?}", output);
+    }
+    cancel::write_atomically(&output, &file, &CancellationToken::default())?;
+    info!(
+        "Packed {} files ({} compressed) into {:?}, {}",
+        paths.len(),
+        compressed_count,
+        output,
+        format_size(file.len() as u64)
+    );
+    Ok(())
+}
+
+// the entry table and data region of a mod, built up one entry at a time in
+// the order they are added
+pub(crate) struct ArchiveWriter {
+    table: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl ArchiveWriter {
+    pub fn new(name: &str, version: &str, entries: usize) -> Self {
+        let mut table = Vec::new();
+        write_string(&mut table, name);
+        write_string(&mut table, version);
+        table.extend_from_slice(&(entries as i32).to_le_bytes());
+        Self {
+            table,
+            data: Vec::new(),
+        }
+    }
+
+    // `stored` is either the contents or their DEFLATE stream, told apart by
+    // whether its length matches `uncompressed_len`
+    pub fn add(&mut self, name: &str, uncompressed_len: i32, stored: &[u8]) {
+        write_string(&mut self.table, name);
+        self.table
+            .extend_from_slice(&uncompressed_len.to_le_bytes());
+        self.table
+            .extend_from_slice(&(stored.len() as i32).to_le_bytes());
+        self.data.extend_from_slice(stored);
+    }
+
+    // the whole .tmod, with its hash and an empty signature. `source` is
+    // what the mod is built from, for the error when it is too big.
+    pub fn finish(mut self, tmodloader_version: &str, source: &Path) -> Result<Vec<u8>, TModError> {
+        self.table.extend_from_slice(&self.data);
+        let table = self.table;
+        let data_len = u32::try_from(table.len())
+            .map_err(|_| TModError::InvalidEntry(source.to_path_buf(), "the mod is over 4 GiB"))?;
+
+        let mut file = Vec::with_capacity(table.len() + 512);
+        file.extend_from_slice(TMOD_HEADER);
+        write_string(&mut file, tmodloader_version);
+        file.extend_from_slice(&Sha1::digest(&table));
+        file.extend_from_slice(&[0; 256]);
+        file.extend_from_slice(&data_len.to_le_bytes());
+        file.extend_from_slice(&table);
+        Ok(file)
+    }
+}
+
+// every file below `dir` with its entry name, which always uses `/`
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    paths: &mut Vec<(String, PathBuf)>,
+) -> Result<(), TModError> {
+    for dir_entry in std::fs::read_dir(dir)? {
+        let path = dir_entry?.path();
+        let file_type = std::fs::symlink_metadata(&path)?.file_type();
+        if file_type.is_symlink() {
+            warn!("skipping symlink: {:?}", path);
+        } else if file_type.is_dir() {
+            collect_files(root, &path, paths)?;
+        } else {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            let components: Option<Vec<&str>> = relative
+                .components()
+                .map(|component| component.as_os_str().to_str())
+                .collect();
+            let entry_name = components
+                .ok_or_else(|| TModError::InvalidEntry(path.clone(), "its name isn't UTF-8"))?
+                .join("/");
+            paths.push((entry_name, path));
+        }
+    }
+    Ok(())
+}
+
+fn entry_len(path: &Path, len: usize) -> Result<i32, TModError> {
+    i32::try_from(len).map_err(|_| TModError::InvalidEntry(path.to_path_buf(), "it is over 2 GiB"))
+}
+
+// the compressed data, if tModLoader would have compressed the entry
+pub(crate) fn compress(name: &str, contents: &[u8]) -> Result<Option<Vec<u8>>, TModError> {
+    let skipped =
+        extension(name).map_or(false, |ext| UNCOMPRESSED_EXTENSIONS.contains(&ext.as_str()));
+    if contents.len() <= MIN_COMPRESS_SIZE || skipped {
+        return Ok(None);
+    }
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(contents)?;
+    let compressed = encoder.finish()?;
+    Ok(
+        ((compressed.len() as f64) < contents.len() as f64 * COMPRESSION_TRADEOFF)
+            .then_some(compressed),
+    )
+}
+
+// the `version = ...` line of build.txt, if there is one
+fn build_version(dir: &Path) -> Result<Option<String>, TModError> {
+    let build = dir.join("build.txt");
+    if !build.is_file() {
+        return Ok(None);
+    }
+    let text = std::fs::read_to_string(build)?;
+    Ok(text.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "version").then(|| value.trim().to_string())
+    }))
+}
+
+// a string prefixed with its length as a 7 bit encoded integer, the inverse
+// of `read_csharp_string`
+pub fn write_string(out: &mut Vec<u8>, value: &str) {
+    let mut len = value.len();
+    while len >= 0x80 {
+        out.push((len as u8 & 0x7f) | 0x80);
+        len >>= 7;
+    }
+    out.push(len as u8);
+    out.extend_from_slice(value.as_bytes());
+}
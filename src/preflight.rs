@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use log::trace;
+
+use crate::names::{components, unsafe_reason};
+use crate::ModFile;
+
+pub struct Preflight {
+    // problems that make extraction impossible or unsafe
+    pub problems: Vec<String>,
+    // names that would escape the output directory, only a problem unless
+    // they are explicitly allowed
+    pub unsafe_paths: Vec<String>,
+    // the file ending before the data of every entry, only a problem unless
+    // the entries it cuts off are to be skipped
+    pub missing_data: Option<String>,
+    // pairs of entries that would be written to the same path, either exactly
+    // or on a case-insensitive filesystem
+    pub conflicts: Vec<(String, String)>,
+    pub total_compressed: u64,
+    pub total_uncompressed: u64,
+}
+
+// limits on entry names, so exotic archives fail up front naming the entry
+// instead of with an opaque OS error halfway through extraction
+pub struct PathLimits {
+    // directories plus the file name
+    pub max_depth: usize,
+    // in bytes, which is what most filesystems limit
+    pub max_component_len: usize,
+}
+
+impl Default for PathLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 64,
+            max_component_len: 255,
+        }
+    }
+}
+
+// how much the entry table may claim its entries unpack to. every entry is
+// decompressed into a buffer of its declared size, so a crafted table could
+// otherwise ask for gigabytes from a tiny file.
+#[derive(Clone, Copy)]
+pub struct SizeLimits {
+    pub max_entry_size: u64,
+    pub max_total_size: u64,
+}
+
+impl Default for SizeLimits {
+    fn default() -> Self {
+        Self {
+            max_entry_size: 1024 * 1024 * 1024,
+            max_total_size: 8 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+// validates the entry table against the space actually available for file
+// data, without reading any of it
+pub fn check_entries(
+    files: &[ModFile],
+    available_data: u64,
+    limits: &PathLimits,
+    sizes: &SizeLimits,
+) -> Preflight {
+    let mut problems = Vec::new();
+    let mut unsafe_paths = Vec::new();
+    let mut conflicts = Vec::new();
+    let mut total_compressed = 0u64;
+    let mut total_uncompressed = 0u64;
+    let mut seen: HashMap<String, &str> = HashMap::with_capacity(files.len());
+
+    for file in files {
+        trace!("validating entry: {}", file.name);
+        let name = file.name.as_str();
+
+        if name.is_empty() {
+            problems.push("entry with an empty name".to_string());
+        } else if name.contains('\0') {
+            problems.push(format!("`{}`: name contains a NUL character", name));
+        } else if let Some(reason) = unsafe_reason(name) {
+            unsafe_paths.push(format!("`{}`: {}", name, reason));
+        } else {
+            let depth = components(name).count();
+            if depth > limits.max_depth {
+                problems.push(format!(
+                    "`{}`: {} path components, more than the limit of {}",
+                    name, depth, limits.max_depth
+                ));
+            }
+            if let Some(long) = components(name).find(|c| c.len() > limits.max_component_len) {
+                problems.push(format!(
+                    "`{}`: component `{}` is {} bytes, more than the limit of {}",
+                    name,
+                    long,
+                    long.len(),
+                    limits.max_component_len
+                ));
+            }
+        }
+
+        if let Some(problem) = entry_size_problem(file, sizes) {
+            problems.push(format!("`{}`: {}", name, problem));
+        }
+        if is_negative(file) {
+            continue;
+        }
+        total_compressed += file.compressed_len as u64;
+        total_uncompressed += file.uncompressed_len as u64;
+
+        if let Some(previous) = seen.insert(name.to_lowercase(), name) {
+            conflicts.push((previous.to_string(), name.to_string()));
+        }
+    }
+
+    if total_uncompressed > sizes.max_total_size {
+        problems.push(format!(
+            "entries unpack to {} bytes, more than the limit of {}",
+            total_uncompressed, sizes.max_total_size
+        ));
+    }
+    let mut missing_data = None;
+    if total_compressed > available_data {
+        missing_data = Some(format!(
+            "entries need {} bytes of file data but only {} are available",
+            total_compressed, available_data
+        ));
+    }
+
+    Preflight {
+        problems,
+        unsafe_paths,
+        missing_data,
+        conflicts,
+        total_compressed,
+        total_uncompressed,
+    }
+}
+
+// the sizes in an entry table that can't be right, by entry (`None` for the
+// table as a whole): negative lengths, and more than `sizes` allows. the
+// lengths can only be added up or used as offsets when there are none.
+pub fn size_problems<'a>(
+    files: &'a [ModFile],
+    sizes: &SizeLimits,
+) -> Vec<(Option<&'a str>, String)> {
+    let mut problems = Vec::new();
+    let mut total_uncompressed = 0u64;
+    for file in files {
+        if let Some(problem) = entry_size_problem(file, sizes) {
+            problems.push((Some(file.name.as_str()), problem));
+        }
+        if file.uncompressed_len > 0 {
+            total_uncompressed = total_uncompressed.saturating_add(file.uncompressed_len as u64);
+        }
+    }
+    if total_uncompressed > sizes.max_total_size {
+        problems.push((
+            None,
+            format!(
+                "entries unpack to {} bytes, more than the limit of {}",
+                total_uncompressed, sizes.max_total_size
+            ),
+        ));
+    }
+    problems
+}
+
+// the entries with a negative length, which nothing that adds lengths up
+// can go on with
+pub fn negative_lengths(files: &[ModFile]) -> Vec<String> {
+    files
+        .iter()
+        .filter(|file| is_negative(file))
+        .map(|file| format!("`{}`: {}", file.name, negative_length(file)))
+        .collect()
+}
+
+fn is_negative(file: &ModFile) -> bool {
+    file.compressed_len < 0 || file.uncompressed_len < 0
+}
+
+fn negative_length(file: &ModFile) -> String {
+    format!(
+        "negative length (compressed {}, uncompressed {})",
+        file.compressed_len, file.uncompressed_len
+    )
+}
+
+fn entry_size_problem(file: &ModFile, sizes: &SizeLimits) -> Option<String> {
+    if is_negative(file) {
+        Some(negative_length(file))
+    } else if file.uncompressed_len as u64 > sizes.max_entry_size {
+        Some(format!(
+            "unpacks to {} bytes, more than the limit of {}",
+            file.uncompressed_len, sizes.max_entry_size
+        ))
+    } else {
+        None
+    }
+}
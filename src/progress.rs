@@ -0,0 +1,220 @@
+#[cfg(feature = "progress")]
+use std::{
+    io::Write,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+
+#[cfg(feature = "progress")]
+use crate::format_size;
+
+// one line of `--progress-json`, written to stderr after every entry
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ProgressEvent {
+    pub entries_done: usize,
+    pub entries_total: usize,
+    // uncompressed, as stored in the entry table
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+}
+
+// tracks an extraction by entries and by bytes. the progress bar advances by
+// bytes, so one huge sound doesn't count the same as a tiny localization file
+// and the ETA stays honest.
+pub struct Progress {
+    event: ProgressEvent,
+    json: bool,
+    #[cfg(feature = "progress")]
+    bar: Option<Bar>,
+}
+
+impl Progress {
+    // `bar` is whether to draw a progress bar on stderr at all; it is only
+    // drawn when stderr is a terminal, so redirected output stays clean
+    pub fn start(entries_total: usize, bytes_total: u64, json: bool, bar: bool) -> Self {
+        #[cfg(not(feature = "progress"))]
+        let _ = bar;
+        Self {
+            event: ProgressEvent {
+                entries_done: 0,
+                entries_total,
+                bytes_done: 0,
+                bytes_total,
+            },
+            json,
+            #[cfg(feature = "progress")]
+            bar: Bar::start(bar && !json),
+        }
+    }
+
+    pub fn advance(&mut self, bytes: u64) {
+        self.event.entries_done += 1;
+        self.event.bytes_done += bytes;
+
+        #[cfg(feature = "progress")]
+        if let Some(bar) = &mut self.bar {
+            bar.draw_event(&self.event, false);
+        }
+
+        if self.json {
+            if let Ok(line) = serde_json::to_string(&self.event) {
+                eprintln!("{}", line);
+            }
+        }
+    }
+
+    pub fn finish(self) {
+        #[cfg(feature = "progress")]
+        if let Some(mut bar) = self.bar {
+            bar.draw_event(&self.event, true);
+            bar.clear();
+            eprintln!(
+                "Extracted {} entries ({}) in {:.1}s",
+                self.event.entries_done,
+                format_size(self.event.bytes_done),
+                bar.started.elapsed().as_secs_f64()
+            );
+        }
+    }
+}
+
+// a download of `total` bytes, when the server said how big it is; without
+// that there is no bar, since there is nothing to fill it against
+pub struct Download {
+    #[cfg(feature = "progress")]
+    bar: Option<Bar>,
+    #[cfg(feature = "progress")]
+    done: u64,
+    #[cfg(feature = "progress")]
+    total: u64,
+}
+
+impl Download {
+    pub fn start(total: Option<u64>) -> Self {
+        #[cfg(not(feature = "progress"))]
+        let _ = total;
+        Self {
+            #[cfg(feature = "progress")]
+            bar: total.and_then(|_| Bar::start(true)),
+            #[cfg(feature = "progress")]
+            done: 0,
+            #[cfg(feature = "progress")]
+            total: total.unwrap_or_default(),
+        }
+    }
+
+    pub fn advance(&mut self, bytes: u64) {
+        #[cfg(feature = "progress")]
+        {
+            self.done += bytes;
+            if let Some(bar) = &mut self.bar {
+                bar.draw_download(self.done, self.total, false);
+            }
+        }
+        #[cfg(not(feature = "progress"))]
+        let _ = bytes;
+    }
+
+    pub fn finish(self) {
+        #[cfg(feature = "progress")]
+        if let Some(mut bar) = self.bar {
+            bar.draw_download(self.done, self.total, true);
+            bar.clear();
+        }
+    }
+}
+
+// redrawing is throttled, so extracting thousands of tiny files isn't slowed
+// down by the terminal
+#[cfg(feature = "progress")]
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+#[cfg(feature = "progress")]
+const BAR_WIDTH: usize = 24;
+
+// a single line on stderr, redrawn in place with `\r`. plain characters
+// only, so it looks the same in every terminal, the Windows console included.
+#[cfg(feature = "progress")]
+struct Bar {
+    started: Instant,
+    last_draw: Option<Instant>,
+    // the length of the last line, to blank out what a shorter one leaves
+    last_len: usize,
+}
+
+#[cfg(feature = "progress")]
+impl Bar {
+    fn start(enabled: bool) -> Option<Self> {
+        use is_terminal::IsTerminal;
+        (enabled && std::io::stderr().is_terminal()).then(|| Self {
+            started: Instant::now(),
+            last_draw: None,
+            last_len: 0,
+        })
+    }
+
+    fn draw_event(&mut self, event: &ProgressEvent, force: bool) {
+        let detail = format!("{}/{} entries", event.entries_done, event.entries_total);
+        self.draw(
+            "Extracting",
+            event.bytes_done,
+            event.bytes_total,
+            &detail,
+            force,
+        );
+    }
+
+    fn draw_download(&mut self, done: u64, total: u64, force: bool) {
+        let detail = format!("{}/{}", format_size(done), format_size(total));
+        self.draw("Downloading", done, total, &detail, force);
+    }
+
+    fn draw(&mut self, verb: &str, done: u64, total: u64, detail: &str, force: bool) {
+        let now = Instant::now();
+        if !force
+            && matches!(self.last_draw, Some(last) if now.duration_since(last) < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        self.last_draw = Some(now);
+
+        let fraction = match total {
+            0 => 1.0,
+            total => (done as f64 / total as f64).min(1.0),
+        };
+        let filled = ((fraction * BAR_WIDTH as f64) as usize).min(BAR_WIDTH);
+        let mut line = format!(
+            "{} [{}{}] {:>3}% {}",
+            verb,
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            (fraction * 100.0) as u32,
+            detail
+        );
+        // wait for a second of data before guessing
+        let elapsed = self.started.elapsed().as_secs_f64();
+        if fraction > 0.0 && fraction < 1.0 && elapsed >= 1.0 {
+            let left = elapsed / fraction - elapsed;
+            line.push_str(&format!(", {}s left", left.ceil() as u64));
+        }
+        self.write(&line);
+    }
+
+    fn clear(&mut self) {
+        self.write("");
+        let _ = write!(std::io::stderr(), "\r");
+    }
+
+    fn write(&mut self, line: &str) {
+        let len = line.chars().count();
+        let padding = self.last_len.saturating_sub(len);
+        self.last_len = len;
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r{}{}", line, " ".repeat(padding));
+        let _ = stderr.flush();
+    }
+}
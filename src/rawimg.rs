@@ -0,0 +1,80 @@
+use crate::TModError;
+
+// tModLoader converts PNG textures to `.rawimg` when building a mod: an i32
+// format version (1), i32 width and height, then straight RGBA8 pixels
+const RAWIMG_VERSION: i32 = 1;
+const HEADER_LEN: usize = 12;
+
+pub struct RawImage<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: &'a [u8],
+}
+
+pub fn parse<'a>(name: &str, data: &'a [u8]) -> Result<RawImage<'a>, TModError> {
+    let invalid = |reason: String| TModError::InvalidImage(name.to_string(), reason);
+    if data.len() < HEADER_LEN {
+        return Err(invalid(format!("{} bytes is too short", data.len())));
+    }
+    let field =
+        |at: usize| i32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+
+    let version = field(0);
+    if version != RAWIMG_VERSION {
+        return Err(invalid(format!("unsupported version {}", version)));
+    }
+    let (width, height) = (field(4), field(8));
+    if width < 0 || height < 0 {
+        return Err(invalid(format!("negative size {}x{}", width, height)));
+    }
+    let (width, height) = (width as u32, height as u32);
+
+    let len = width as usize * height as usize * 4;
+    let rgba = data.get(HEADER_LEN..HEADER_LEN + len).ok_or_else(|| {
+        invalid(format!(
+            "{}x{} pixels don't fit in {} bytes",
+            width,
+            height,
+            data.len()
+        ))
+    })?;
+    Ok(RawImage {
+        width,
+        height,
+        rgba,
+    })
+}
+
+impl RawImage<'_> {
+    #[cfg(feature = "images")]
+    pub fn to_png(&self) -> Result<Vec<u8>, TModError> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(png_error)?;
+        writer.write_image_data(self.rgba).map_err(png_error)?;
+        writer.finish().map_err(png_error)?;
+        Ok(out)
+    }
+
+    #[cfg(not(feature = "images"))]
+    pub fn to_png(&self) -> Result<Vec<u8>, TModError> {
+        Err(TModError::FeatureDisabled("images"))
+    }
+
+    // back into the format it was parsed from
+    pub fn to_rawimg(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN + self.rgba.len());
+        out.extend(RAWIMG_VERSION.to_le_bytes());
+        out.extend((self.width as i32).to_le_bytes());
+        out.extend((self.height as i32).to_le_bytes());
+        out.extend(self.rgba);
+        out
+    }
+}
+
+#[cfg(feature = "images")]
+fn png_error(e: png::EncodingError) -> TModError {
+    TModError::IoError(std::io::Error::new(std::io::ErrorKind::Other, e))
+}
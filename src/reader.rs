@@ -0,0 +1,242 @@
+use std::{
+    io::{Read, Write},
+    path::Path,
+};
+
+use binary_rw::{BinaryReader, SeekStream};
+use flate2::read::DeflateDecoder;
+use log::trace;
+
+use crate::cancel::CancellationToken;
+use crate::legacy;
+use crate::order::data_offsets;
+use crate::preflight::SizeLimits;
+use crate::sink::{DirSink, OutputSink};
+use crate::source::{self, SourceStream};
+use crate::{
+    decompress_slice, read_entry_table, read_file_data, read_header, ModFile, ModHeader, TModError,
+};
+
+// an opened mod: its header and entry table are parsed up front, entries are
+// read on demand. this is the entry point for using the parser as a library,
+// e.g.
+//
+//     let mut reader = TModReader::open("MyMod.tmod")?;
+//     println!("{} v{}", reader.name(), reader.version());
+//     let data = reader.read("build.txt")?;
+//     reader.extract_to("MyMod-extracted")?;
+//
+// entries are inflated into memory, so what the entry table says they unpack
+// to is checked against `SizeLimits::default()` before any of it is read; use
+// `with_limits` (or `from_bytes_with_limits`) for other limits.
+pub struct TModReader {
+    stream: SourceStream,
+    header: ModHeader,
+    entries: Vec<ModFile>,
+    // where each entry's data starts, in table order
+    offsets: Vec<usize>,
+    limits: SizeLimits,
+}
+
+impl TModReader {
+    // a path, an http(s) url (with the `http` feature) or `-` for stdin
+    pub fn open(input: &str) -> Result<Self, TModError> {
+        Self::with_limits(input, SizeLimits::default())
+    }
+
+    // like `open`, with the size limits that reading entries (and converting
+    // a legacy mod) is held to
+    pub fn with_limits(input: &str, limits: SizeLimits) -> Result<Self, TModError> {
+        trace!("opening file: {}", input);
+        Self::from_source_with_limits(source::open_limited(input, &limits)?, limits)
+    }
+
+    // like `open`, but memory-maps files, so entries are decompressed
+    // straight from the mapping instead of being read into a buffer first
+    pub fn open_mapped(input: &str) -> Result<Self, TModError> {
+        trace!("mapping file: {}", input);
+        let limits = SizeLimits::default();
+        Self::from_source_with_limits(source::open_mapped(input, &limits)?, limits)
+    }
+
+    // a mod that is already in memory
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, TModError> {
+        Self::from_bytes_with_limits(data, SizeLimits::default())
+    }
+
+    pub fn from_bytes_with_limits(data: Vec<u8>, limits: SizeLimits) -> Result<Self, TModError> {
+        let stream = legacy::upgrade(SourceStream::from_bytes(data), &limits)?;
+        Self::from_source_with_limits(stream, limits)
+    }
+
+    pub fn from_source(stream: SourceStream) -> Result<Self, TModError> {
+        Self::from_source_with_limits(stream, SizeLimits::default())
+    }
+
+    fn from_source_with_limits(
+        mut stream: SourceStream,
+        limits: SizeLimits,
+    ) -> Result<Self, TModError> {
+        let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+        let header = read_header(&mut reader, None)?;
+        let entries = read_entry_table(&mut reader)?;
+        let offsets = data_offsets(&entries, reader.tell()?)?;
+        Ok(Self {
+            stream,
+            header,
+            entries,
+            offsets,
+            limits,
+        })
+    }
+
+    pub fn header(&self) -> &ModHeader {
+        &self.header
+    }
+
+    pub fn name(&self) -> &str {
+        &self.header.mod_name
+    }
+
+    pub fn version(&self) -> &str {
+        &self.header.mod_version
+    }
+
+    pub fn tmodloader_version(&self) -> &str {
+        &self.header.tmodloader_version
+    }
+
+    pub fn fingerprint(&self) -> String {
+        self.header.fingerprint()
+    }
+
+    // every entry, in table order
+    pub fn entries(&self) -> &[ModFile] {
+        &self.entries
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&ModFile> {
+        self.entries.iter().find(|file| file.name == name)
+    }
+
+    // where the data of the entry at `index` starts in the archive
+    pub fn offset(&self, index: usize) -> usize {
+        self.offsets[index]
+    }
+
+    // the stored (possibly compressed) bytes of the entry at `index`, without
+    // copying them, when the mod is mapped or in memory
+    pub fn stored_bytes(&self, index: usize) -> Option<&[u8]> {
+        let file = &self.entries[index];
+        self.stream
+            .slice(self.offsets[index], file.compressed_len as usize)
+    }
+
+    // the decompressed contents of the entry at `index` in `entries()`. an
+    // entry cut short by the end of the file fails with `TruncatedEntry`, one
+    // bigger than the size limits with `TooLarge`.
+    pub fn read_entry(&mut self, index: usize) -> Result<Vec<u8>, TModError> {
+        self.check_size(index)?;
+        let file = &self.entries[index];
+        if let Some(stored) = self.stored_bytes(index) {
+            return decompress_slice(file, stored);
+        }
+        // not in memory, or not all there: reading checks which
+        let mut reader = BinaryReader::new(&mut self.stream, binary_rw::Endian::Little);
+        reader.seek(self.offsets[index])?;
+        read_file_data(&mut reader, file)
+    }
+
+    // writes the decompressed contents of the entry at `index` to `out`,
+    // without holding all of it in memory when the mod is mapped or in memory.
+    // returns how many bytes were written.
+    pub fn copy_entry(&mut self, index: usize, out: &mut dyn Write) -> Result<u64, TModError> {
+        self.check_size(index)?;
+        let file = &self.entries[index];
+        let stored = match self.stored_bytes(index) {
+            Some(stored) => stored,
+            None => {
+                let data = self.read_entry(index)?;
+                out.write_all(&data)?;
+                return Ok(data.len() as u64);
+            }
+        };
+        if file.compressed_len == file.uncompressed_len {
+            out.write_all(stored)?;
+            return Ok(stored.len() as u64);
+        }
+        let expected = file.uncompressed_len as u64;
+        // one byte more than expected is enough to tell it inflates to more
+        let mut decoder = DeflateDecoder::new(stored).take(expected + 1);
+        let len = std::io::copy(&mut decoder, out)?;
+        if len != expected {
+            return Err(TModError::InflateError(
+                "miniz",
+                format!(
+                    "{} doesn't inflate to the {} bytes the entry table says",
+                    file.name, expected
+                ),
+            ));
+        }
+        Ok(len)
+    }
+
+    // the table was checked for negative lengths when the mod was opened
+    fn check_size(&self, index: usize) -> Result<(), TModError> {
+        let file = &self.entries[index];
+        let len = file.uncompressed_len as u64;
+        if len > self.limits.max_entry_size {
+            return Err(TModError::TooLarge(
+                file.name.clone(),
+                len,
+                self.limits.max_entry_size,
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn read(&mut self, name: &str) -> Result<Vec<u8>, TModError> {
+        let index = self
+            .entries
+            .iter()
+            .position(|file| file.name == name)
+            .ok_or_else(|| TModError::EntryNotFound(name.to_string()))?;
+        self.read_entry(index)
+    }
+
+    // writes every entry to `sink`, in table order
+    pub fn extract(&mut self, sink: &mut dyn OutputSink) -> Result<(), TModError> {
+        let total: u64 = self
+            .entries
+            .iter()
+            .map(|file| file.uncompressed_len as u64)
+            .sum();
+        if total > self.limits.max_total_size {
+            return Err(TModError::TooLarge(
+                self.header.mod_name.clone(),
+                total,
+                self.limits.max_total_size,
+            ));
+        }
+        for index in 0..self.entries.len() {
+            let data = self.read_entry(index)?;
+            sink.write_entry(&self.entries[index], &data)?;
+        }
+        sink.finish()
+    }
+
+    // extracts into a directory, creating it if needed. unlike the CLI this
+    // doesn't check the hash or take a lock, see `unpack` for that.
+    pub fn extract_to(&mut self, out_dir: impl AsRef<Path>) -> Result<(), TModError> {
+        let out_dir = out_dir.as_ref();
+        std::fs::create_dir_all(out_dir)?;
+        self.extract(&mut DirSink {
+            out_dir: Some(out_dir),
+            routes: &[],
+            force: false,
+            allow_unsafe_paths: false,
+            retries: 0,
+            cancel: CancellationToken::default(),
+        })
+    }
+}
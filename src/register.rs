@@ -0,0 +1,198 @@
+// associates .tmod files with this binary for the current user, so
+// double-clicking a mod extracts it
+#[cfg(all(unix, not(target_os = "macos")))]
+use std::path::PathBuf;
+#[cfg(any(windows, all(unix, not(target_os = "macos"))))]
+use std::process::Command;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+use log::warn;
+#[cfg(any(windows, all(unix, not(target_os = "macos"))))]
+use log::{info, trace};
+
+use crate::TModError;
+
+#[cfg(windows)]
+const PROG_ID: &str = "tModLoader.Mod";
+
+#[cfg(windows)]
+pub fn register() -> Result<(), TModError> {
+    let exe = std::env::current_exe()?;
+    let command = format!("\"{}\" \"%1\"", exe.display());
+
+    reg(&[
+        "add",
+        r"HKCU\Software\Classes\.tmod",
+        "/ve",
+        "/d",
+        PROG_ID,
+        "/f",
+    ])?;
+    reg(&[
+        "add",
+        &format!(r"HKCU\Software\Classes\{}", PROG_ID),
+        "/ve",
+        "/d",
+        "tModLoader mod",
+        "/f",
+    ])?;
+    reg(&[
+        "add",
+        &format!(r"HKCU\Software\Classes\{}\shell\open\command", PROG_ID),
+        "/ve",
+        "/d",
+        &command,
+        "/f",
+    ])?;
+
+    info!("Registered {} for .tmod files", exe.display());
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn unregister() -> Result<(), TModError> {
+    reg(&[
+        "delete",
+        &format!(r"HKCU\Software\Classes\{}", PROG_ID),
+        "/f",
+    ])?;
+    reg(&["delete", r"HKCU\Software\Classes\.tmod", "/f"])?;
+    info!("Removed the .tmod file association");
+    Ok(())
+}
+
+#[cfg(windows)]
+fn reg(args: &[&str]) -> Result<(), TModError> {
+    run("reg", args)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+const MIME_TYPE: &str = "application/x-tmod";
+
+#[cfg(all(unix, not(target_os = "macos")))]
+const DESKTOP_FILE: &str = "tmod-unpacker.desktop";
+
+// a shared-mime-info package so `.tmod` files (and anything starting with the
+// magic) get a type of their own
+#[cfg(all(unix, not(target_os = "macos")))]
+const MIME_PACKAGE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<mime-info xmlns="http://www.freedesktop.org/standards/shared-mime-info">
+  <mime-type type="application/x-tmod">
+    <comment>tModLoader mod</comment>
+    <glob pattern="*.tmod"/>
+    <magic priority="50">
+      <match type="string" offset="0" value="TMOD"/>
+    </magic>
+  </mime-type>
+</mime-info>
+"#;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn register() -> Result<(), TModError> {
+    let exe = std::env::current_exe()?;
+    let data_home = data_home()?;
+
+    let mime_dir = data_home.join("mime");
+    let packages = mime_dir.join("packages");
+    std::fs::create_dir_all(&packages)?;
+    trace!("writing mime package to {:?}", packages);
+    std::fs::write(packages.join("tmod-unpacker.xml"), MIME_PACKAGE)?;
+
+    let applications = data_home.join("applications");
+    std::fs::create_dir_all(&applications)?;
+    let desktop_entry = format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=tModLoader mod unpacker\n\
+         Comment=Extract the contents of a tModLoader mod\n\
+         Exec=\"{}\" %f\n\
+         Terminal=true\n\
+         NoDisplay=true\n\
+         MimeType={};\n",
+        exe.display(),
+        MIME_TYPE
+    );
+    trace!("writing desktop entry to {:?}", applications);
+    std::fs::write(applications.join(DESKTOP_FILE), desktop_entry)?;
+
+    refresh_databases(&mime_dir, &applications);
+    if let Err(e) = run("xdg-mime", &["default", DESKTOP_FILE, MIME_TYPE]) {
+        warn!("could not make it the default application: {}", e);
+    }
+
+    info!("Registered {} for .tmod files", exe.display());
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn unregister() -> Result<(), TModError> {
+    let data_home = data_home()?;
+    let mime_dir = data_home.join("mime");
+    let applications = data_home.join("applications");
+
+    for path in [
+        mime_dir.join("packages").join("tmod-unpacker.xml"),
+        applications.join(DESKTOP_FILE),
+    ] {
+        if path.exists() {
+            trace!("removing {:?}", path);
+            std::fs::remove_file(path)?;
+        }
+    }
+
+    refresh_databases(&mime_dir, &applications);
+    info!("Removed the .tmod file association");
+    Ok(())
+}
+
+// the caches are only an optimisation, so a desktop without the tools still
+// picks the files up eventually
+#[cfg(all(unix, not(target_os = "macos")))]
+fn refresh_databases(mime_dir: &std::path::Path, applications: &std::path::Path) {
+    let mime_dir = mime_dir.display().to_string();
+    if let Err(e) = run("update-mime-database", &[&mime_dir]) {
+        warn!("could not update the MIME database: {}", e);
+    }
+    let applications = applications.display().to_string();
+    if let Err(e) = run("update-desktop-database", &[&applications]) {
+        warn!("could not update the desktop database: {}", e);
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn data_home() -> Result<PathBuf, TModError> {
+    if let Some(dir) = std::env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+    std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".local").join("share"))
+        .ok_or_else(|| TModError::RegisterError("HOME is not set".into()))
+}
+
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+pub fn register() -> Result<(), TModError> {
+    Err(TModError::RegisterError(
+        "file associations are only supported on Windows and Linux".into(),
+    ))
+}
+
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+pub fn unregister() -> Result<(), TModError> {
+    register()
+}
+
+#[cfg(any(windows, all(unix, not(target_os = "macos"))))]
+fn run(program: &str, args: &[&str]) -> Result<(), TModError> {
+    trace!("running {} {:?}", program, args);
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .map_err(|e| TModError::RegisterError(format!("could not run {}: {}", program, e)))?;
+    if !status.success() {
+        return Err(TModError::RegisterError(format!(
+            "{} exited with {}",
+            program, status
+        )));
+    }
+    Ok(())
+}
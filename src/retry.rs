@@ -0,0 +1,46 @@
+use std::{io, thread, time::Duration};
+
+use log::warn;
+
+// doubled after every failed attempt
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+// errors that network filesystems (NFS, SMB, FUSE mounts) report now and then
+// and that usually go away when the operation is simply tried again
+pub fn is_transient(e: &io::Error) -> bool {
+    use io::ErrorKind::*;
+
+    if matches!(
+        e.kind(),
+        Interrupted | TimedOut | WouldBlock | ConnectionReset | ConnectionAborted | BrokenPipe
+    ) {
+        return true;
+    }
+    // EIO and ESTALE
+    cfg!(target_os = "linux") && matches!(e.raw_os_error(), Some(5) | Some(116))
+}
+
+// runs `op` up to `retries` more times while it fails with a transient error,
+// backing off exponentially in between
+pub fn with_retries<T>(
+    what: &str,
+    retries: u32,
+    mut op: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(e) if attempt < retries && is_transient(&e) => {
+                attempt += 1;
+                warn!(
+                    "{}: {}, retrying in {:?} ({}/{})",
+                    what, e, backoff, attempt, retries
+                );
+                thread::sleep(backoff);
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "globs")]
+use globset::GlobBuilder;
+#[cfg(feature = "globs")]
+pub use globset::GlobMatcher;
+
+use crate::TModError;
+
+// sends entries matching a glob to a different output root, written on the
+// command line as `<glob>=><directory>`
+pub struct Route {
+    pub pattern: String,
+    matcher: GlobMatcher,
+    pub root: PathBuf,
+}
+
+impl Route {
+    pub fn parse(value: &str) -> Result<Self, TModError> {
+        let (pattern, root) = value.split_once("=>").ok_or_else(|| {
+            TModError::InvalidRoute(value.to_string(), "expected `<glob>=><directory>`".into())
+        })?;
+        let (pattern, root) = (pattern.trim(), root.trim());
+        if root.is_empty() {
+            return Err(TModError::InvalidRoute(
+                value.to_string(),
+                "missing destination directory".into(),
+            ));
+        }
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            matcher: compile_glob(pattern)?,
+            root: PathBuf::from(root),
+        })
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        self.matcher.is_match(name)
+    }
+}
+
+// globs match entry names with `/` as the separator: `*` stays within one
+// directory and `**` crosses any number of them
+#[cfg(feature = "globs")]
+pub fn compile_glob(pattern: &str) -> Result<GlobMatcher, TModError> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+        .map_err(|e| TModError::InvalidGlob(pattern.to_string(), e.to_string()))
+}
+
+#[cfg(not(feature = "globs"))]
+pub fn compile_glob(_pattern: &str) -> Result<GlobMatcher, TModError> {
+    Err(TModError::FeatureDisabled("globs"))
+}
+
+// without the `globs` feature no glob compiles, so there is never a matcher
+#[cfg(not(feature = "globs"))]
+pub enum GlobMatcher {}
+
+#[cfg(not(feature = "globs"))]
+impl GlobMatcher {
+    pub fn is_match(&self, _name: &str) -> bool {
+        match *self {}
+    }
+}
+
+// the root an entry is extracted under: the first matching route, falling
+// back to the default output directory
+pub fn destination<'a>(
+    routes: &'a [Route],
+    name: &str,
+    default: Option<&'a Path>,
+) -> Option<&'a Path> {
+    routes
+        .iter()
+        .find(|route| route.matches(name))
+        .map(|route| route.root.as_path())
+        .or(default)
+}
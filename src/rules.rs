@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use regex::bytes::Regex;
+use serde::Deserialize;
+
+use crate::TModError;
+
+// the on-disk shape of a rules file, e.g.
+//
+// rules:
+//   - name: discord-webhook
+//     regex: "https://discord(app)?\\.com/api/webhooks/"
+//   - name: upx-packed
+//     bytes: "55505821"
+//     description: UPX packed executable
+#[derive(Debug, Deserialize)]
+struct RulesFile {
+    rules: Vec<RuleDefinition>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuleDefinition {
+    name: String,
+    description: Option<String>,
+    regex: Option<String>,
+    bytes: Option<String>,
+}
+
+pub struct Rule {
+    pub name: String,
+    pub description: Option<String>,
+    pattern: Regex,
+}
+
+impl Rule {
+    pub fn matches(&self, data: &[u8]) -> bool {
+        self.pattern.is_match(data)
+    }
+}
+
+pub fn load_rules(path: impl AsRef<Path>) -> Result<Vec<Rule>, TModError> {
+    let contents = std::fs::read_to_string(path)?;
+    let file: RulesFile = serde_yaml::from_str(&contents)?;
+
+    file.rules.into_iter().map(compile_rule).collect()
+}
+
+fn compile_rule(definition: RuleDefinition) -> Result<Rule, TModError> {
+    let pattern = match (definition.regex, definition.bytes) {
+        (Some(regex), None) => regex,
+        (None, Some(bytes)) => {
+            let bytes = hex::decode(bytes.replace(' ', ""))
+                .map_err(|e| TModError::InvalidRule(definition.name.clone(), e.to_string()))?;
+            // match the raw bytes literally, with unicode disabled so every
+            // escape stands for exactly one byte
+            let escaped: String = bytes.iter().map(|b| format!("\\x{:02x}", b)).collect();
+            format!("(?-u){}", escaped)
+        }
+        _ => {
+            return Err(TModError::InvalidRule(
+                definition.name,
+                "exactly one of `regex` or `bytes` must be given".to_string(),
+            ))
+        }
+    };
+
+    let pattern = Regex::new(&pattern)
+        .map_err(|e| TModError::InvalidRule(definition.name.clone(), e.to_string()))?;
+
+    Ok(Rule {
+        name: definition.name,
+        description: definition.description,
+        pattern,
+    })
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use crate::names::extension;
+use crate::ModFile;
+
+// picks a subset of entries to extract when probing the structure of a big mod
+pub struct Sample {
+    pub count: usize,
+    // pick entries at random instead of the first ones in the table
+    pub random: bool,
+    // take `count` entries of every extension instead of `count` in total
+    pub per_extension: bool,
+    pub seed: Option<u64>,
+}
+
+impl Sample {
+    // a mask over `files` of the entries to extract, picked among those
+    // already `eligible` (e.g. through `--include`)
+    pub fn select(&self, files: &[ModFile], eligible: &[bool]) -> Vec<bool> {
+        let candidates = (0..files.len()).filter(|&index| eligible[index]);
+        let mut groups: HashMap<Option<String>, Vec<usize>> = HashMap::new();
+        if self.per_extension {
+            for index in candidates {
+                groups
+                    .entry(extension(&files[index].name))
+                    .or_default()
+                    .push(index);
+            }
+        } else {
+            groups.insert(None, candidates.collect());
+        }
+
+        let mut rng = SplitMix64::new(self.seed.unwrap_or_else(time_seed));
+        let mut selected = vec![false; files.len()];
+        // iterate groups in a fixed order so a given seed always picks the
+        // same entries
+        let mut groups: Vec<_> = groups.into_iter().collect();
+        groups.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, mut indices) in groups {
+            if self.random {
+                // partial Fisher-Yates shuffle of just the entries we keep
+                let keep = self.count.min(indices.len());
+                for i in 0..keep {
+                    let j = i + (rng.next() % (indices.len() - i) as u64) as usize;
+                    indices.swap(i, j);
+                }
+            }
+            for &index in indices.iter().take(self.count) {
+                selected[index] = true;
+            }
+        }
+        selected
+    }
+}
+
+fn time_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+// tiny, good enough PRNG so sampling doesn't need a dependency
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
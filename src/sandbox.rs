@@ -0,0 +1,277 @@
+use std::path::Path;
+
+use crate::TModError;
+
+// with the `hardened` feature, an extraction gives up everything it doesn't
+// need once the input is open and the output exists, so a bug in the parser
+// or a decompressor that a hostile archive manages to exploit can't reach
+// much:
+//
+// - landlock limits the filesystem to the output roots given here; files
+//   that are already open, like the input, keep working
+// - seccomp makes creating sockets, running programs and tracing other
+//   processes fail with EPERM
+//
+// both last until the process exits. landlock needs Linux 5.13, and is skipped
+// with a warning on older kernels.
+#[cfg(target_os = "linux")]
+pub fn restrict(write_roots: &[&Path]) -> Result<(), TModError> {
+    use log::{debug, warn};
+
+    // both require it, and it stops setuid programs from undoing them
+    if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+        return Err(sandbox_error("PR_SET_NO_NEW_PRIVS"));
+    }
+    match landlock::restrict(write_roots)? {
+        Some(abi) => debug!("landlock ABI {} applied to {:?}", abi, write_roots),
+        None => warn!("landlock isn't supported by this kernel, the filesystem isn't restricted"),
+    }
+    seccomp::restrict()?;
+    debug!("seccomp filter installed");
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn restrict(_write_roots: &[&Path]) -> Result<(), TModError> {
+    Err(TModError::SandboxError(
+        "only supported on Linux".to_string(),
+    ))
+}
+
+#[cfg(target_os = "linux")]
+fn sandbox_error(what: &str) -> TModError {
+    TModError::SandboxError(format!("{}: {}", what, std::io::Error::last_os_error()))
+}
+
+#[cfg(target_os = "linux")]
+mod landlock {
+    use std::{fs::OpenOptions, os::unix::fs::OpenOptionsExt, os::unix::io::AsRawFd, path::Path};
+
+    use super::sandbox_error;
+    use crate::TModError;
+
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+        handled_access_net: u64,
+    }
+
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    const CREATE_RULESET_VERSION: u32 = 1 << 0;
+    const RULE_PATH_BENEATH: u32 = 1;
+
+    const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+    const ACCESS_FS_MAKE_CHAR: u64 = 1 << 6;
+    const ACCESS_FS_MAKE_SOCK: u64 = 1 << 9;
+    const ACCESS_FS_MAKE_BLOCK: u64 = 1 << 11;
+    // everything up to MAKE_SYM is in the first ABI
+    const ACCESS_FS_V1: u64 = (1 << 13) - 1;
+    const ACCESS_FS_REFER: u64 = 1 << 13;
+    const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+    const ACCESS_NET_BIND_TCP: u64 = 1 << 0;
+    const ACCESS_NET_CONNECT_TCP: u64 = 1 << 1;
+
+    // the ABI version that was applied, `None` without landlock
+    pub fn restrict(write_roots: &[&Path]) -> Result<Option<i64>, TModError> {
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<RulesetAttr>(),
+                0,
+                CREATE_RULESET_VERSION,
+            )
+        };
+        if abi < 1 {
+            return Ok(None);
+        }
+
+        // only what this kernel knows about can be handled, the rest stays
+        // allowed
+        let mut handled_fs = ACCESS_FS_V1;
+        if abi >= 2 {
+            handled_fs |= ACCESS_FS_REFER;
+        }
+        if abi >= 3 {
+            handled_fs |= ACCESS_FS_TRUNCATE;
+        }
+        let mut handled_net = 0;
+        if abi >= 4 {
+            handled_net |= ACCESS_NET_BIND_TCP | ACCESS_NET_CONNECT_TCP;
+        }
+        let attr = RulesetAttr {
+            handled_access_fs: handled_fs,
+            handled_access_net: handled_net,
+        };
+        let ruleset = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                std::mem::size_of::<RulesetAttr>(),
+                0,
+            )
+        };
+        if ruleset < 0 {
+            return Err(sandbox_error("landlock_create_ruleset"));
+        }
+        let ruleset = ruleset as i32;
+
+        // reading and writing files and directories below each root, but
+        // not running anything or making device nodes and sockets
+        let allowed = handled_fs
+            & !(ACCESS_FS_EXECUTE
+                | ACCESS_FS_MAKE_CHAR
+                | ACCESS_FS_MAKE_SOCK
+                | ACCESS_FS_MAKE_BLOCK);
+        let result = (|| {
+            for root in write_roots {
+                let dir = OpenOptions::new()
+                    .read(true)
+                    .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+                    .open(root)?;
+                let rule = PathBeneathAttr {
+                    allowed_access: allowed,
+                    parent_fd: dir.as_raw_fd(),
+                };
+                let added = unsafe {
+                    libc::syscall(
+                        libc::SYS_landlock_add_rule,
+                        ruleset,
+                        RULE_PATH_BENEATH,
+                        &rule as *const PathBeneathAttr,
+                        0,
+                    )
+                };
+                if added != 0 {
+                    return Err(sandbox_error("landlock_add_rule"));
+                }
+            }
+            if unsafe { libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0) } != 0 {
+                return Err(sandbox_error("landlock_restrict_self"));
+            }
+            Ok(Some(abi))
+        })();
+        unsafe { libc::close(ruleset) };
+        result
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod seccomp {
+    use super::sandbox_error;
+    use crate::TModError;
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+    const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+    // on x86_64, x32 syscalls are numbered from here and would get around
+    // the list below
+    #[cfg(target_arch = "x86_64")]
+    const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+    // offsets into `struct seccomp_data`
+    const NR_OFFSET: u32 = 0;
+    const ARCH_OFFSET: u32 = 4;
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    const DENIED: &[i64] = &[
+        libc::SYS_socket,
+        libc::SYS_socketpair,
+        libc::SYS_connect,
+        libc::SYS_bind,
+        libc::SYS_listen,
+        libc::SYS_accept,
+        libc::SYS_accept4,
+        libc::SYS_execve,
+        libc::SYS_execveat,
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+    ];
+
+    fn statement(code: u32, k: u32) -> libc::sock_filter {
+        libc::sock_filter {
+            code: code as u16,
+            jt: 0,
+            jf: 0,
+            k,
+        }
+    }
+
+    fn jump(code: u32, k: u32, jt: usize, jf: usize) -> libc::sock_filter {
+        libc::sock_filter {
+            code: code as u16,
+            jt: jt as u8,
+            jf: jf as u8,
+            k,
+        }
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    pub fn restrict() -> Result<(), TModError> {
+        let load = libc::BPF_LD | libc::BPF_W | libc::BPF_ABS;
+        let equals = libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K;
+        let ret = libc::BPF_RET | libc::BPF_K;
+
+        let mut filter = vec![
+            statement(load, ARCH_OFFSET),
+            jump(equals, AUDIT_ARCH, 1, 0),
+            statement(ret, SECCOMP_RET_KILL_PROCESS),
+            statement(load, NR_OFFSET),
+        ];
+        // every check jumps to the final `deny` when it matches; the
+        // distances are filled in once the length is known
+        let mut checks = Vec::new();
+        #[cfg(target_arch = "x86_64")]
+        {
+            checks.push(filter.len());
+            filter.push(jump(
+                libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K,
+                X32_SYSCALL_BIT,
+                0,
+                0,
+            ));
+        }
+        for &nr in DENIED {
+            checks.push(filter.len());
+            filter.push(jump(equals, nr as u32, 0, 0));
+        }
+        filter.push(statement(ret, libc::SECCOMP_RET_ALLOW));
+        let deny = filter.len();
+        filter.push(statement(ret, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32));
+        for at in checks {
+            filter[at].jt = (deny - at - 1) as u8;
+        }
+
+        let program = libc::sock_fprog {
+            len: filter.len() as u16,
+            filter: filter.as_mut_ptr(),
+        };
+        // every thread, not just this one: the Ctrl-C handler already runs
+        let installed = unsafe {
+            libc::syscall(
+                libc::SYS_seccomp,
+                libc::SECCOMP_SET_MODE_FILTER,
+                libc::SECCOMP_FILTER_FLAG_TSYNC,
+                &program as *const libc::sock_fprog,
+            )
+        };
+        if installed != 0 {
+            return Err(sandbox_error("seccomp"));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    pub fn restrict() -> Result<(), TModError> {
+        log::warn!("no seccomp filter for this architecture, system calls aren't restricted");
+        Ok(())
+    }
+}
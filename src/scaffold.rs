@@ -0,0 +1,166 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use binary_rw::{BinaryReader, SeekStream};
+use log::{info, trace};
+use serde_json::Value;
+
+use crate::localization::{self, localization_file};
+use crate::names::extension;
+use crate::order::data_offsets;
+use crate::source;
+use crate::{rawimg, read_entry_table, read_file_data, read_header, TModError};
+
+// entries tModLoader generates when building, which a source tree doesn't have
+const BUILD_OUTPUTS: &[&str] = &["Info", "icon.png", "icon.rawimg"];
+
+const ICON_SIZE: u32 = 80;
+
+pub struct ScaffoldOptions {
+    pub from: String,
+    pub name: String,
+    // defaults to a directory named after the new mod
+    pub output: Option<PathBuf>,
+}
+
+// creates the skeleton of a new mod modeled after an existing one: its
+// directory layout, a build.txt, a main class, its localization keys with the
+// values left empty, and a placeholder icon. none of the original's assets,
+// code or text are copied.
+pub fn scaffold(options: &ScaffoldOptions) -> Result<(), TModError> {
+    let name = options.name.as_str();
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(TModError::InvalidModName(name.to_string()));
+    }
+    let out_dir = options
+        .output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(name));
+    if out_dir.exists() && std::fs::read_dir(&out_dir)?.next().is_some() {
+        return Err(TModError::OutputExists(out_dir));
+    }
+
+    trace!("opening file: {}", options.from);
+    let mut stream = source::open(&options.from)?;
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+
+    let header = read_header(&mut reader, None)?;
+    let file_entries = read_entry_table(&mut reader)?;
+    let offsets = data_offsets(&file_entries, reader.tell()?)?;
+    let old_name = header.mod_name.as_str();
+
+    info!("Scaffolding {} from {}", name, old_name);
+    let mut directories = BTreeSet::new();
+    // everything is parsed before anything is written, so a broken file in
+    // the original doesn't leave a half-made mod behind
+    let mut localization_files = Vec::new();
+    let mut skipped = 0;
+    for (file, offset) in file_entries.iter().zip(offsets) {
+        if BUILD_OUTPUTS.contains(&file.name.as_str()) {
+            continue;
+        }
+        if let Some((directory, _)) = file.name.rsplit_once('/') {
+            directories.insert(directory.to_string());
+        }
+
+        if localization_file(&file.name).is_some() {
+            // only localization files are read at all
+            reader.seek(offset)?;
+            let data = read_file_data(&mut reader, file)?;
+            let text = String::from_utf8_lossy(&data);
+            let mut value = localization::parse_value(&file.name, &text)?;
+            blank(&mut value, old_name, name, false);
+            let file_name = file
+                .name
+                .replace(&format!("Mods.{}", old_name), &format!("Mods.{}", name));
+            localization_files.push((file_name, serde_json::to_string_pretty(&value)?));
+        } else if !matches!(extension(&file.name).as_deref(), Some("dll" | "pdb")) {
+            skipped += 1;
+        }
+    }
+
+    for directory in &directories {
+        std::fs::create_dir_all(out_dir.join(directory))?;
+    }
+    for (file_name, contents) in &localization_files {
+        write(&out_dir, file_name, contents)?;
+    }
+    write(
+        &out_dir,
+        "build.txt",
+        &format!("displayName = {}\nauthor = \nversion = 0.1\n", name),
+    )?;
+    write(&out_dir, "description.txt", "")?;
+    write(
+        &out_dir,
+        &format!("{}.cs", name),
+        &format!(
+            "using Terraria.ModLoader;\n\nnamespace {}\n{{\n\tpublic class {} : Mod\n\t{{\n\t}}\n}}\n",
+            name, name
+        ),
+    )?;
+    std::fs::write(out_dir.join("icon.png"), placeholder_icon()?)?;
+
+    println!(
+        "Scaffolded {} from {} v{} in {}: {} directories, {} localization files ({} assets not copied)",
+        name,
+        old_name,
+        header.mod_version,
+        out_dir.display(),
+        directories.len(),
+        localization_files.len(),
+        skipped
+    );
+
+    Ok(())
+}
+
+fn write(out_dir: &Path, name: &str, contents: &str) -> Result<(), TModError> {
+    let path = out_dir.join(name);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    trace!("writing file: {:?}", path);
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+// keeps the structure of a localization file but empties every value, and
+// moves keys under `Mods.<old name>` to the new mod
+fn blank(value: &mut Value, old_name: &str, new_name: &str, under_mods: bool) {
+    match value {
+        Value::Object(map) => {
+            if under_mods {
+                if let Some(inner) = map.remove(old_name) {
+                    map.insert(new_name.to_string(), inner);
+                }
+            }
+            for (key, value) in map.iter_mut() {
+                blank(value, old_name, new_name, key == "Mods");
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                blank(value, old_name, new_name, false);
+            }
+        }
+        value => *value = Value::String(String::new()),
+    }
+}
+
+fn placeholder_icon() -> Result<Vec<u8>, TModError> {
+    let rgba: Vec<u8> = [0x80, 0x80, 0x80, 0xff]
+        .iter()
+        .copied()
+        .cycle()
+        .take((ICON_SIZE * ICON_SIZE * 4) as usize)
+        .collect();
+    rawimg::RawImage {
+        width: ICON_SIZE,
+        height: ICON_SIZE,
+        rgba: &rgba,
+    }
+    .to_png()
+}
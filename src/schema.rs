@@ -0,0 +1,102 @@
+use std::path::Path;
+
+use log::{info, trace};
+#[cfg(feature = "schema")]
+use schemars::{schema::RootSchema, schema_for};
+
+use crate::TModError;
+#[cfg(feature = "schema")]
+use crate::{
+    audit, bench, capabilities, check, corpus, dedup, diff, doctor, entry_log, layout, lint,
+    orphans, progress, stats, strings, verify_dir,
+};
+
+// every kind of machine-readable output, named like the subcommand that
+// writes it, with what it describes
+pub const KINDS: &[(&str, &str)] = &[
+    (
+        "manifest",
+        "one line of `--emit-entries`, as read by `verify-dir`",
+    ),
+    ("info", "`info`"),
+    ("list", "`list`"),
+    ("diff", "`diff` without `--localization`"),
+    ("progress", "one line of `--progress-json`"),
+    ("check", "`check`"),
+    ("layout", "`layout`"),
+    ("lint", "`lint`"),
+    ("audit", "`audit`"),
+    ("orphans", "`orphans`"),
+    ("strings", "`strings`"),
+    ("stats", "`stats`"),
+    ("stats-history", "`stats --history`"),
+    ("dedup", "`dedup`"),
+    ("verify-dir", "`verify-dir`"),
+    ("bench", "`bench`"),
+    ("doctor", "`doctor`"),
+    ("corpus", "`corpus run`"),
+    ("capabilities", "`capabilities`"),
+];
+
+// the JSON Schema of one kind of output, generated from the types it is
+// serialized from, so it can't drift from what is actually written. the
+// PowerShell format wraps the same data in `{ kind, version, data }`.
+#[cfg(feature = "schema")]
+pub fn schema(kind: &str) -> Result<RootSchema, TModError> {
+    Ok(match kind {
+        "manifest" => schema_for!(entry_log::EntryRecord),
+        "info" => schema_for!(crate::info::ModInfo),
+        "list" => schema_for!(Vec<crate::info::ListedEntry>),
+        "diff" => schema_for!(diff::DiffReport),
+        "progress" => schema_for!(progress::ProgressEvent),
+        "check" => schema_for!(check::CheckReport),
+        "layout" => schema_for!(layout::Layout),
+        "lint" => schema_for!(lint::LintReport),
+        "audit" => schema_for!(audit::AuditReport),
+        "orphans" => schema_for!(orphans::OrphanReport),
+        "strings" => schema_for!(Vec<strings::FoundString>),
+        "stats" => schema_for!(stats::ModStats),
+        "stats-history" => schema_for!(Vec<stats::ModStats>),
+        "dedup" => schema_for!(dedup::DedupReport),
+        "verify-dir" => schema_for!(verify_dir::DirReport),
+        "bench" => schema_for!(bench::BenchReport),
+        "doctor" => schema_for!(Vec<doctor::Check>),
+        "corpus" => schema_for!(corpus::CorpusReport),
+        "capabilities" => schema_for!(capabilities::Capabilities),
+        _ => return Err(TModError::UnknownSchema(kind.to_string())),
+    })
+}
+
+#[cfg(feature = "schema")]
+fn schema_json(kind: &str) -> Result<String, TModError> {
+    Ok(serde_json::to_string_pretty(&schema(kind)?)?)
+}
+
+#[cfg(not(feature = "schema"))]
+fn schema_json(_kind: &str) -> Result<String, TModError> {
+    Err(TModError::FeatureDisabled("schema"))
+}
+
+pub fn print_schema(kind: Option<&str>) -> Result<(), TModError> {
+    match kind {
+        Some(kind) => println!("{}", schema_json(kind)?),
+        None => {
+            for (kind, description) in KINDS {
+                println!("{:<14} {}", kind, description);
+            }
+        }
+    }
+    Ok(())
+}
+
+// every schema as `<kind>.schema.json`, for publishing alongside a release
+pub fn write_schemas(out_dir: &Path) -> Result<(), TModError> {
+    std::fs::create_dir_all(out_dir)?;
+    for (kind, _) in KINDS {
+        let path = out_dir.join(format!("{}.schema.json", kind));
+        trace!("writing schema: {:?}", path);
+        std::fs::write(&path, schema_json(kind)? + "\n")?;
+    }
+    info!("Wrote {} schemas to {:?}", KINDS.len(), out_dir);
+    Ok(())
+}
@@ -0,0 +1,765 @@
+use std::{
+    io::{BufWriter, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use log::{info, trace, warn};
+use serde::Serialize;
+
+use crate::info::{listed_entry, mod_info, ListedEntry, ModInfo};
+use crate::names::unsafe_reason;
+use crate::preflight::SizeLimits;
+use crate::{TModError, TModReader};
+
+// a request line and headers longer than this are refused
+const MAX_HEAD_LEN: usize = 16 * 1024;
+// `/extract` writes plain zip, not zip64, so its counts and offsets have to fit
+const MAX_ZIP_ENTRIES: usize = u16::MAX as usize;
+const MAX_ZIP_LEN: u64 = u32::MAX as u64;
+// what `/extract` buffers before sending it as a chunk
+const CHUNK_LEN: usize = 64 * 1024;
+
+#[derive(Clone)]
+pub struct ServeOptions {
+    // the address to listen on, e.g. `127.0.0.1:8080`
+    pub listen: String,
+    // the biggest upload (or download, for `?url=`) accepted
+    pub max_size: u64,
+    // the most an archive may inflate to for `/extract`
+    pub max_unpacked: u64,
+    // for reading the request, handling it and writing the response
+    pub timeout: Duration,
+    // how many connections are handled at once; more wait to be accepted
+    pub max_connections: usize,
+    // whether `?url=` may make the server fetch a mod
+    pub allow_fetch: bool,
+}
+
+// the body of a successful `POST /inspect`
+#[derive(Serialize)]
+pub struct InspectResponse {
+    pub info: ModInfo,
+    pub entries: Vec<ListedEntry>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse<'a> {
+    error: &'a str,
+    // the `explain` code, when the archive itself was the problem
+    code: Option<&'static str>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    content_type: String,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+// `/extract` sends its zip as it is written rather than as one response
+enum Reply {
+    Done(Response),
+    Extract(TModReader),
+}
+
+impl Response {
+    fn json(status: u16, body: &impl Serialize) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_vec(body).unwrap_or_default(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::json(
+            status,
+            &ErrorResponse {
+                error: message,
+                code: None,
+            },
+        )
+    }
+
+    fn from_error(e: &TModError) -> Self {
+        let status = match e {
+            TModError::IoError(_) | TModError::Cancelled => 500,
+            #[cfg(feature = "http")]
+            TModError::HttpError(..) => 502,
+            TModError::FeatureDisabled(_) => 501,
+            TModError::TooLarge(..) => 413,
+            _ => 400,
+        };
+        Self::json(
+            status,
+            &ErrorResponse {
+                error: &e.to_string(),
+                code: Some(e.code()),
+            },
+        )
+    }
+}
+
+// a stateless HTTP API over the parser, for sites that want to check uploaded
+// mods without writing their own:
+//
+//     POST /inspect   the mod's metadata and entry table as JSON
+//     POST /extract   every entry in a zip archive
+//
+// the mod is the request body, either as is or as the first file of a
+// `multipart/form-data` upload, or, with the `http` feature and
+// `--allow-fetch`, fetched from `?url=`. nothing is written to disk and
+// nothing is kept between requests. every connection gets its own thread, up
+// to `max_connections` of them, and is closed after one response.
+pub fn serve(options: &ServeOptions) -> Result<(), TModError> {
+    let listener = TcpListener::bind(&options.listen)?;
+    println!("Listening on http://{}", listener.local_addr()?);
+    serve_on(listener, options);
+    Ok(())
+}
+
+pub(crate) fn serve_on(listener: TcpListener, options: &ServeOptions) {
+    let slots = Arc::new(Slots::default());
+    loop {
+        // taken before accepting, so connections over the limit wait in the
+        // listen backlog instead of getting a thread each
+        let slot = slots.take(options.max_connections.max(1));
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("could not accept a connection: {}", e);
+                continue;
+            }
+        };
+        let options = options.clone();
+        std::thread::spawn(move || {
+            handle(stream, &options);
+            drop(slot);
+        });
+    }
+}
+
+#[derive(Default)]
+struct Slots {
+    active: Mutex<usize>,
+    freed: Condvar,
+}
+
+impl Slots {
+    fn take(self: &Arc<Self>, max: usize) -> Slot {
+        let mut active = self.active.lock().unwrap_or_else(PoisonError::into_inner);
+        while *active >= max {
+            active = self
+                .freed
+                .wait(active)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *active += 1;
+        Slot(self.clone())
+    }
+}
+
+// frees up a connection once its thread is done, even if it panicked
+struct Slot(Arc<Slots>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        *self.0.active.lock().unwrap_or_else(PoisonError::into_inner) -= 1;
+        self.0.freed.notify_one();
+    }
+}
+
+fn handle(mut stream: TcpStream, options: &ServeOptions) {
+    let deadline = Instant::now() + options.timeout;
+    let _ = stream.set_read_timeout(Some(options.timeout));
+    let _ = stream.set_write_timeout(Some(options.timeout));
+
+    let (label, reply) = match read_request(&mut stream, options, deadline) {
+        Ok(request) => (
+            format!("{} {}", request.method, request.path),
+            route(&request, options, deadline),
+        ),
+        Err(response) => ("-".to_string(), Reply::Done(response)),
+    };
+    let written = match reply {
+        Reply::Done(response) => {
+            info!("{} -> {}", label, response.status);
+            send(&mut stream, &response)
+        }
+        Reply::Extract(mut reader) => {
+            info!("{} -> 200", label);
+            send_zip(&mut stream, &mut reader, deadline)
+        }
+    };
+    if let Err(e) = written {
+        trace!("could not send the response: {}", e);
+    }
+}
+
+fn send(stream: &mut TcpStream, response: &Response) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+// the zip goes out as it is written, one entry in memory at a time. the status
+// is sent before any of it, so a failure part way (a corrupt entry, the
+// deadline) can only drop the connection: without the final chunk the client
+// sees an incomplete response rather than a short zip
+fn send_zip(
+    stream: &mut TcpStream,
+    reader: &mut TModReader,
+    deadline: Instant,
+) -> std::io::Result<()> {
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: application/zip\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n",
+    )?;
+    let mut chunked = Chunked(stream);
+    let mut writer = ZipStream::new(BufWriter::with_capacity(CHUNK_LEN, &mut chunked));
+    for index in 0..reader.entries().len() {
+        if Instant::now() > deadline {
+            return Err(ErrorKind::TimedOut.into());
+        }
+        let data = reader
+            .read_entry(index)
+            .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e.to_string()))?;
+        let file = &reader.entries()[index];
+        writer.add(
+            &file.name,
+            &data,
+            file.compressed_len != file.uncompressed_len,
+        )?;
+    }
+    writer.finish()?.flush()?;
+    chunked.finish()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        403 => "Forbidden",
+        408 => "Request Timeout",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+fn read_request(
+    stream: &mut TcpStream,
+    options: &ServeOptions,
+    deadline: Instant,
+) -> Result<Request, Response> {
+    let timed_out = || Response::error(408, "the request took too long to arrive");
+    let read_error = |e: std::io::Error| match e.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => timed_out(),
+        _ => Response::error(400, &e.to_string()),
+    };
+
+    let mut buf = Vec::new();
+    let mut chunk = [0; 4096];
+    let head_end = loop {
+        if let Some(at) = buf.windows(4).position(|window| window == b"\r\n\r\n") {
+            break at;
+        }
+        if buf.len() > MAX_HEAD_LEN {
+            return Err(Response::error(431, "the request headers are too long"));
+        }
+        if Instant::now() > deadline {
+            return Err(timed_out());
+        }
+        match stream.read(&mut chunk).map_err(read_error)? {
+            0 => return Err(Response::error(400, "the request ended early")),
+            n => buf.extend_from_slice(&chunk[..n]),
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut content_len = None;
+    let mut content_type = String::new();
+    for line in lines {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.trim().to_ascii_lowercase(), value.trim()),
+            None => continue,
+        };
+        match name.as_str() {
+            "content-length" => {
+                let len = value
+                    .parse::<u64>()
+                    .map_err(|_| Response::error(400, "invalid Content-Length"))?;
+                content_len = Some(len);
+            }
+            "content-type" => content_type = value.to_string(),
+            "transfer-encoding" => {
+                return Err(Response::error(411, "send the body with a Content-Length"))
+            }
+            _ => {}
+        }
+    }
+    let content_len = content_len.unwrap_or(0);
+    // refused before reading any of it
+    if content_len > options.max_size {
+        return Err(Response::error(
+            413,
+            &format!("uploads are limited to {} bytes", options.max_size),
+        ));
+    }
+
+    let mut body = buf.split_off(head_end + 4);
+    while (body.len() as u64) < content_len {
+        if Instant::now() > deadline {
+            return Err(timed_out());
+        }
+        match stream.read(&mut chunk).map_err(read_error)? {
+            0 => return Err(Response::error(400, "the request body ended early")),
+            n => body.extend_from_slice(&chunk[..n]),
+        }
+    }
+    body.truncate(content_len as usize);
+
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query: query.to_string(),
+        content_type,
+        body,
+    })
+}
+
+fn route(request: &Request, options: &ServeOptions, deadline: Instant) -> Reply {
+    let extract = match request.path.as_str() {
+        "/inspect" => false,
+        "/extract" => true,
+        _ => {
+            return Reply::Done(Response::error(
+                404,
+                "expected POST /inspect or POST /extract",
+            ))
+        }
+    };
+    if request.method != "POST" {
+        return Reply::Done(Response::error(405, "only POST is supported"));
+    }
+
+    let data = match upload(request, options, deadline) {
+        Ok(data) => data,
+        Err(response) => return Reply::Done(response),
+    };
+    // a legacy mod is inflated whole to be read at all, so `max_unpacked`
+    // holds for `/inspect` too
+    let limits = SizeLimits {
+        max_entry_size: options.max_unpacked,
+        max_total_size: options.max_unpacked,
+    };
+    let reader = match TModReader::from_bytes_with_limits(data, limits) {
+        Ok(reader) => reader,
+        Err(e) => return Reply::Done(Response::from_error(&e)),
+    };
+    if !extract {
+        return Reply::Done(Response::json(
+            200,
+            &InspectResponse {
+                info: mod_info(reader.header(), reader.entries()),
+                entries: reader.entries().iter().map(listed_entry).collect(),
+            },
+        ));
+    }
+    match check_extract(&reader, options) {
+        Ok(()) => Reply::Extract(reader),
+        Err(response) => Reply::Done(response),
+    }
+}
+
+// everything that can refuse an `/extract` has to be checked before the status
+// line goes out
+fn check_extract(reader: &TModReader, options: &ServeOptions) -> Result<(), Response> {
+    let entries = reader.entries();
+    if let Some((file, reason)) = entries
+        .iter()
+        .find_map(|file| unsafe_reason(&file.name).map(|reason| (file, reason)))
+    {
+        return Err(Response::from_error(&TModError::UnsafePath(
+            file.name.clone(),
+            reason,
+        )));
+    }
+
+    let unpacked: u64 = entries
+        .iter()
+        .map(|file| file.uncompressed_len.max(0) as u64)
+        .sum();
+    if unpacked > options.max_unpacked {
+        return Err(Response::error(
+            413,
+            &format!(
+                "the mod unpacks to {} bytes, over the limit of {}",
+                unpacked, options.max_unpacked
+            ),
+        ));
+    }
+
+    // entries are only deflated when that makes them smaller, so this bounds
+    // the whole zip
+    let zip_len = unpacked
+        + entries
+            .iter()
+            .map(|file| ZipStream::<Vec<u8>>::overhead(&file.name))
+            .sum::<u64>()
+        + ZipStream::<Vec<u8>>::END_LEN;
+    if entries.len() > MAX_ZIP_ENTRIES || zip_len > MAX_ZIP_LEN {
+        return Err(Response::error(
+            413,
+            "the mod is too big to extract as a zip archive",
+        ));
+    }
+    Ok(())
+}
+
+// the mod from the request: `?url=`, a multipart upload or the raw body
+fn upload(
+    request: &Request,
+    options: &ServeOptions,
+    deadline: Instant,
+) -> Result<Vec<u8>, Response> {
+    let url = request
+        .query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("url="));
+    if let Some(url) = url {
+        if !options.allow_fetch {
+            return Err(Response::error(
+                403,
+                "fetching mods from `?url=` is turned off, start the server with --allow-fetch",
+            ));
+        }
+        return fetch(&percent_decode(url), options.max_size, deadline);
+    }
+
+    let boundary = request
+        .content_type
+        .strip_prefix("multipart/form-data")
+        .and_then(|params| params.split("boundary=").nth(1))
+        .map(|boundary| {
+            boundary
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim_matches('"')
+        });
+    let data = match boundary {
+        Some(boundary) => first_part(&request.body, boundary)
+            .ok_or_else(|| Response::error(400, "no file in the multipart upload"))?
+            .to_vec(),
+        None => request.body.clone(),
+    };
+    if data.is_empty() {
+        return Err(Response::error(400, "no mod was uploaded"));
+    }
+    Ok(data)
+}
+
+// the contents of the first part of a `multipart/form-data` body
+fn first_part<'a>(body: &'a [u8], boundary: &str) -> Option<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let start = find(body, delimiter.as_bytes())? + delimiter.len();
+    let headers_end = start + find(&body[start..], b"\r\n\r\n")? + 4;
+    let end = find(
+        &body[headers_end..],
+        format!("\r\n{}", delimiter).as_bytes(),
+    )?;
+    Some(&body[headers_end..headers_end + end])
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(feature = "http")]
+fn fetch(url: &str, max_size: u64, deadline: Instant) -> Result<Vec<u8>, Response> {
+    if !crate::source::is_url(url) {
+        return Err(Response::error(400, "only http(s) urls can be fetched"));
+    }
+    trace!("fetching: {}", url);
+    let http_error = |e: String| Response::from_error(&TModError::HttpError(url.to_string(), e));
+    // every connection, redirects included, goes through the resolver, so
+    // a name that resolves (or is rebound) to an internal address is refused
+    // at connect time rather than only checked up front
+    let agent = ureq::AgentBuilder::new()
+        .timeout(deadline.saturating_duration_since(Instant::now()))
+        .redirects(5)
+        .resolver(|netloc: &str| {
+            let addrs: Vec<_> = std::net::ToSocketAddrs::to_socket_addrs(netloc)?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(std::io::Error::new(
+                    ErrorKind::PermissionDenied,
+                    format!("`{}` is not a public address", netloc),
+                ));
+            }
+            Ok(addrs)
+        })
+        .build();
+    let response = agent
+        .get(url)
+        .call()
+        .map_err(|e| http_error(e.to_string()))?;
+    let mut data = Vec::new();
+    response
+        .into_reader()
+        .take(max_size + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| http_error(e.to_string()))?;
+    if data.len() as u64 > max_size {
+        return Err(Response::error(
+            413,
+            &format!("downloads are limited to {} bytes", max_size),
+        ));
+    }
+    Ok(data)
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch(_url: &str, _max_size: u64, _deadline: Instant) -> Result<Vec<u8>, Response> {
+    Err(Response::from_error(&TModError::FeatureDisabled("http")))
+}
+
+// whether `?url=` may connect to an address: not loopback, private, link-local
+// or otherwise reserved, including those written as IPv4 inside IPv6
+#[cfg(feature = "http")]
+pub(crate) fn is_public(ip: std::net::IpAddr) -> bool {
+    use std::net::IpAddr;
+
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                // shared address space (carrier-grade NAT)
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 192 && b == 0 && ip.octets()[2] == 0)
+                // benchmarking
+                || (a == 198 && (b == 18 || b == 19))
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            match segments {
+                // IPv4-mapped, IPv4-compatible and NAT64
+                [0, 0, 0, 0, 0, 0xffff, ..]
+                | [0, 0, 0, 0, 0, 0, ..]
+                | [0x64, 0xff9b, 0, 0, 0, 0, ..]
+                    if !ip.is_loopback() && !ip.is_unspecified() =>
+                {
+                    let [.., c, d, e, f] = ip.octets();
+                    is_public(IpAddr::from([c, d, e, f]))
+                }
+                _ => {
+                    !(ip.is_unspecified()
+                        || ip.is_loopback()
+                        || ip.is_multicast()
+                        // unique local
+                        || segments[0] & 0xfe00 == 0xfc00
+                        // link-local
+                        || segments[0] & 0xffc0 == 0xfe80
+                        // documentation
+                        || (segments[0] == 0x2001 && segments[1] == 0xdb8))
+                }
+            }
+        }
+    }
+}
+
+// HTTP/1.1 chunked transfer encoding, one chunk per write
+struct Chunked<W: Write>(W);
+
+impl<W: Write> Chunked<W> {
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.0.write_all(b"0\r\n\r\n")?;
+        self.0.flush()
+    }
+}
+
+impl<W: Write> Write for Chunked<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        write!(self.0, "{:x}\r\n", buf.len())?;
+        self.0.write_all(buf)?;
+        self.0.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+// a zip archive written front to back, for `/extract`. the zip crate needs to
+// seek back over each local header, which a socket can't do; here every entry
+// is already in memory, so its sizes and crc are known before its header
+pub(crate) struct ZipStream<W: Write> {
+    writer: W,
+    offset: u64,
+    central: Vec<u8>,
+    count: u16,
+}
+
+impl<W: Write> ZipStream<W> {
+    const LOCAL_LEN: u64 = 30;
+    const CENTRAL_LEN: u64 = 46;
+    pub(crate) const END_LEN: u64 = 22;
+    // general purpose flag 11: the names are UTF-8
+    const UTF8: u16 = 1 << 11;
+    // 1980-01-01, the earliest date a zip can hold
+    const DATE: u16 = (1 << 5) | 1;
+
+    pub(crate) fn new(writer: W) -> Self {
+        Self {
+            writer,
+            offset: 0,
+            central: Vec::new(),
+            count: 0,
+        }
+    }
+
+    // the headers an entry adds to the archive besides its data
+    pub(crate) fn overhead(name: &str) -> u64 {
+        Self::LOCAL_LEN + Self::CENTRAL_LEN + 2 * name.len() as u64
+    }
+
+    // stored or deflated like the entry was in the mod, but only deflated if
+    // that makes it smaller
+    pub(crate) fn add(&mut self, name: &str, data: &[u8], deflate: bool) -> std::io::Result<()> {
+        let mut crc = flate2::Crc::new();
+        crc.update(data);
+        let deflated = if deflate {
+            let mut encoder =
+                flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            Some(encoder.finish()?).filter(|deflated| deflated.len() < data.len())
+        } else {
+            None
+        };
+        let (method, stored): (u16, &[u8]) = match &deflated {
+            Some(deflated) => (8, deflated),
+            None => (0, data),
+        };
+        let too_big = || std::io::Error::new(ErrorKind::InvalidInput, "too big for a zip archive");
+        let stored_len = u32::try_from(stored.len()).map_err(|_| too_big())?;
+        let data_len = u32::try_from(data.len()).map_err(|_| too_big())?;
+        let offset = u32::try_from(self.offset).map_err(|_| too_big())?;
+        let name_len = u16::try_from(name.len()).map_err(|_| too_big())?;
+        self.count = self.count.checked_add(1).ok_or_else(too_big)?;
+
+        // the fields the local and central headers share, from "version needed"
+        let mut common = Vec::with_capacity(26);
+        for field in [20, Self::UTF8, method, 0, Self::DATE] {
+            common.extend_from_slice(&field.to_le_bytes());
+        }
+        for field in [crc.sum(), stored_len, data_len] {
+            common.extend_from_slice(&field.to_le_bytes());
+        }
+        common.extend_from_slice(&name_len.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+
+        self.writer.write_all(&0x0403_4b50u32.to_le_bytes())?;
+        self.writer.write_all(&common)?;
+        self.writer.write_all(name.as_bytes())?;
+        self.writer.write_all(stored)?;
+        self.offset += Self::LOCAL_LEN + name.len() as u64 + stored.len() as u64;
+
+        self.central
+            .extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        // version made by
+        self.central.extend_from_slice(&20u16.to_le_bytes());
+        self.central.extend_from_slice(&common);
+        // comment length, disk number, internal and external attributes
+        self.central.extend_from_slice(&[0; 10]);
+        self.central.extend_from_slice(&offset.to_le_bytes());
+        self.central.extend_from_slice(name.as_bytes());
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> std::io::Result<W> {
+        let too_big = || std::io::Error::new(ErrorKind::InvalidInput, "too big for a zip archive");
+        let central_len = u32::try_from(self.central.len()).map_err(|_| too_big())?;
+        let offset = u32::try_from(self.offset).map_err(|_| too_big())?;
+        self.writer.write_all(&self.central)?;
+        self.writer.write_all(&0x0605_4b50u32.to_le_bytes())?;
+        // this disk and the disk the central directory starts on
+        self.writer.write_all(&[0; 4])?;
+        self.writer.write_all(&self.count.to_le_bytes())?;
+        self.writer.write_all(&self.count.to_le_bytes())?;
+        self.writer.write_all(&central_len.to_le_bytes())?;
+        self.writer.write_all(&offset.to_le_bytes())?;
+        // comment length
+        self.writer.write_all(&0u16.to_le_bytes())?;
+        Ok(self.writer)
+    }
+}
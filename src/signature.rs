@@ -0,0 +1,235 @@
+use std::path::Path;
+
+use base64::Engine;
+use sha1::{Digest, Sha1};
+
+use crate::{ModHeader, TModError};
+
+// what PKCS#1 v1.5 puts in front of a SHA1 digest before signing it
+const SHA1_DIGEST_INFO: &[u8] = &[
+    0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00, 0x04, 0x14,
+];
+
+// the key tModLoader's mod browser signs published mods with, from
+// `ModLoader.modBrowserPublicKey` in its source. `--check-signature` checks
+// against it unless `--pubkey` gives another one.
+pub const MOD_BROWSER_KEY: &str = "<RSAKeyValue><Modulus>oCZObovrqLjlgTXY/BKy72dRZhoaA6nWRSGuA+aAIzlvtcxkBK5uKev3DZzIj0X51dE/qgRS3OHkcrukqvrdKdsuluu0JmQXCv+m7sDYjPQ0E6rN4nYQhgfRn2kfSvKYWGefp+kqmMF9xoAq666YNGVoERPm3j99vA+6EIwKaeqLB24MrNMO/TIf9ysb0SSxoV8pC/5P/N6ViIOk3adSnrgGbXnFkNQwD0qsgOWDks8jbYyrxUFMc4rFmZ8lZKhikVR+AisQtPGUs3ruVh4EWbiZGM2NOkhOCOM4k1hsdBOyX2gUliD0yjK5tiU3LBqkxoi2t342hWAkNNb4ZxLotw==</Modulus><Exponent>AQAB</Exponent></RSAKeyValue>";
+
+// an RSA public key in the .NET `<RSAKeyValue>` XML form tModLoader keeps its
+// keys in, e.g. `<RSAKeyValue><Modulus>…</Modulus><Exponent>AQAB</Exponent></RSAKeyValue>`
+pub struct PublicKey {
+    // both big-endian
+    modulus: Vec<u8>,
+    exponent: Vec<u8>,
+}
+
+impl PublicKey {
+    pub fn load(path: &Path) -> Result<Self, TModError> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text)
+            .map_err(|reason| TModError::InvalidPublicKey(path.to_path_buf(), reason.into()))
+    }
+
+    pub fn mod_browser() -> Self {
+        Self::parse(MOD_BROWSER_KEY).expect("the mod browser key is valid")
+    }
+
+    // the key to check signatures against, if any: the one in `path`, or
+    // the mod browser's when only `--check-signature` was given
+    pub fn select(check_signature: bool, path: Option<&Path>) -> Result<Option<Self>, TModError> {
+        match path {
+            Some(path) => Self::load(path).map(Some),
+            None if check_signature => Ok(Some(Self::mod_browser())),
+            None => Ok(None),
+        }
+    }
+
+    pub fn parse(xml: &str) -> Result<Self, &'static str> {
+        let field = |tag: &str| -> Result<Vec<u8>, &'static str> {
+            let open = format!("<{}>", tag);
+            let close = format!("</{}>", tag);
+            let start = xml
+                .find(&open)
+                .ok_or("expected an <RSAKeyValue> with a <Modulus> and an <Exponent>")?
+                + open.len();
+            let len = xml[start..].find(&close).ok_or("unterminated element")?;
+            let value: String = xml[start..start + len]
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect();
+            base64::engine::general_purpose::STANDARD
+                .decode(value)
+                .map_err(|_| "invalid base64")
+        };
+        let modulus = strip_zeroes(&field("Modulus")?).to_vec();
+        let exponent = strip_zeroes(&field("Exponent")?).to_vec();
+        if modulus.len() < SHA1_DIGEST_INFO.len() + 20 + 11 || exponent.is_empty() {
+            return Err("the key is too small");
+        }
+        Ok(Self { modulus, exponent })
+    }
+
+    // checks a PKCS#1 v1.5 signature over a SHA1 digest
+    pub fn verify(&self, digest: &[u8], signature: &[u8]) -> bool {
+        let signature = strip_zeroes(signature);
+        if signature.len() > self.modulus.len() {
+            return false;
+        }
+        let modulus = BigUint::from_be(&self.modulus);
+        let signature = BigUint::from_be(signature);
+        if signature.compare(&modulus) != std::cmp::Ordering::Less {
+            return false;
+        }
+
+        let decrypted = signature.pow_mod(&self.exponent, &modulus);
+        let mut expected = vec![0xff; self.modulus.len()];
+        expected[0] = 0x00;
+        expected[1] = 0x01;
+        let suffix_len = SHA1_DIGEST_INFO.len() + digest.len();
+        let separator = expected.len() - suffix_len - 1;
+        expected[separator] = 0x00;
+        expected[separator + 1..separator + 1 + SHA1_DIGEST_INFO.len()]
+            .copy_from_slice(SHA1_DIGEST_INFO);
+        let digest_start = expected.len() - digest.len();
+        expected[digest_start..].copy_from_slice(digest);
+        decrypted.to_be(self.modulus.len()) == expected
+    }
+
+    // a short identifier for the key, for messages
+    pub fn id(&self) -> String {
+        hex::encode(&Sha1::digest(&self.modulus)[..8])
+    }
+}
+
+// tModLoader signs the 20-byte hash from the header, so a valid signature
+// only vouches for the data if the hash is checked as well
+pub fn verify_header(header: &ModHeader, key: &PublicKey) -> Result<(), TModError> {
+    if header.signature.iter().all(|&byte| byte == 0) {
+        return Err(TModError::InvalidSignature("the mod isn't signed".into()));
+    }
+    if !key.verify(&header.hash, &header.signature) {
+        return Err(TModError::InvalidSignature(format!(
+            "the signature wasn't made with key {}",
+            key.id()
+        )));
+    }
+    Ok(())
+}
+
+fn strip_zeroes(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|&byte| byte != 0)
+        .unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+// just enough unsigned bignum arithmetic for one modular exponentiation with
+// a public exponent. little-endian 32-bit limbs, no leading zero limbs.
+#[derive(Clone, PartialEq, Eq)]
+struct BigUint(Vec<u32>);
+
+impl BigUint {
+    fn from_be(bytes: &[u8]) -> Self {
+        let mut limbs: Vec<u32> = bytes
+            .rchunks(4)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .fold(0, |limb, &byte| (limb << 8) | byte as u32)
+            })
+            .collect();
+        trim(&mut limbs);
+        Self(limbs)
+    }
+
+    fn to_be(&self, len: usize) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self
+            .0
+            .iter()
+            .rev()
+            .flat_map(|limb| limb.to_be_bytes())
+            .collect();
+        let extra = bytes.len().saturating_sub(len);
+        bytes.drain(..extra);
+        let mut out = vec![0; len - bytes.len()];
+        out.extend(bytes);
+        out
+    }
+
+    fn compare(&self, other: &Self) -> std::cmp::Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        let mut product = vec![0u32; self.0.len() + other.0.len()];
+        for (i, &a) in self.0.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.0.iter().enumerate() {
+                let value = product[i + j] as u64 + a as u64 * b as u64 + carry;
+                product[i + j] = value as u32;
+                carry = value >> 32;
+            }
+            product[i + other.0.len()] = carry as u32;
+        }
+        trim(&mut product);
+        Self(product)
+    }
+
+    // binary long division, slow but short, and only done a few dozen times
+    fn rem(&self, modulus: &Self) -> Self {
+        let mut remainder = Self(Vec::new());
+        for bit in (0..self.0.len() * 32).rev() {
+            remainder.shift_left_one((self.0[bit / 32] >> (bit % 32)) & 1);
+            if remainder.compare(modulus) != std::cmp::Ordering::Less {
+                remainder.sub(modulus);
+            }
+        }
+        remainder
+    }
+
+    fn shift_left_one(&mut self, low_bit: u32) {
+        let mut carry = low_bit;
+        for limb in &mut self.0 {
+            let next = *limb >> 31;
+            *limb = (*limb << 1) | carry;
+            carry = next;
+        }
+        if carry != 0 {
+            self.0.push(carry);
+        }
+    }
+
+    // self -= other, with self >= other
+    fn sub(&mut self, other: &Self) {
+        let mut borrow = 0i64;
+        for (i, limb) in self.0.iter_mut().enumerate() {
+            let value = *limb as i64 - other.0.get(i).copied().unwrap_or(0) as i64 - borrow;
+            borrow = (value < 0) as i64;
+            *limb = value.rem_euclid(1 << 32) as u32;
+        }
+        trim(&mut self.0);
+    }
+
+    fn pow_mod(&self, exponent: &[u8], modulus: &Self) -> Self {
+        let mut result = Self(vec![1]);
+        for byte in exponent {
+            for bit in (0..8).rev() {
+                result = result.mul(&result).rem(modulus);
+                if (byte >> bit) & 1 == 1 {
+                    result = result.mul(self).rem(modulus);
+                }
+            }
+        }
+        result
+    }
+}
+
+fn trim(limbs: &mut Vec<u32>) {
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    path::{Path, PathBuf},
+};
+#[cfg(feature = "archives")]
+use std::{fs::File, io::Write};
+
+use log::{trace, warn};
+
+use crate::cancel::{self, CancellationToken};
+use crate::names;
+use crate::route::{self, Route};
+use crate::{retry, ModFile, TModError};
+
+// where extracted entries go. extraction only ever talks to this, so a new
+// destination is one more implementation.
+pub trait OutputSink {
+    // stores one entry under its name in the archive, returning where it
+    // ended up (for `--emit-entries`)
+    fn write_entry(&mut self, file: &ModFile, data: &[u8]) -> Result<PathBuf, TModError>;
+
+    // the same for contents read as a stream of `file.uncompressed_len`
+    // bytes (`--low-memory`). sinks that can write as they read override
+    // this, the rest get everything at once.
+    fn write_stream(&mut self, file: &ModFile, data: &mut dyn Read) -> Result<PathBuf, TModError> {
+        let mut buf = Vec::new();
+        data.read_to_end(&mut buf)?;
+        self.write_entry(file, &buf)
+    }
+
+    // called once after the last entry, nothing is guaranteed to be complete
+    // before this returns
+    fn finish(&mut self) -> Result<(), TModError> {
+        Ok(())
+    }
+}
+
+// what to do about a file that is already where an entry would be written.
+// settled for every entry before anything is written, so skipped entries
+// aren't even decompressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Existing {
+    #[default]
+    Overwrite,
+    // leave it, and the entry unwritten
+    Skip,
+    Fail,
+    // ask for each one, on a terminal
+    Prompt,
+}
+
+// plain files below one or more output roots
+pub struct DirSink<'a> {
+    pub out_dir: Option<&'a Path>,
+    pub routes: &'a [Route],
+    pub force: bool,
+    pub allow_unsafe_paths: bool,
+    pub retries: u32,
+    pub cancel: CancellationToken,
+}
+
+impl DirSink<'_> {
+    // where the entry goes, with its parent directory created and nothing in
+    // the way that shouldn't be replaced
+    fn prepare(&self, file: &ModFile) -> Result<PathBuf, TModError> {
+        let root = route::destination(self.routes, &file.name, self.out_dir)
+            .ok_or_else(|| TModError::NoRoute(file.name.clone()))?;
+        // the pre-flight check already rejects these, but library users and
+        // converted names don't go through it
+        if let Some(reason) = names::unsafe_reason(&file.name) {
+            if !self.allow_unsafe_paths {
+                return Err(TModError::UnsafePath(file.name.clone(), reason));
+            }
+        }
+        let file_path = root.join(&file.name);
+        if let Some(parent) = file_path.parent() {
+            trace!("checking if file's parent directory exists: {:?}", parent);
+            if !parent.try_exists()? {
+                trace!("creating parent directory: {:?}", parent);
+                retry::with_retries(&file.name, self.retries, || std::fs::create_dir_all(parent))?;
+            }
+        }
+        // an existing link in the output (planted, or left over from an
+        // earlier extraction) may point outside the output directory, so
+        // don't silently replace it
+        if let Ok(metadata) = std::fs::symlink_metadata(&file_path) {
+            if metadata.file_type().is_symlink() {
+                if !self.force {
+                    return Err(TModError::SymlinkedOutput(file_path));
+                }
+                warn!("replacing symlink: {:?}", file_path);
+                std::fs::remove_file(&file_path)?;
+            }
+        }
+        Ok(file_path)
+    }
+
+    fn write_error(&self, e: std::io::Error) -> TModError {
+        match self.cancel.is_cancelled() {
+            true => TModError::Cancelled,
+            false => e.into(),
+        }
+    }
+}
+
+impl OutputSink for DirSink<'_> {
+    fn write_entry(&mut self, file: &ModFile, data: &[u8]) -> Result<PathBuf, TModError> {
+        let file_path = self.prepare(file)?;
+        trace!("writing file: {:?}", file_path);
+        retry::with_retries(&file.name, self.retries, || {
+            cancel::write_atomically(&file_path, data, &self.cancel)
+        })
+        .map_err(|e| self.write_error(e))?;
+        Ok(file_path)
+    }
+
+    // a stream can't be read twice, so there are no retries
+    fn write_stream(&mut self, file: &ModFile, data: &mut dyn Read) -> Result<PathBuf, TModError> {
+        let file_path = self.prepare(file)?;
+        trace!("streaming file: {:?}", file_path);
+        cancel::write_atomically_from(&file_path, data, file.uncompressed_len as u64, &self.cancel)
+            .map_err(|e| self.write_error(e))?;
+        Ok(file_path)
+    }
+}
+
+// keeps every entry in memory, for embedding the extractor
+#[derive(Default)]
+pub struct MemorySink {
+    pub files: BTreeMap<String, Vec<u8>>,
+}
+
+impl OutputSink for MemorySink {
+    fn write_entry(&mut self, file: &ModFile, data: &[u8]) -> Result<PathBuf, TModError> {
+        self.files.insert(file.name.clone(), data.to_vec());
+        Ok(PathBuf::from(&file.name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    // `--to-tar out.tar.gz` is compressed, like `tar czf` would
+    pub fn tar_for(path: &Path) -> Self {
+        let name = path.to_string_lossy();
+        if name.ends_with(".gz") || name.ends_with(".tgz") {
+            ArchiveFormat::TarGz
+        } else {
+            ArchiveFormat::Tar
+        }
+    }
+}
+
+// `--to-tar -` streams the archive to stdout instead of a file
+pub fn is_stdout(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+// an archive is built under a temporary name and only renamed into place by
+// `finish`, so a failed or cancelled extraction doesn't leave a truncated one.
+// the temporary file is created like the partial files of a directory
+// extraction, never through a symlink.
+#[cfg(feature = "archives")]
+struct Partial {
+    partial: PathBuf,
+    path: PathBuf,
+    done: bool,
+}
+
+#[cfg(feature = "archives")]
+impl Partial {
+    fn create(path: &Path) -> Result<(Self, File), TModError> {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let partial = path.with_file_name(format!(".{}.partial", file_name));
+        let file = cancel::create_partial(&partial)?;
+        Ok((
+            Self {
+                partial,
+                path: path.to_path_buf(),
+                done: false,
+            },
+            file,
+        ))
+    }
+
+    fn commit(&mut self) -> Result<(), TModError> {
+        std::fs::rename(&self.partial, &self.path)?;
+        self.done = true;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "archives")]
+impl Drop for Partial {
+    fn drop(&mut self) {
+        if !self.done {
+            trace!("removing partial archive: {:?}", self.partial);
+            let _ = std::fs::remove_file(&self.partial);
+        }
+    }
+}
+
+#[cfg(feature = "archives")]
+pub fn archive_sink(format: ArchiveFormat, path: &Path) -> Result<Box<dyn OutputSink>, TModError> {
+    use flate2::{write::GzEncoder, Compression};
+
+    if is_stdout(path) {
+        // a zip's central directory points back into the file, so it can't
+        // be written as a stream
+        let out: Box<dyn TarOutput> = match format {
+            ArchiveFormat::Zip => {
+                return Err(TModError::ConflictingArguments("stdout", "--to-zip"))
+            }
+            ArchiveFormat::Tar => Box::new(std::io::BufWriter::new(std::io::stdout())),
+            ArchiveFormat::TarGz => Box::new(GzEncoder::new(
+                std::io::BufWriter::new(std::io::stdout()),
+                Compression::default(),
+            )),
+        };
+        return Ok(Box::new(TarSink {
+            builder: tar::Builder::new(out),
+            partial: None,
+        }));
+    }
+
+    let (partial, file) = Partial::create(path)?;
+    Ok(match format {
+        ArchiveFormat::Zip => Box::new(ZipSink {
+            writer: zip::ZipWriter::new(file),
+            partial,
+        }),
+        ArchiveFormat::Tar => Box::new(TarSink {
+            builder: tar::Builder::new(Box::new(file)),
+            partial: Some(partial),
+        }),
+        ArchiveFormat::TarGz => Box::new(TarSink {
+            builder: tar::Builder::new(Box::new(GzEncoder::new(file, Compression::default()))),
+            partial: Some(partial),
+        }),
+    })
+}
+
+#[cfg(not(feature = "archives"))]
+pub fn archive_sink(
+    _format: ArchiveFormat,
+    _path: &Path,
+) -> Result<Box<dyn OutputSink>, TModError> {
+    Err(TModError::FeatureDisabled("archives"))
+}
+
+#[cfg(feature = "archives")]
+struct ZipSink {
+    writer: zip::ZipWriter<File>,
+    partial: Partial,
+}
+
+#[cfg(feature = "archives")]
+impl ZipSink {
+    fn start_file(&mut self, file: &ModFile) -> Result<(), TModError> {
+        self.writer
+            .start_file(file.name.as_str(), zip_options(file))
+            .map_err(zip_error)
+    }
+}
+
+// entries tModLoader left uncompressed are usually already-compressed media,
+// deflating them again is wasted effort
+#[cfg(feature = "archives")]
+pub(crate) fn zip_options(file: &ModFile) -> zip::write::FileOptions {
+    let method = if file.compressed_len == file.uncompressed_len {
+        zip::CompressionMethod::Stored
+    } else {
+        zip::CompressionMethod::Deflated
+    };
+    zip::write::FileOptions::default().compression_method(method)
+}
+
+#[cfg(feature = "archives")]
+impl OutputSink for ZipSink {
+    fn write_entry(&mut self, file: &ModFile, data: &[u8]) -> Result<PathBuf, TModError> {
+        self.start_file(file)?;
+        self.writer.write_all(data)?;
+        Ok(self.partial.path.join(&file.name))
+    }
+
+    fn write_stream(&mut self, file: &ModFile, data: &mut dyn Read) -> Result<PathBuf, TModError> {
+        self.start_file(file)?;
+        std::io::copy(data, &mut self.writer)?;
+        Ok(self.partial.path.join(&file.name))
+    }
+
+    fn finish(&mut self) -> Result<(), TModError> {
+        self.writer.finish().map_err(zip_error)?;
+        self.partial.commit()
+    }
+}
+
+#[cfg(feature = "archives")]
+pub(crate) fn zip_error(e: zip::result::ZipError) -> TModError {
+    match e {
+        zip::result::ZipError::Io(e) => TModError::IoError(e),
+        e => TModError::IoError(std::io::Error::new(std::io::ErrorKind::Other, e)),
+    }
+}
+
+// where a tar archive is written, which may need finishing on top of the
+// archive itself
+#[cfg(feature = "archives")]
+trait TarOutput: Write {
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.flush()
+    }
+}
+
+#[cfg(feature = "archives")]
+impl TarOutput for File {}
+
+#[cfg(feature = "archives")]
+impl TarOutput for std::io::BufWriter<std::io::Stdout> {}
+
+#[cfg(feature = "archives")]
+impl<W: Write> TarOutput for flate2::write::GzEncoder<W> {
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.try_finish()?;
+        self.get_mut().flush()
+    }
+}
+
+#[cfg(feature = "archives")]
+struct TarSink {
+    builder: tar::Builder<Box<dyn TarOutput>>,
+    // `None` when streaming to stdout
+    partial: Option<Partial>,
+}
+
+#[cfg(feature = "archives")]
+impl TarSink {
+    fn path(&self, name: &str) -> PathBuf {
+        match &self.partial {
+            Some(partial) => partial.path.join(name),
+            None => Path::new("-").join(name),
+        }
+    }
+
+    fn header(len: u64) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(len);
+        header.set_mode(0o644);
+        header.set_mtime(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|time| time.as_secs())
+                .unwrap_or(0),
+        );
+        header
+    }
+}
+
+#[cfg(feature = "archives")]
+impl OutputSink for TarSink {
+    fn write_entry(&mut self, file: &ModFile, data: &[u8]) -> Result<PathBuf, TModError> {
+        let mut header = Self::header(data.len() as u64);
+        self.builder.append_data(&mut header, &file.name, data)?;
+        Ok(self.path(&file.name))
+    }
+
+    // the size goes in the header in front of the contents, so it has to be
+    // the one from the entry table
+    fn write_stream(&mut self, file: &ModFile, data: &mut dyn Read) -> Result<PathBuf, TModError> {
+        let mut header = Self::header(file.uncompressed_len as u64);
+        self.builder.append_data(&mut header, &file.name, data)?;
+        Ok(self.path(&file.name))
+    }
+
+    fn finish(&mut self) -> Result<(), TModError> {
+        self.builder.finish()?;
+        self.builder.get_mut().finish()?;
+        match &mut self.partial {
+            Some(partial) => partial.commit(),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,526 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use binary_rw::{ReadStream, SeekStream};
+use is_terminal::IsTerminal;
+use log::{debug, trace};
+
+use crate::preflight::SizeLimits;
+use crate::{legacy, TModError};
+
+// random access to the bytes of a mod, wherever they come from. the parsing
+// code only ever reads through a `SourceStream`, so it doesn't care whether
+// the mod is a file, a mapping, a buffer or a remote url.
+pub trait Source {
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // reads up to `buf.len()` bytes starting at `offset`, returning how many
+    // were read (0 at the end)
+    fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> io::Result<usize>;
+
+    // the whole mod, for sources that already have it in memory
+    fn bytes(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+// reads and seeks directly on an open file
+pub struct FileSource {
+    file: File,
+    len: usize,
+}
+
+impl FileSource {
+    pub fn new(file: File) -> io::Result<Self> {
+        let len = file.metadata()?.len() as usize;
+        Ok(Self { file, len })
+    }
+}
+
+impl Source for FileSource {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.seek(SeekFrom::Start(offset as u64))?;
+        self.file.read(buf)
+    }
+}
+
+// a mod that is already in memory: a buffer, a memory mapping, or stdin once
+// it has been read to the end (unless it was spooled to a file)
+pub struct BytesSource<T>(pub T);
+
+impl<T: AsRef<[u8]>> Source for BytesSource<T> {
+    fn len(&self) -> usize {
+        self.0.as_ref().len()
+    }
+
+    fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.0.as_ref();
+        let start = offset.min(data.len());
+        let count = buf.len().min(data.len() - start);
+        buf[..count].copy_from_slice(&data[start..start + count]);
+        Ok(count)
+    }
+
+    fn bytes(&self) -> Option<&[u8]> {
+        Some(self.0.as_ref())
+    }
+}
+
+// adapts any source to the stream traits binary_rw reads from
+pub struct SourceStream {
+    source: Box<dyn Source>,
+    position: usize,
+}
+
+impl SourceStream {
+    pub fn new(source: impl Source + 'static) -> Self {
+        Self {
+            source: Box::new(source),
+            position: 0,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        Self::new(BytesSource(data))
+    }
+
+    // `len` bytes at `offset` borrowed straight from a buffer or mapping,
+    // `None` if the source isn't in memory or the range is out of bounds
+    pub fn slice(&self, offset: usize, len: usize) -> Option<&[u8]> {
+        self.source.bytes()?.get(offset..offset.checked_add(len)?)
+    }
+}
+
+// fills the whole buffer unless the source ends first: binary_rw reads with a
+// single `read` and pads whatever that didn't fill with zeroes
+impl Read for SourceStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            let read = self.source.read_at(self.position, &mut buf[filled..])?;
+            if read == 0 {
+                break;
+            }
+            self.position += read;
+            filled += read;
+        }
+        Ok(filled)
+    }
+}
+
+impl SeekStream for SourceStream {
+    fn seek(&mut self, to: usize) -> binary_rw::Result<usize> {
+        self.position = to;
+        Ok(to)
+    }
+
+    fn tell(&mut self) -> binary_rw::Result<usize> {
+        Ok(self.position)
+    }
+
+    fn len(&self) -> binary_rw::Result<usize> {
+        Ok(self.source.len())
+    }
+}
+
+impl ReadStream for SourceStream {}
+
+pub fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+// opens the input the way it was given on the command line: `-` is stdin, an
+// http(s) url is fetched on demand and anything else is a file path. legacy
+// archives come back converted to the current layout.
+pub fn open(input: &str) -> Result<SourceStream, TModError> {
+    open_limited(input, &SizeLimits::default())
+}
+
+// like `open`, with `sizes` capping what a legacy archive may inflate to
+pub fn open_limited(input: &str, sizes: &SizeLimits) -> Result<SourceStream, TModError> {
+    legacy::upgrade(open_raw(input)?, sizes)
+}
+
+// like `open`, but a legacy archive is returned as it is
+pub(crate) fn open_raw(input: &str) -> Result<SourceStream, TModError> {
+    if input == "-" {
+        return open_stdin();
+    }
+    if is_url(input) {
+        return open_url(input);
+    }
+    Ok(SourceStream::new(FileSource::new(File::open(input)?)?))
+}
+
+// stdin can't seek, so it is read to the end first: into memory, or once it
+// grows past this, into a temporary file
+const STDIN_MEMORY_LIMIT: u64 = 256 * 1024 * 1024;
+
+fn open_stdin() -> Result<SourceStream, TModError> {
+    let stdin = io::stdin();
+    // nothing was piped in, waiting for the user to type a mod won't help
+    if stdin.is_terminal() {
+        return Err(TModError::NoInputFile);
+    }
+    trace!("reading stdin");
+    let mut stdin = stdin.lock();
+    let mut data = Vec::new();
+    (&mut stdin)
+        .take(STDIN_MEMORY_LIMIT)
+        .read_to_end(&mut data)?;
+    if (data.len() as u64) < STDIN_MEMORY_LIMIT {
+        return Ok(SourceStream::from_bytes(data));
+    }
+
+    debug!(
+        "stdin is bigger than {} bytes, spooling it to a temporary file",
+        STDIN_MEMORY_LIMIT
+    );
+    let mut file = spool_file()?;
+    file.write_all(&data)?;
+    drop(data);
+    io::copy(&mut stdin, &mut file)?;
+    Ok(SourceStream::new(FileSource::new(file)?))
+}
+
+// a temporary file that is gone again once it is closed, however the run
+// ends
+fn spool_file() -> io::Result<File> {
+    let path = std::env::temp_dir().join(format!("tmod-stdin-{}", std::process::id()));
+    trace!("spooling stdin to {:?}", path);
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create_new(true);
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+
+        const FILE_FLAG_DELETE_ON_CLOSE: u32 = 0x0400_0000;
+        options.custom_flags(FILE_FLAG_DELETE_ON_CLOSE);
+    }
+    let file = options.open(&path)?;
+    // the open file keeps its data, the name isn't needed anymore
+    #[cfg(unix)]
+    std::fs::remove_file(&path)?;
+    Ok(file)
+}
+
+// like `open`, but keeps a copy of a remote mod in `cache_dir`, see
+// `http::open_cached`. local inputs are opened as usual.
+pub fn open_cached(
+    input: &str,
+    cache_dir: &Path,
+    sizes: &SizeLimits,
+) -> Result<SourceStream, TModError> {
+    if !is_url(input) {
+        return open_limited(input, sizes);
+    }
+    open_url_cached(input, cache_dir, sizes)
+}
+
+#[cfg(feature = "http")]
+fn open_url_cached(
+    url: &str,
+    cache_dir: &Path,
+    sizes: &SizeLimits,
+) -> Result<SourceStream, TModError> {
+    legacy::upgrade(http::open_cached(url, cache_dir)?, sizes)
+}
+
+#[cfg(not(feature = "http"))]
+fn open_url_cached(
+    _url: &str,
+    _cache_dir: &Path,
+    _sizes: &SizeLimits,
+) -> Result<SourceStream, TModError> {
+    Err(TModError::FeatureDisabled("http"))
+}
+
+// like `open`, but memory-maps files instead of reading them. builds
+// without the `mmap` feature read them as usual.
+#[cfg(feature = "mmap")]
+pub fn open_mapped(input: &str, sizes: &SizeLimits) -> Result<SourceStream, TModError> {
+    if input == "-" || is_url(input) {
+        return open_limited(input, sizes);
+    }
+    let file = File::open(input)?;
+    // the mapping is only valid as long as nobody truncates the file under us,
+    // which is the same assumption reading it in place makes
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    legacy::upgrade(SourceStream::new(BytesSource(map)), sizes)
+}
+
+#[cfg(not(feature = "mmap"))]
+pub fn open_mapped(input: &str, sizes: &SizeLimits) -> Result<SourceStream, TModError> {
+    open_limited(input, sizes)
+}
+
+#[cfg(feature = "http")]
+fn open_url(url: &str) -> Result<SourceStream, TModError> {
+    Ok(SourceStream::new(http::HttpSource::open(url)?))
+}
+
+#[cfg(not(feature = "http"))]
+fn open_url(_url: &str) -> Result<SourceStream, TModError> {
+    Err(TModError::FeatureDisabled("http"))
+}
+
+#[cfg(feature = "http")]
+mod http {
+    use std::{
+        collections::VecDeque,
+        fs::File,
+        io::{self, Read, Seek, SeekFrom, Write},
+        path::Path,
+    };
+
+    use binary_rw::{BinaryReader, SeekStream};
+    use log::{debug, trace};
+    use sha1::{Digest, Sha1};
+
+    use super::{BytesSource, FileSource, Source, SourceStream};
+    use crate::progress::Download;
+    use crate::{read_csharp_string, TModError, TMOD_HEADER};
+
+    // ranges are requested in blocks this size, and the most recent few are
+    // kept around since the header and entry table are read in small pieces
+    const BLOCK_SIZE: usize = 256 * 1024;
+    const CACHED_BLOCKS: usize = 64;
+
+    // a remote mod read with http range requests, so listing or viewing a big
+    // mod only downloads the parts that are actually needed
+    pub enum HttpSource {
+        Ranged {
+            url: String,
+            len: usize,
+            blocks: VecDeque<(usize, Vec<u8>)>,
+        },
+        // the server doesn't support ranges, so the whole mod was downloaded
+        Downloaded(BytesSource<Vec<u8>>),
+    }
+
+    fn http_error(url: &str, e: impl ToString) -> TModError {
+        TModError::HttpError(url.to_string(), e.to_string())
+    }
+
+    impl HttpSource {
+        pub fn open(url: &str) -> Result<Self, TModError> {
+            trace!("requesting headers: {}", url);
+            let response = ureq::head(url).call().map_err(|e| http_error(url, e))?;
+            let len = response
+                .header("Content-Length")
+                .and_then(|len| len.parse().ok());
+            let ranged = response.header("Accept-Ranges") == Some("bytes");
+
+            match len {
+                Some(len) if ranged => Ok(HttpSource::Ranged {
+                    url: url.to_string(),
+                    len,
+                    blocks: VecDeque::new(),
+                }),
+                _ => {
+                    trace!("server doesn't support ranges, downloading: {}", url);
+                    let mut data = Vec::new();
+                    download(url, &mut data)?;
+                    Ok(HttpSource::Downloaded(BytesSource(data)))
+                }
+            }
+        }
+    }
+
+    // the whole mod, with a progress bar when the server says how big it is
+    fn download(url: &str, out: &mut impl Write) -> Result<u64, TModError> {
+        let response = ureq::get(url).call().map_err(|e| http_error(url, e))?;
+        let len = response
+            .header("Content-Length")
+            .and_then(|len| len.parse().ok());
+        let mut progress = Download::start(len);
+        let mut body = response.into_reader();
+        let mut buf = vec![0; 64 * 1024];
+        let mut total = 0;
+        loop {
+            let read = match body.read(&mut buf) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            out.write_all(&buf[..read])?;
+            total += read as u64;
+            progress.advance(read as u64);
+        }
+        progress.finish();
+        Ok(total)
+    }
+
+    // a remote mod read through a local copy in `cache_dir`, named after the
+    // hash in its header, so only the header is requested when the copy is
+    // there already. a new copy is checked against the hash before it is
+    // kept, so a broken download isn't reused. archives whose header can't
+    // be read this way are read remotely as usual.
+    pub fn open_cached(url: &str, cache_dir: &Path) -> Result<SourceStream, TModError> {
+        let mut stream = SourceStream::new(HttpSource::open(url)?);
+        let header = {
+            let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+            read_hash(&mut reader)
+        };
+        let (hash, data_offset, data_len) = match header {
+            Ok(header) => header,
+            Err(e) => {
+                debug!("not caching {}: {}", url, e);
+                stream.seek(0)?;
+                return Ok(stream);
+            }
+        };
+
+        let key = hex::encode(&hash);
+        let path = cache_dir.join(format!("{}.tmod", key));
+        if path.is_file() {
+            debug!("using the cached copy of {}: {:?}", url, path);
+            return Ok(SourceStream::new(FileSource::new(File::open(&path)?)?));
+        }
+
+        std::fs::create_dir_all(cache_dir)?;
+        let partial = cache_dir.join(format!("{}.{}.part", key, std::process::id()));
+        trace!("caching {} in {:?}", url, partial);
+        let len = stream.len()?;
+        let result = (|| {
+            let mut file = File::create(&partial)?;
+            match stream.slice(0, len) {
+                // the server doesn't do ranges, so it is all here already
+                Some(data) => file.write_all(data)?,
+                None => {
+                    download(url, &mut file)?;
+                }
+            }
+            drop(file);
+
+            let mut file = File::open(&partial)?;
+            file.seek(SeekFrom::Start(data_offset as u64))?;
+            let mut hasher = Sha1::new();
+            io::copy(&mut file.take(data_len as u64), &mut hasher)?;
+            let computed = hasher.finalize();
+            if computed[..] != hash[..] {
+                return Err(TModError::HashMismatch(
+                    hex::encode(&hash),
+                    hex::encode(computed),
+                ));
+            }
+            std::fs::rename(&partial, &path)?;
+            Ok(())
+        })();
+        if result.is_err() {
+            let _ = std::fs::remove_file(&partial);
+        }
+        result?;
+        debug!("cached {} as {:?}", url, path);
+        Ok(SourceStream::new(FileSource::new(File::open(&path)?)?))
+    }
+
+    // the hash, and where the data region it covers starts and how long it
+    // is. that part of the header is the same in legacy archives.
+    fn read_hash(reader: &mut BinaryReader) -> Result<(Vec<u8>, usize, usize), TModError> {
+        let magic = reader.read_bytes(TMOD_HEADER.len())?;
+        if magic != TMOD_HEADER {
+            return Err(TModError::InvalidHeader(magic, TMOD_HEADER));
+        }
+        read_csharp_string(reader)?;
+        let hash = reader.read_bytes(20)?;
+        reader.read_bytes(256)?;
+        let data_len = reader.read_u32()? as usize;
+        Ok((hash, reader.tell()?, data_len))
+    }
+
+    fn fetch(url: &str, start: usize, end: usize) -> io::Result<Vec<u8>> {
+        trace!("requesting bytes {}..{}: {}", start, end, url);
+        let response = ureq::get(url)
+            .set("Range", &format!("bytes={}-{}", start, end - 1))
+            .call()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        if response.status() != 206 {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("expected a partial response, got {}", response.status()),
+            ));
+        }
+        let mut block = Vec::with_capacity(end - start);
+        response.into_reader().read_to_end(&mut block)?;
+        Ok(block)
+    }
+
+    fn cached_block<'a>(
+        url: &str,
+        len: usize,
+        blocks: &'a mut VecDeque<(usize, Vec<u8>)>,
+        start: usize,
+    ) -> io::Result<&'a [u8]> {
+        let index = match blocks.iter().position(|(at, _)| *at == start) {
+            Some(index) => index,
+            None => {
+                let block = fetch(url, start, (start + BLOCK_SIZE).min(len))?;
+                if blocks.len() == CACHED_BLOCKS {
+                    blocks.pop_front();
+                }
+                blocks.push_back((start, block));
+                blocks.len() - 1
+            }
+        };
+        Ok(&blocks[index].1)
+    }
+
+    impl Source for HttpSource {
+        fn len(&self) -> usize {
+            match self {
+                HttpSource::Ranged { len, .. } => *len,
+                HttpSource::Downloaded(data) => data.len(),
+            }
+        }
+
+        fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> io::Result<usize> {
+            let (url, len, blocks) = match self {
+                HttpSource::Ranged { url, len, blocks } => (url, *len, blocks),
+                HttpSource::Downloaded(data) => return data.read_at(offset, buf),
+            };
+            if offset >= len {
+                return Ok(0);
+            }
+
+            let end = (offset + buf.len()).min(len);
+            // big reads are entry data, which is read once, so it isn't
+            // worth pushing everything else out of the cache for it
+            if end - offset > BLOCK_SIZE {
+                let data = fetch(url, offset, end)?;
+                let count = data.len().min(buf.len());
+                buf[..count].copy_from_slice(&data[..count]);
+                return Ok(count);
+            }
+
+            // binary_rw expects a read to fill the buffer, so keep going
+            // when it spans two blocks
+            let mut count = 0;
+            while offset + count < end {
+                let at = offset + count;
+                let block = cached_block(url, len, blocks, at / BLOCK_SIZE * BLOCK_SIZE)?;
+                let from = at % BLOCK_SIZE;
+                if from >= block.len() {
+                    break;
+                }
+                let copied = (end - at).min(block.len() - from);
+                buf[count..count + copied].copy_from_slice(&block[from..from + copied]);
+                count += copied;
+            }
+            Ok(count)
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::{io::Write, path::Path};
+
+use log::trace;
+
+use crate::{ModFile, TModError};
+
+// where an archive entry lives in a ModSources directory, or `None` if it is
+// a build output that has no source counterpart
+pub fn source_path(name: &str, mod_name: &str) -> Option<String> {
+    // the build properties are compiled from build.txt
+    if name == "Info" {
+        return Some("build.txt".to_string());
+    }
+
+    // the mod's own assembly and its debug symbols / docs are produced by the
+    // build, whereas anything under lib/ is a referenced assembly
+    for ext in ["dll", "pdb", "xml"] {
+        if name == format!("{}.{}", mod_name, ext) {
+            return None;
+        }
+    }
+
+    // textures are stored as raw images but authored as PNGs
+    if let Some(stem) = name.strip_suffix(".rawimg") {
+        return Some(format!("{}.png", stem));
+    }
+
+    Some(name.to_string())
+}
+
+// writes one `<archive path>\t<source path>` line per entry, leaving the source
+// path empty for build outputs. names are written exactly as stored so case is
+// preserved.
+pub fn write_source_map(
+    path: impl AsRef<Path>,
+    files: &[ModFile],
+    mod_name: &str,
+) -> Result<(), TModError> {
+    trace!("writing source map: {:?}", path.as_ref());
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    for file in files {
+        let source = source_path(&file.name, mod_name).unwrap_or_default();
+        writeln!(out, "{}\t{}", file.name, source)?;
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,247 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use log::warn;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::names::extension;
+use crate::output::{self, OutputFormat};
+use crate::{format_size, TModError, TModReader};
+
+// how many of the biggest new files each release lists
+const NEW_FILES_SHOWN: usize = 5;
+
+#[derive(Default)]
+pub struct StatsOptions {
+    // compare several versions of the same mod instead of describing one
+    pub history: bool,
+    pub format: OutputFormat,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ExtensionStats {
+    // `None` for files without one
+    pub extension: Option<String>,
+    pub files: usize,
+    pub size: u64,
+    // growth since the previous release, only in a history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct NewFile {
+    pub name: String,
+    pub size: u64,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ModStats {
+    pub mod_name: String,
+    pub mod_version: String,
+    pub fingerprint: String,
+    pub files: usize,
+    pub size: u64,
+    pub compressed_size: u64,
+    // biggest first
+    pub extensions: Vec<ExtensionStats>,
+    // growth since the previous release, only in a history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change: Option<i64>,
+    // entries the previous release didn't have, biggest first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub new_files: Vec<NewFile>,
+}
+
+// sizes of a mod by extension, or with `--history`, of every given release of
+// one mod in version order, with what each release added
+pub fn stats(paths: Vec<String>, options: &StatsOptions) -> Result<(), TModError> {
+    let mut releases = Vec::new();
+    for path in &paths {
+        let reader = TModReader::open(path)?;
+        let entries: BTreeMap<String, u64> = reader
+            .entries()
+            .iter()
+            .map(|file| (file.name.clone(), file.uncompressed_len as u64))
+            .collect();
+        let compressed_size = reader
+            .entries()
+            .iter()
+            .map(|file| file.compressed_len as u64)
+            .sum();
+        releases.push((reader, entries, compressed_size));
+    }
+
+    if !options.history {
+        let (reader, entries, compressed_size) = releases.first().ok_or(TModError::NoInputFile)?;
+        let stats = describe(reader, entries, *compressed_size, None);
+        if options.format == OutputFormat::Text {
+            print_stats(&stats);
+        } else {
+            println!("{}", output::render(options.format, "stats", &stats)?);
+        }
+        return Ok(());
+    }
+
+    let names: BTreeSet<&str> = releases.iter().map(|(reader, ..)| reader.name()).collect();
+    if names.len() > 1 {
+        warn!(
+            "comparing different mods: {}",
+            names.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+    releases.sort_by_cached_key(|(reader, ..)| version_key(reader.version()));
+
+    let mut history = Vec::new();
+    let mut previous: Option<&BTreeMap<String, u64>> = None;
+    for (reader, entries, compressed_size) in &releases {
+        history.push(describe(reader, entries, *compressed_size, previous));
+        previous = Some(entries);
+    }
+
+    if options.format == OutputFormat::Text {
+        for stats in &history {
+            print_stats(stats);
+            println!();
+        }
+        if let (Some(first), Some(last)) = (history.first(), history.last()) {
+            println!(
+                "{} -> {}: {} -> {}",
+                first.mod_version,
+                last.mod_version,
+                format_size(first.size),
+                format_size(last.size)
+            );
+        }
+    } else {
+        println!("{}", output::render(options.format, "stats", &history)?);
+    }
+    Ok(())
+}
+
+fn describe(
+    reader: &TModReader,
+    entries: &BTreeMap<String, u64>,
+    compressed_size: u64,
+    previous: Option<&BTreeMap<String, u64>>,
+) -> ModStats {
+    let totals = |entries: &BTreeMap<String, u64>| {
+        let mut totals: BTreeMap<Option<String>, (usize, u64)> = BTreeMap::new();
+        for (name, size) in entries {
+            let total = totals.entry(extension(name)).or_default();
+            total.0 += 1;
+            total.1 += size;
+        }
+        totals
+    };
+    let current = totals(entries);
+    let before = previous.map(totals);
+
+    let mut extensions: Vec<ExtensionStats> = current
+        .iter()
+        .map(|(ext, &(files, size))| ExtensionStats {
+            extension: ext.clone(),
+            files,
+            size,
+            change: before
+                .as_ref()
+                .map(|before| size as i64 - before.get(ext).map_or(0, |&(_, size)| size as i64)),
+        })
+        .collect();
+    // extensions that are gone entirely still shrank the mod
+    if let Some(before) = &before {
+        for (ext, &(_, size)) in before {
+            if !current.contains_key(ext) {
+                extensions.push(ExtensionStats {
+                    extension: ext.clone(),
+                    files: 0,
+                    size: 0,
+                    change: Some(-(size as i64)),
+                });
+            }
+        }
+    }
+    extensions.sort_by(|a, b| b.size.cmp(&a.size).then(a.extension.cmp(&b.extension)));
+
+    let size = entries.values().sum();
+    let mut new_files: Vec<NewFile> = match previous {
+        Some(previous) => entries
+            .iter()
+            .filter(|(name, _)| !previous.contains_key(*name))
+            .map(|(name, &size)| NewFile {
+                name: name.clone(),
+                size,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    new_files.sort_by(|a, b| b.size.cmp(&a.size).then(a.name.cmp(&b.name)));
+    new_files.truncate(NEW_FILES_SHOWN);
+
+    ModStats {
+        mod_name: reader.name().to_string(),
+        mod_version: reader.version().to_string(),
+        fingerprint: reader.fingerprint(),
+        files: entries.len(),
+        size,
+        compressed_size,
+        extensions,
+        change: previous.map(|previous| size as i64 - previous.values().sum::<u64>() as i64),
+        new_files,
+    }
+}
+
+// `1.10` sorts after `1.9`; anything that isn't a number sorts as text
+pub(crate) fn version_key(version: &str) -> Vec<(u64, String)> {
+    version
+        .split('.')
+        .map(|part| (part.parse().unwrap_or(u64::MAX), part.to_string()))
+        .collect()
+}
+
+fn format_change(change: i64) -> String {
+    let sign = if change < 0 { "-" } else { "+" };
+    format!("{}{}", sign, format_size(change.unsigned_abs()))
+}
+
+fn print_stats(stats: &ModStats) {
+    print!(
+        "{} v{} ({}): {} files, {} ({} compressed)",
+        stats.mod_name,
+        stats.mod_version,
+        stats.fingerprint,
+        stats.files,
+        format_size(stats.size),
+        format_size(stats.compressed_size)
+    );
+    match stats.change {
+        Some(change) => println!(", {}", format_change(change)),
+        None => println!(),
+    }
+    for ext in &stats.extensions {
+        let name = ext
+            .extension
+            .as_deref()
+            .map_or("(none)".to_string(), |ext| format!(".{}", ext));
+        print!(
+            "  {:<10} {:>5} files {:>10}",
+            name,
+            ext.files,
+            format_size(ext.size)
+        );
+        match ext.change {
+            Some(change) if change != 0 => println!("  {}", format_change(change)),
+            _ => println!(),
+        }
+    }
+    if !stats.new_files.is_empty() {
+        println!("  biggest new files:");
+        for file in &stats.new_files {
+            println!("    {:>10}  {}", format_size(file.size), file.name);
+        }
+    }
+}
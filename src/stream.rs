@@ -0,0 +1,183 @@
+use std::{
+    cell::RefCell,
+    io::{self, Read},
+    rc::Rc,
+};
+
+use binary_rw::{BinaryReader, ReadStream, SeekStream};
+use flate2::read::DeflateDecoder;
+use sha1::{Digest, Sha1};
+
+use crate::ModFile;
+
+// how much of an entry `--low-memory` holds at a time, before and after
+// decompressing it
+pub const LOW_MEMORY_CHUNK: usize = 64 * 1024;
+
+// a handle to the SHA1 state shared with a `HashingStream`, used to start
+// hashing at the beginning of the data region and to collect the digest once
+// everything has been read
+#[derive(Clone, Default)]
+pub struct DataHasher(Rc<RefCell<Option<Sha1>>>);
+
+impl DataHasher {
+    pub fn start(&self) {
+        *self.0.borrow_mut() = Some(Sha1::new());
+    }
+
+    pub fn finish(&self) -> Option<Vec<u8>> {
+        self.0
+            .borrow_mut()
+            .take()
+            .map(|hasher| hasher.finalize().to_vec())
+    }
+
+    fn update(&self, data: &[u8]) {
+        if let Some(hasher) = self.0.borrow_mut().as_mut() {
+            hasher.update(data);
+        }
+    }
+}
+
+// tees every byte read into the hasher (once it has been started), so the
+// hash of the data region is computed in the same pass that extracts it.
+// seeking while hashing invalidates the digest.
+pub struct HashingStream<S> {
+    inner: S,
+    hasher: DataHasher,
+}
+
+impl<S: ReadStream> HashingStream<S> {
+    pub fn new(inner: S, hasher: DataHasher) -> Self {
+        Self { inner, hasher }
+    }
+}
+
+impl<S: ReadStream> Read for HashingStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // `BinaryReader` only ever issues a single read per value, so always
+        // fill the whole buffer rather than risk a short read
+        self.inner.read_exact(buf)?;
+        self.hasher.update(buf);
+        Ok(buf.len())
+    }
+}
+
+impl<S: ReadStream> SeekStream for HashingStream<S> {
+    fn seek(&mut self, to: usize) -> binary_rw::Result<usize> {
+        self.inner.seek(to)
+    }
+
+    fn tell(&mut self) -> binary_rw::Result<usize> {
+        self.inner.tell()
+    }
+
+    fn len(&self) -> binary_rw::Result<usize> {
+        self.inner.len()
+    }
+}
+
+impl<S: ReadStream> ReadStream for HashingStream<S> {}
+
+// the stored bytes of one entry, read through the archive's reader a chunk at
+// a time
+struct Chunked<'r, 'a> {
+    reader: &'r mut BinaryReader<'a>,
+    remaining: usize,
+}
+
+impl Read for Chunked<'_, '_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.remaining).min(LOW_MEMORY_CHUNK);
+        if len == 0 {
+            return Ok(0);
+        }
+        let chunk = self
+            .reader
+            .read_bytes(len)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        buf[..len].copy_from_slice(&chunk);
+        self.remaining -= len;
+        Ok(len)
+    }
+}
+
+enum Contents<'r, 'a> {
+    Stored(Chunked<'r, 'a>),
+    Deflated(DeflateDecoder<Chunked<'r, 'a>>),
+}
+
+// one entry's extracted contents as a stream, for `--low-memory`: nothing
+// bigger than a chunk is ever buffered, whatever the size of the entry. the
+// SHA1 of the contents is computed on the way through for `--emit-entries`.
+pub struct EntryReader<'r, 'a> {
+    contents: Contents<'r, 'a>,
+    name: String,
+    expected_len: u64,
+    len: u64,
+    hasher: Sha1,
+}
+
+impl<'r, 'a> EntryReader<'r, 'a> {
+    // the reader must be at the start of the entry's data
+    pub fn new(reader: &'r mut BinaryReader<'a>, file: &ModFile) -> Self {
+        let stored = Chunked {
+            reader,
+            remaining: file.compressed_len as usize,
+        };
+        let contents = if file.compressed_len == file.uncompressed_len {
+            Contents::Stored(stored)
+        } else {
+            Contents::Deflated(DeflateDecoder::new_with_buf(
+                stored,
+                vec![0; LOW_MEMORY_CHUNK],
+            ))
+        };
+        Self {
+            contents,
+            name: file.name.clone(),
+            expected_len: file.uncompressed_len as u64,
+            len: 0,
+            hasher: Sha1::new(),
+        }
+    }
+
+    // skips whatever the decompressor didn't need, so the reader ends up at
+    // the next entry, and returns the SHA1 of the contents
+    pub fn finish(self) -> io::Result<String> {
+        let mut stored = match self.contents {
+            Contents::Stored(stored) => stored,
+            Contents::Deflated(decoder) => decoder.into_inner(),
+        };
+        io::copy(&mut stored, &mut io::sink())?;
+        Ok(hex::encode(self.hasher.finalize()))
+    }
+}
+
+impl Read for EntryReader<'_, '_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // never produce more than the table promised, however much the
+        // compressed data would inflate to. one byte past it is enough to
+        // tell it would be more.
+        let limit = buf.len().min((self.expected_len - self.len + 1) as usize);
+        let read = match &mut self.contents {
+            Contents::Stored(stored) => stored.read(&mut buf[..limit])?,
+            Contents::Deflated(decoder) => decoder.read(&mut buf[..limit])?,
+        };
+        self.len += read as u64;
+        if self.len > self.expected_len || (read == 0 && self.len != self.expected_len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} doesn't inflate to the {} bytes the entry table says",
+                    self.name, self.expected_len
+                ),
+            ));
+        }
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashSet;
+
+use binary_rw::BinaryReader;
+use log::{info, trace, warn};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::names::extension;
+use crate::output::{self, OutputFormat};
+use crate::{read_entry_table, read_file_data, read_header, source, TModError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum StringKind {
+    // from the assembly's `#US` heap
+    Literal,
+    // anything else that looked like text
+    Printable,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct FoundString {
+    pub entry: String,
+    pub kind: StringKind,
+    pub value: String,
+}
+
+pub struct StringsOptions {
+    pub min_len: usize,
+    pub format: OutputFormat,
+}
+
+impl Default for StringsOptions {
+    fn default() -> Self {
+        Self {
+            min_len: 6,
+            format: OutputFormat::Text,
+        }
+    }
+}
+
+// prints the text found in every assembly in the mod, each string once.
+// string literals come first when the metadata can be parsed.
+pub fn strings(path: String, options: &StringsOptions) -> Result<(), TModError> {
+    trace!("opening file: {}", path);
+    let mut stream = source::open(&path)?;
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+
+    read_header(&mut reader, None)?;
+    let file_entries = read_entry_table(&mut reader)?;
+
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    for file in &file_entries {
+        let data = read_file_data(&mut reader, file)?;
+        if extension(&file.name).as_deref() != Some("dll") {
+            continue;
+        }
+        info!("Extracting strings from {}", file.name);
+        let literals = user_strings(&data).unwrap_or_else(|| {
+            warn!("{}: couldn't read the assembly's metadata", file.name);
+            Vec::new()
+        });
+        let strings = literals
+            .into_iter()
+            .map(|value| (StringKind::Literal, value))
+            .chain(
+                printable(&data, options.min_len)
+                    .into_iter()
+                    .map(|value| (StringKind::Printable, value)),
+            );
+        for (kind, value) in strings {
+            if value.chars().count() < options.min_len || !mostly_text(&value) {
+                continue;
+            }
+            if seen.insert(value.clone()) {
+                found.push(FoundString {
+                    entry: file.name.clone(),
+                    kind,
+                    value,
+                });
+            }
+        }
+    }
+
+    if options.format == OutputFormat::Text {
+        for string in &found {
+            println!("{}", string.value);
+        }
+    } else {
+        println!("{}", output::render(options.format, "strings", &found)?);
+    }
+
+    Ok(())
+}
+
+// drops runs of padding and table data that happen to be printable, like
+// `@@@@@@` or `$$%&'()*`
+fn mostly_text(value: &str) -> bool {
+    let letters = value
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == ' ')
+        .count();
+    letters * 2 >= value.chars().count() && value.chars().any(char::is_alphabetic)
+}
+
+// printable text in binary entries, mostly compiled assemblies. identifiers in
+// the metadata are NUL-terminated UTF-8, string literals are UTF-16LE, so both
+// are looked for.
+pub fn printable(data: &[u8], min_len: usize) -> Vec<String> {
+    let mut found = Vec::new();
+
+    let mut current = String::new();
+    for &byte in data {
+        if is_printable(byte as u32) {
+            current.push(byte as char);
+        } else {
+            take(&mut current, min_len, &mut found);
+        }
+    }
+    take(&mut current, min_len, &mut found);
+
+    // both alignments, since a literal may start at an odd offset
+    for start in 0..2 {
+        for pair in data[start.min(data.len())..].chunks_exact(2) {
+            let unit = u16::from_le_bytes([pair[0], pair[1]]) as u32;
+            if is_printable(unit) {
+                current.push(char::from_u32(unit).unwrap_or('?'));
+            } else {
+                take(&mut current, min_len, &mut found);
+            }
+        }
+        take(&mut current, min_len, &mut found);
+    }
+
+    found
+}
+
+fn is_printable(unit: u32) -> bool {
+    (0x20..0x7f).contains(&unit)
+}
+
+fn take(current: &mut String, min_len: usize, found: &mut Vec<String>) {
+    if current.len() >= min_len {
+        found.push(std::mem::take(current));
+    } else {
+        current.clear();
+    }
+}
+
+// the `#US` heap of a .NET assembly: every string literal in its code, in
+// declaration order. `None` when `data` isn't an assembly this can parse.
+pub fn user_strings(data: &[u8]) -> Option<Vec<String>> {
+    let read_u16 = |at: usize| Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?));
+    let read_u32 = |at: usize| Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?));
+
+    let pe_offset = read_u32(0x3c)? as usize;
+    if data.get(pe_offset..pe_offset + 4)? != b"PE\0\0" {
+        return None;
+    }
+    let sections = read_u16(pe_offset + 6)? as usize;
+    let optional_header = pe_offset + 24;
+    let section_table = optional_header + read_u16(pe_offset + 20)? as usize;
+    let data_directories = match read_u16(optional_header)? {
+        0x10b => optional_header + 96,
+        0x20b => optional_header + 112,
+        _ => return None,
+    };
+
+    // data directories hold virtual addresses, which the section table maps
+    // back to file offsets
+    let to_offset = |rva: usize| {
+        (0..sections).find_map(|index| {
+            let section = section_table + index * 40;
+            let virtual_size = read_u32(section + 8)? as usize;
+            let virtual_address = read_u32(section + 12)? as usize;
+            let raw_offset = read_u32(section + 20)? as usize;
+            (virtual_address..virtual_address + virtual_size)
+                .contains(&rva)
+                .then(|| rva - virtual_address + raw_offset)
+        })
+    };
+
+    // the CLR runtime header is the 15th data directory, and points at the
+    // metadata root
+    let cli_header = to_offset(read_u32(data_directories + 14 * 8)? as usize)?;
+    let metadata = to_offset(read_u32(cli_header + 8)? as usize)?;
+    if read_u32(metadata)? != 0x424a_5342 {
+        return None;
+    }
+    let version_len = read_u32(metadata + 12)? as usize;
+    let streams = read_u16(metadata + 18 + version_len)? as usize;
+
+    let mut header = metadata + 20 + version_len;
+    let mut heap = None;
+    for _ in 0..streams {
+        let offset = read_u32(header)? as usize;
+        let size = read_u32(header + 4)? as usize;
+        let name = data.get(header + 8..)?;
+        let name_len = name.iter().position(|&b| b == 0)?;
+        if &name[..name_len] == b"#US" {
+            heap = data.get(metadata + offset..metadata + offset + size);
+        }
+        // names are NUL-terminated and padded to four bytes
+        header += 8 + (name_len + 4) / 4 * 4;
+    }
+
+    let heap = heap?;
+    let mut found = Vec::new();
+    // the first entry is always the empty string
+    let mut at = 1;
+    while at < heap.len() {
+        let (len, prefix) = match heap[at] {
+            b if b & 0x80 == 0 => (b as usize, 1),
+            b if b & 0xc0 == 0x80 => (((b as usize & 0x3f) << 8) | *heap.get(at + 1)? as usize, 2),
+            b => (
+                ((b as usize & 0x1f) << 24)
+                    | (*heap.get(at + 1)? as usize) << 16
+                    | (*heap.get(at + 2)? as usize) << 8
+                    | *heap.get(at + 3)? as usize,
+                4,
+            ),
+        };
+        at += prefix;
+        // UTF-16LE code units, then one flag byte
+        let bytes = heap.get(at..at + len)?;
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        if !units.is_empty() {
+            found.push(String::from_utf16_lossy(&units));
+        }
+        at += len;
+    }
+    Some(found)
+}
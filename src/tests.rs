@@ -0,0 +1,1204 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use flate2::{write::DeflateEncoder, Compression};
+use sha1::{Digest, Sha1};
+
+use crate::check::{self, CheckOptions};
+#[cfg(feature = "globs")]
+use crate::filter::Filter;
+use crate::info::{self, InfoOptions};
+use crate::names::unsafe_reason;
+#[cfg(feature = "globs")]
+use crate::order::PriorityList;
+use crate::order::{data_offsets, ExtractOrder};
+use crate::output::OutputFormat;
+use crate::pack::{self, write_string, ArchiveWriter, PackOptions};
+use crate::preflight::SizeLimits;
+#[cfg(feature = "globs")]
+use crate::rawimg::{self, RawImage};
+#[cfg(feature = "globs")]
+use crate::route::{self, Route};
+#[cfg(feature = "rules")]
+use crate::rules::load_rules;
+use crate::sample::Sample;
+#[cfg(feature = "dedup")]
+use crate::sample::SplitMix64;
+use crate::signature::PublicKey;
+#[cfg(feature = "globs")]
+use crate::transform::{self, Transform};
+use crate::{
+    audit, bench, dedup, diff, gallery, layout, lint, orphans, scaffold, stats, strings, view,
+};
+use crate::{unpack, ModFile, TModError, TModReader, UnpackOptions, TMOD_HEADER};
+
+const TML_VERSION: &str = "2023.6.25.1";
+
+// a directory of its own under the temporary directory, removed again when
+// the test is over
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("tmod-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    fn write(&self, name: &str, data: &[u8]) -> String {
+        let path = self.0.join(name);
+        std::fs::write(&path, data).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+// a valid mod with one stored and one compressed entry
+fn fixture() -> Vec<u8> {
+    let code = b"public class Foo { }\n".repeat(100);
+    let mut writer = ArchiveWriter::new("Fixture", "1.0", 2);
+    writer.add("Info", 5, b"hello");
+    writer.add("Foo.cs", code.len() as i32, &deflate(&code));
+    writer.finish(TML_VERSION, Path::new("Fixture")).unwrap()
+}
+
+// a mod with an entry table that says whatever it is given, with a hash
+// that matches the data either way
+fn raw_archive(entries: &[(&str, i32, i32)], data: &[u8]) -> Vec<u8> {
+    let mut table = Vec::new();
+    write_string(&mut table, "Broken");
+    write_string(&mut table, "1.0");
+    table.extend_from_slice(&(entries.len() as i32).to_le_bytes());
+    for (name, uncompressed_len, compressed_len) in entries {
+        write_string(&mut table, name);
+        table.extend_from_slice(&uncompressed_len.to_le_bytes());
+        table.extend_from_slice(&compressed_len.to_le_bytes());
+    }
+    table.extend_from_slice(data);
+
+    let mut file = Vec::new();
+    file.extend_from_slice(TMOD_HEADER);
+    write_string(&mut file, TML_VERSION);
+    file.extend_from_slice(&Sha1::digest(&table));
+    file.extend_from_slice(&[0; 256]);
+    file.extend_from_slice(&(table.len() as u32).to_le_bytes());
+    file.extend_from_slice(&table);
+    file
+}
+
+fn negative_length() -> Vec<u8> {
+    raw_archive(&[("Info", 5, 5), ("Foo.cs", 100, -5)], b"hello")
+}
+
+fn truncated() -> Vec<u8> {
+    let mut file = fixture();
+    file.truncate(file.len() - 10);
+    file
+}
+
+// the data is intact, the hash stored for it isn't
+fn bad_hash() -> Vec<u8> {
+    let mut file = fixture();
+    let hash_start = TMOD_HEADER.len() + 1 + TML_VERSION.len();
+    file[hash_start] ^= 0xff;
+    file
+}
+
+// a table entry stored as is
+fn entry(name: &str, len: i32) -> ModFile {
+    ModFile {
+        name: name.to_string(),
+        uncompressed_len: len,
+        compressed_len: len,
+    }
+}
+
+fn unpack_options() -> UnpackOptions {
+    UnpackOptions {
+        yes: true,
+        quiet: true,
+        no_progress: true,
+        ..Default::default()
+    }
+}
+
+fn check_options(fast: bool) -> CheckOptions {
+    CheckOptions {
+        fast,
+        key: None,
+        format: OutputFormat::Text,
+    }
+}
+
+fn files(dir: &Path) -> Vec<(String, Vec<u8>)> {
+    let mut files: Vec<(String, Vec<u8>)> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file())
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, std::fs::read(&path).unwrap())
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn reads_what_the_writer_wrote() {
+    let mut reader = TModReader::from_bytes(fixture()).unwrap();
+    assert_eq!(reader.name(), "Fixture");
+    assert_eq!(reader.version(), "1.0");
+    assert_eq!(reader.tmodloader_version(), TML_VERSION);
+    assert_eq!(reader.read("Info").unwrap(), b"hello");
+    assert_eq!(
+        reader.read("Foo.cs").unwrap(),
+        b"public class Foo { }\n".repeat(100)
+    );
+}
+
+#[test]
+fn pack_then_unpack_round_trips() {
+    let dir = TempDir::new("round-trip");
+    let input = dir.0.join("RoundTrip");
+    std::fs::create_dir_all(input.join("Items")).unwrap();
+    std::fs::write(input.join("build.txt"), "version = 1.2\n").unwrap();
+    std::fs::write(input.join("small.txt"), "small").unwrap();
+    std::fs::write(input.join("Items/Big.cs"), "// big\n".repeat(1000)).unwrap();
+
+    pack::pack(&PackOptions {
+        input: input.clone(),
+        name: None,
+        version: None,
+        tmodloader_version: TML_VERSION.to_string(),
+        output: None,
+        output_dir: Some(dir.0.clone()),
+        shared: Vec::new(),
+        optimize_png: false,
+        reencode_audio: None,
+    })
+    .unwrap();
+    let packed = dir.0.join("RoundTrip.tmod");
+    let reader = TModReader::open(&packed.to_string_lossy()).unwrap();
+    assert_eq!(reader.version(), "1.2");
+    // big enough to be compressed, the rest stored
+    let big = reader.entry("Items/Big.cs").unwrap();
+    assert!(big.compressed_len < big.uncompressed_len);
+
+    let out = dir.0.join("out");
+    let options = UnpackOptions {
+        verify: true,
+        ..unpack_options()
+    };
+    unpack(
+        packed.to_string_lossy().into_owned(),
+        Some(out.clone()),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(files(&out.join("Items")), files(&input.join("Items")));
+    assert_eq!(
+        std::fs::read(out.join("small.txt")).unwrap(),
+        std::fs::read(input.join("small.txt")).unwrap()
+    );
+    assert!(check::check(packed.to_string_lossy().into_owned(), &check_options(false)).is_ok());
+}
+
+#[test]
+fn unpack_refuses_negative_lengths() {
+    let dir = TempDir::new("unpack-negative");
+    let path = dir.write("negative.tmod", &negative_length());
+    let out = dir.0.join("out");
+    let result = unpack(path, Some(out.clone()), &unpack_options());
+    assert!(matches!(result, Err(TModError::InvalidEntryTable(_))));
+    assert!(!out.join("Info").exists());
+}
+
+#[test]
+fn unpack_refuses_truncated_data() {
+    let dir = TempDir::new("unpack-truncated");
+    let path = dir.write("truncated.tmod", &truncated());
+    let result = unpack(path, Some(dir.0.join("out")), &unpack_options());
+    assert!(matches!(result, Err(TModError::InvalidEntryTable(_))));
+}
+
+#[test]
+fn unpack_verify_refuses_a_bad_hash() {
+    let dir = TempDir::new("unpack-hash");
+    let path = dir.write("hash.tmod", &bad_hash());
+    let options = UnpackOptions {
+        verify: true,
+        ..unpack_options()
+    };
+    let result = unpack(path, Some(dir.0.join("out")), &options);
+    assert!(matches!(result, Err(TModError::HashMismatch(..))));
+}
+
+#[test]
+fn check_reports_corrupt_archives() {
+    let dir = TempDir::new("check");
+    for (name, data) in [
+        ("negative.tmod", negative_length()),
+        ("truncated.tmod", truncated()),
+        ("hash.tmod", bad_hash()),
+    ] {
+        let path = dir.write(name, &data);
+        let result = check::check(path, &check_options(false));
+        assert!(
+            matches!(result, Err(TModError::CheckFailed(_))),
+            "{}: {:?}",
+            name,
+            result.err().map(|e| e.to_string())
+        );
+    }
+    // a fast check doesn't hash, but still catches broken tables
+    for (name, data) in [
+        ("negative.tmod", negative_length()),
+        ("truncated.tmod", truncated()),
+    ] {
+        let path = dir.write(name, &data);
+        let result = check::check(path, &check_options(true));
+        assert!(matches!(result, Err(TModError::CheckFailed(_))), "{}", name);
+    }
+}
+
+// every command that reads entries fails on a negative length instead of
+// trying to allocate it
+// runs every subcommand that reads entries over the mod at `path`
+fn every_reader(dir: &TempDir, path: &str) -> Vec<(&'static str, Result<(), TModError>)> {
+    let good = dir.write("good.tmod", &fixture());
+    let p = || path.to_string();
+    vec![
+        ("info", info::info(p(), &InfoOptions::default())),
+        ("list", info::list(p(), &InfoOptions::default())),
+        ("view", view::view(p(), "Foo.cs")),
+        ("cat", view::cat(p(), "Info")),
+        ("grep", view::grep(p(), "class", false)),
+        ("audit", audit::audit(p(), &audit::AuditOptions::default())),
+        ("lint", lint::lint(p(), &lint::LintOptions::default())),
+        ("orphans", orphans::orphans(p(), OutputFormat::Json)),
+        (
+            "strings",
+            strings::strings(p(), &strings::StringsOptions::default()),
+        ),
+        (
+            "stats",
+            stats::stats(vec![p()], &stats::StatsOptions::default()),
+        ),
+        ("bench", bench::bench(p(), &bench::BenchOptions::default())),
+        ("diff", diff::diff(good, p(), &diff::DiffOptions::default())),
+        ("gallery", gallery::gallery(p(), &dir.0.join("gallery"))),
+        (
+            "scaffold",
+            scaffold::scaffold(&scaffold::ScaffoldOptions {
+                from: p(),
+                name: "Scaffolded".to_string(),
+                output: Some(dir.0.join("scaffold")),
+            }),
+        ),
+        (
+            "unpack",
+            unpack(p(), Some(dir.0.join("out")), &unpack_options()),
+        ),
+    ]
+}
+
+#[test]
+fn every_reader_refuses_negative_lengths() {
+    let dir = TempDir::new("negative");
+    let path = dir.write("negative.tmod", &negative_length());
+    let p = || path.clone();
+
+    let results = every_reader(&dir, &path);
+    for (command, result) in results {
+        assert!(
+            matches!(result, Err(TModError::InvalidEntryTable(1))),
+            "{}: {:?}",
+            command,
+            result.err().map(|e| e.to_string())
+        );
+    }
+    // these describe damaged mods rather than failing on them
+    assert!(layout::layout(p(), OutputFormat::Json).is_ok());
+    if cfg!(feature = "dedup") {
+        let options = dedup::DedupOptions::default();
+        assert!(dedup::dedup(vec![path.clone().into()], &options).is_ok());
+    }
+    assert!(TModReader::open(&path).is_err());
+    assert!(matches!(
+        check::check(p(), &check_options(false)),
+        Err(TModError::CheckFailed(_))
+    ));
+}
+
+// the compressed entry is cut short: whatever reads it fails, whatever only
+// needs the table or the intact entry still works
+#[test]
+fn every_reader_refuses_truncated_data() {
+    let dir = TempDir::new("truncated-readers");
+    let path = dir.write("truncated.tmod", &truncated());
+
+    for (command, result) in every_reader(&dir, &path) {
+        let expected = match command {
+            "info" | "list" | "cat" | "stats" | "scaffold" => result.is_ok(),
+            // refused up front, before anything is written
+            "unpack" => matches!(result, Err(TModError::InvalidEntryTable(1))),
+            _ => {
+                matches!(result, Err(TModError::TruncatedEntry(ref name, 47, 37)) if name == "Foo.cs")
+            }
+        };
+        assert!(
+            expected,
+            "{}: {:?}",
+            command,
+            result.err().map(|e| e.to_string())
+        );
+    }
+    assert!(!dir.0.join("out").exists());
+    assert!(layout::layout(path.clone(), OutputFormat::Json).is_ok());
+    assert!(matches!(
+        view::cat(path.clone(), "Foo.cs"),
+        Err(TModError::TruncatedEntry(..))
+    ));
+    let mut reader = TModReader::open(&path).unwrap();
+    assert!(matches!(
+        reader.read("Foo.cs"),
+        Err(TModError::TruncatedEntry(..))
+    ));
+    for fast in [false, true] {
+        assert!(matches!(
+            check::check(path.clone(), &check_options(fast)),
+            Err(TModError::CheckFailed(1))
+        ));
+    }
+}
+
+// the data is fine, so only the commands that check the hash mind
+#[test]
+fn only_verifying_readers_refuse_a_bad_hash() {
+    let dir = TempDir::new("bad-hash-readers");
+    let path = dir.write("bad-hash.tmod", &bad_hash());
+
+    for (command, result) in every_reader(&dir, &path) {
+        assert!(
+            result.is_ok(),
+            "{}: {:?}",
+            command,
+            result.err().map(|e| e.to_string())
+        );
+    }
+    assert!(matches!(
+        check::check(path.clone(), &check_options(false)),
+        Err(TModError::CheckFailed(1))
+    ));
+    // `--fast` skips hashing
+    assert!(check::check(path, &check_options(true)).is_ok());
+}
+
+// a stored entry cut short reads as an error, not as its bytes padded with
+// zeroes, whether the mod is in memory or read from a file
+#[test]
+fn truncated_stored_entries_fail() {
+    let mut writer = ArchiveWriter::new("Cut", "1.0", 1);
+    writer.add("a.txt", 100, &[b'a'; 100]);
+    let mut file = writer.finish(TML_VERSION, Path::new("Cut")).unwrap();
+    file.truncate(file.len() - 10);
+
+    let dir = TempDir::new("truncated-stored");
+    let path = dir.write("cut.tmod", &file);
+    let mut in_memory = TModReader::from_bytes(file).unwrap();
+    let mut from_file = TModReader::open(&path).unwrap();
+    for reader in [&mut in_memory, &mut from_file] {
+        assert!(matches!(
+            reader.read("a.txt"),
+            Err(TModError::TruncatedEntry(_, 100, 90))
+        ));
+        assert!(reader.copy_entry(0, &mut Vec::new()).is_err());
+    }
+    assert!(matches!(
+        view::cat(path.clone(), "a.txt"),
+        Err(TModError::TruncatedEntry(..))
+    ));
+    assert!(matches!(
+        bench::bench(path, &bench::BenchOptions::default()),
+        Err(TModError::TruncatedEntry(..))
+    ));
+}
+
+#[test]
+fn data_offsets_follow_the_table() {
+    let file = |compressed_len| ModFile {
+        name: String::new(),
+        uncompressed_len: compressed_len,
+        compressed_len,
+    };
+    let files = [file(5), file(0), file(7)];
+    assert_eq!(data_offsets(&files, 100).unwrap(), vec![100, 105, 105]);
+
+    let negative = [file(5), file(-1), file(-7)];
+    assert!(matches!(
+        data_offsets(&negative, 100),
+        Err(TModError::InvalidEntryTable(2))
+    ));
+    assert!(matches!(
+        data_offsets(&files, usize::MAX - 5),
+        Err(TModError::InvalidEntryTable(1))
+    ));
+}
+
+// a tModLoader 0.10 mod with one stored entry and junk in front of it
+fn legacy() -> Vec<u8> {
+    let mut inner = Vec::new();
+    write_string(&mut inner, "Old");
+    write_string(&mut inner, "0.1");
+    inner.extend_from_slice(&1i32.to_le_bytes());
+    write_string(&mut inner, "Info");
+    inner.extend_from_slice(&5i32.to_le_bytes());
+    inner.extend_from_slice(b"hello");
+    let data = deflate(&inner);
+
+    let mut file = b"<html></html>".to_vec();
+    file.extend_from_slice(TMOD_HEADER);
+    write_string(&mut file, "v0.10.1.5");
+    file.extend_from_slice(&Sha1::digest(&data));
+    file.extend_from_slice(&[0xab; 256]);
+    file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    file.extend_from_slice(&data);
+    file
+}
+
+#[test]
+fn extraction_orders_sort_stably() {
+    let files = [
+        entry("b.png", 10),
+        entry("a.cs", 30),
+        entry("c.txt", 10),
+        entry("Info", 20),
+    ];
+    let arrange = |order| ExtractOrder::parse(order).unwrap().arrange(&files);
+    assert_eq!(arrange("table"), vec![0, 1, 2, 3]);
+    // byte order, so capitals first
+    assert_eq!(arrange("alpha"), vec![3, 1, 0, 2]);
+    assert_eq!(arrange("size-desc"), vec![1, 3, 0, 2]);
+    assert_eq!(arrange("size-asc"), vec![0, 2, 3, 1]);
+    assert!(matches!(
+        ExtractOrder::parse("random"),
+        Err(TModError::UnknownOrder(_))
+    ));
+}
+
+#[cfg(feature = "globs")]
+#[test]
+fn priority_lists_go_first_in_line_order() {
+    let dir = TempDir::new("priority-list");
+    let path = dir.write(
+        "priority.txt",
+        b"# comments and blanks are skipped\n\n*.txt\nInfo\n",
+    );
+    let list = PriorityList::load(Path::new(&path)).unwrap();
+    let files = [
+        entry("b.png", 10),
+        entry("Info", 20),
+        entry("a.cs", 30),
+        entry("c.txt", 10),
+    ];
+    let mut indices = ExtractOrder::SizeDesc.arrange(&files);
+    list.apply(&files, &mut indices);
+    assert_eq!(indices, vec![3, 1, 2, 0]);
+    assert_eq!(list.rank("b.png"), None);
+}
+
+#[test]
+fn samples_pick_from_eligible_entries() {
+    let files = [
+        entry("a.png", 1),
+        entry("b.cs", 1),
+        entry("c.png", 1),
+        entry("d.png", 1),
+        entry("e.cs", 1),
+        entry("f", 1),
+    ];
+    let all = [true; 6];
+    let sample = |count, random, per_extension, seed| Sample {
+        count,
+        random,
+        per_extension,
+        seed,
+    };
+    let picked = |mask: Vec<bool>| -> Vec<usize> {
+        mask.iter()
+            .enumerate()
+            .filter(|(_, &keep)| keep)
+            .map(|(index, _)| index)
+            .collect()
+    };
+
+    assert_eq!(
+        picked(sample(2, false, false, None).select(&files, &all)),
+        vec![0, 1]
+    );
+    let eligible = [false, true, false, true, true, true];
+    assert_eq!(
+        picked(sample(2, false, false, None).select(&files, &eligible)),
+        vec![1, 3]
+    );
+    // one of each extension, and one without
+    assert_eq!(
+        picked(sample(1, false, true, None).select(&files, &all)),
+        vec![0, 1, 5]
+    );
+    assert_eq!(
+        picked(sample(10, false, false, None).select(&files, &all)).len(),
+        6
+    );
+
+    let random = sample(3, true, false, Some(42)).select(&files, &all);
+    assert_eq!(picked(random.clone()).len(), 3);
+    assert_eq!(
+        sample(3, true, false, Some(42)).select(&files, &all),
+        random
+    );
+    let random = picked(sample(3, true, false, Some(7)).select(&files, &eligible));
+    assert!(random.iter().all(|&index| eligible[index]));
+}
+
+#[cfg(feature = "globs")]
+#[test]
+fn routes_send_entries_to_the_first_matching_root() {
+    let routes = [
+        Route::parse("Content/**/*.png => art").unwrap(),
+        Route::parse("**/*.png=>other").unwrap(),
+        Route::parse("*.txt=>text").unwrap(),
+    ];
+    let default = Path::new("out");
+    let destination = |name| route::destination(&routes, name, Some(default));
+    assert_eq!(
+        destination("Content/Items/Sword.png"),
+        Some(Path::new("art"))
+    );
+    assert_eq!(destination("Sword.png"), Some(Path::new("other")));
+    assert_eq!(destination("notes.txt"), Some(Path::new("text")));
+    // `*` doesn't cross directories
+    assert_eq!(destination("docs/notes.txt"), Some(default));
+    assert_eq!(route::destination(&routes, "Foo.cs", None), None);
+
+    assert!(matches!(
+        Route::parse("*.png"),
+        Err(TModError::InvalidRoute(..))
+    ));
+    assert!(matches!(
+        Route::parse("*.png=> "),
+        Err(TModError::InvalidRoute(..))
+    ));
+    assert!(matches!(
+        Route::parse("[=>art"),
+        Err(TModError::InvalidGlob(..))
+    ));
+
+    let dir = TempDir::new("routes");
+    let path = dir.write("Fixture.tmod", &fixture());
+    let code = dir.0.join("code");
+    let options = UnpackOptions {
+        routes: vec![Route::parse(&format!("*.cs=>{}", code.display())).unwrap()],
+        ..unpack_options()
+    };
+    unpack(path, Some(dir.0.join("out")), &options).unwrap();
+    assert_eq!(std::fs::read(dir.0.join("out/Info")).unwrap(), b"hello");
+    assert!(code.join("Foo.cs").is_file());
+    assert!(!dir.0.join("out/Foo.cs").exists());
+}
+
+#[cfg(feature = "globs")]
+#[test]
+fn filters_include_then_exclude() {
+    let patterns = |patterns: &[&str]| -> Vec<String> {
+        patterns.iter().map(|pattern| pattern.to_string()).collect()
+    };
+    let filter = Filter::new(
+        &patterns(&["*.cs", "Content/**"]),
+        &patterns(&["Content/Secret/**", "Old*.cs"]),
+    )
+    .unwrap();
+    // a pattern without a `/` matches the file name in any directory
+    assert!(filter.matches("Items/Sword.cs"));
+    assert!(filter.matches("Content/Items/Sword.png"));
+    assert!(!filter.matches("Content/Secret/key.txt"));
+    assert!(!filter.matches("Items/OldSword.cs"));
+    assert!(!filter.matches("build.txt"));
+
+    let exclude_only = Filter::new(&[], &patterns(&["*.png"])).unwrap();
+    assert!(exclude_only.matches("build.txt"));
+    assert!(!exclude_only.matches("icon.png"));
+    assert!(Filter::new(&[], &[]).unwrap().is_empty());
+    assert!(matches!(
+        Filter::new(&patterns(&["["]), &[]),
+        Err(TModError::InvalidGlob(..))
+    ));
+
+    let dir = TempDir::new("filter");
+    let path = dir.write("Fixture.tmod", &fixture());
+    let out = dir.0.join("out");
+    let options = UnpackOptions {
+        filter: Filter::new(&patterns(&["*.cs"]), &[]).unwrap(),
+        ..unpack_options()
+    };
+    unpack(path, Some(out.clone()), &options).unwrap();
+    let names: Vec<String> = files(&out).into_iter().map(|(name, _)| name).collect();
+    assert_eq!(names, vec!["Foo.cs"]);
+}
+
+#[cfg(feature = "rules")]
+#[test]
+fn rules_match_regexes_and_raw_bytes() {
+    let dir = TempDir::new("rules");
+    let path = dir.write(
+        "rules.yaml",
+        br#"rules:
+  - name: discord-webhook
+    regex: "https://discord(app)?\\.com/api/webhooks/"
+  - name: upx-packed
+    bytes: "55 50 58 21"
+    description: UPX packed executable
+"#,
+    );
+    let rules = load_rules(&path).unwrap();
+    assert_eq!(rules.len(), 2);
+    assert!(rules[0].matches(b"post to https://discordapp.com/api/webhooks/1/x"));
+    assert!(!rules[0].matches(b"https://discord.com/channels/1"));
+    // bytes match anywhere, including ones that aren't valid UTF-8
+    assert!(rules[1].matches(b"\xff\xfeUPX!\x00"));
+    assert!(!rules[1].matches(b"upx!"));
+
+    let file = entry("Lib/packed.dll", 6);
+    let mut findings = Vec::new();
+    audit::apply_rules(&file, b"\xffUPX!\x00", &rules, &mut findings);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].kind, audit::FindingKind::Rule);
+    assert_eq!(
+        findings[0].message,
+        "matched rule `upx-packed` (UPX packed executable)"
+    );
+
+    for (name, definition) in [
+        ("both", "regex: a\n    bytes: \"00\""),
+        ("neither", "description: nothing to match"),
+        ("bad-hex", "bytes: \"0g\""),
+        ("bad-regex", "regex: \"(\""),
+    ] {
+        let path = dir.write(
+            "invalid.yaml",
+            format!("rules:\n  - name: {}\n    {}\n", name, definition).as_bytes(),
+        );
+        assert!(
+            matches!(load_rules(&path), Err(TModError::InvalidRule(rule, _)) if rule == name),
+            "{}",
+            name
+        );
+    }
+}
+
+#[cfg(feature = "globs")]
+#[test]
+fn transforms_run_their_steps_in_a_fixed_order() {
+    let texture = RawImage {
+        width: 2,
+        height: 1,
+        rgba: &[64, 32, 0, 128, 10, 10, 10, 0],
+    }
+    .to_rawimg();
+
+    let unpremultiply = Transform::parse("**/*.rawimg=unpremultiply").unwrap();
+    let out = unpremultiply
+        .apply("Images/a.rawimg", texture.clone())
+        .unwrap();
+    let image = rawimg::parse("a.rawimg", &out).unwrap();
+    assert_eq!((image.width, image.height), (2, 1));
+    // fully transparent pixels become transparent black
+    assert_eq!(image.rgba, &[128, 64, 0, 128, 0, 0, 0, 0]);
+
+    // resizing after unpremultiplying, however they are written
+    let resized = Transform::parse("*.rawimg=resize=4x2:unpremultiply").unwrap();
+    let out = resized.apply("a.rawimg", texture.clone()).unwrap();
+    let image = rawimg::parse("a.rawimg", &out).unwrap();
+    assert_eq!((image.width, image.height), (4, 2));
+    assert_eq!(&image.rgba[..4], &[128, 64, 0, 128]);
+
+    let png = Transform::parse("*.rawimg=png").unwrap();
+    assert_eq!(png.output_name("Images/a.rawimg"), "Images/a.png");
+    assert_eq!(
+        unpremultiply.output_name("Images/a.rawimg"),
+        "Images/a.rawimg"
+    );
+    if cfg!(feature = "images") {
+        let out = png.apply("a.rawimg", texture).unwrap();
+        assert!(out.starts_with(b"\x89PNG\r\n\x1a\n"));
+    }
+    // anything but a texture is left alone
+    assert_eq!(png.apply("a.txt", b"text".to_vec()).unwrap(), b"text");
+    let transforms = [unpremultiply, png];
+    let first = transform::find(&transforms, "a.rawimg").unwrap();
+    assert_eq!(first.pattern, "**/*.rawimg");
+
+    for invalid in [
+        "*.rawimg",
+        "*.rawimg=sharpen",
+        "*.rawimg=png:png",
+        "*.rawimg=resize=0x4",
+        "*.rawimg=resize=4",
+    ] {
+        assert!(
+            matches!(
+                Transform::parse(invalid),
+                Err(TModError::InvalidTransform(..))
+            ),
+            "{}",
+            invalid
+        );
+    }
+}
+
+#[cfg(feature = "dedup")]
+#[test]
+fn dedup_finds_shared_entries_and_chunks() {
+    let mut rng = SplitMix64::new(1);
+    let shared: Vec<u8> = (0..64 * 1024).map(|_| rng.next() as u8).collect();
+    let shifted = [&[0xab; 100][..], &shared].concat();
+    let archive = |name, entries: &[(&str, &[u8])]| {
+        let mut writer = ArchiveWriter::new(name, "1.0", entries.len());
+        for (entry, data) in entries {
+            writer.add(entry, data.len() as i32, data);
+        }
+        writer.finish(TML_VERSION, Path::new(name)).unwrap()
+    };
+
+    let dir = TempDir::new("dedup");
+    dir.write(
+        "A.tmod",
+        &archive("A", &[("shared.bin", &shared), ("a.txt", b"hello")]),
+    );
+    dir.write(
+        "B.tmod",
+        &archive("B", &[("shared.bin", &shared), ("shifted.bin", &shifted)]),
+    );
+    dir.write("Broken.tmod", &truncated());
+    let options = dedup::DedupOptions {
+        avg_size: 1024,
+        ..Default::default()
+    };
+    let report = dedup::dedup_report(std::slice::from_ref(&dir.0), &options).unwrap();
+    assert_eq!(report.mods, 2);
+    assert_eq!(report.skipped.len(), 1);
+    assert_eq!(report.entries, 4);
+    assert_eq!(report.unique_entries, 3);
+    assert_eq!(
+        report.unique_entry_size,
+        (shared.len() + 5 + shifted.len()) as u64
+    );
+    // the shifted copy only adds the chunks around the new prefix
+    assert!(report.unique_chunk_size < report.unique_entry_size - shared.len() as u64 / 2);
+    assert!(report.unique_chunks < report.chunks);
+
+    let tiny = dedup::DedupOptions {
+        avg_size: 64,
+        ..Default::default()
+    };
+    assert!(matches!(
+        dedup::dedup_report(std::slice::from_ref(&dir.0), &tiny),
+        Err(TModError::InvalidNumber(..))
+    ));
+}
+
+#[test]
+fn legacy_mods_are_upgraded_without_their_signature() {
+    let mut reader = TModReader::from_bytes(legacy()).unwrap();
+    assert_eq!(reader.name(), "Old");
+    assert!(reader.header().signature.iter().all(|&byte| byte == 0));
+    assert_eq!(reader.read("Info").unwrap(), b"hello");
+}
+
+#[test]
+fn size_limits_are_checked_before_inflating() {
+    let limits = SizeLimits {
+        max_entry_size: 100,
+        max_total_size: 1000,
+    };
+    let mut reader = TModReader::from_bytes_with_limits(fixture(), limits).unwrap();
+    assert_eq!(reader.read("Info").unwrap(), b"hello");
+    assert!(matches!(
+        reader.read("Foo.cs"),
+        Err(TModError::TooLarge(name, 2100, 100)) if name == "Foo.cs"
+    ));
+    assert!(matches!(
+        reader.copy_entry(1, &mut Vec::new()),
+        Err(TModError::TooLarge(..))
+    ));
+
+    let dir = TempDir::new("size-limits");
+    let path = dir.write("Fixture.tmod", &fixture());
+    let mut reader = TModReader::with_limits(
+        &path,
+        SizeLimits {
+            max_entry_size: 10_000,
+            max_total_size: 1000,
+        },
+    )
+    .unwrap();
+    assert!(matches!(
+        reader.extract_to(dir.0.join("out")),
+        Err(TModError::TooLarge(name, 2105, 1000)) if name == "Fixture"
+    ));
+
+    // 2 GiB from ten bytes, refused by the default limits
+    let bomb = raw_archive(&[("bomb", i32::MAX, 10)], &[0; 10]);
+    let mut reader = TModReader::from_bytes(bomb).unwrap();
+    assert!(matches!(
+        reader.read_entry(0),
+        Err(TModError::TooLarge(_, len, _)) if len == i32::MAX as u64
+    ));
+
+    let tiny = SizeLimits {
+        max_entry_size: 10,
+        max_total_size: 10,
+    };
+    assert!(matches!(
+        TModReader::from_bytes_with_limits(legacy(), tiny),
+        Err(TModError::InflateError(..))
+    ));
+}
+
+#[test]
+fn entry_names_that_escape_are_refused() {
+    for name in [
+        "../evil.txt",
+        "Content/../../evil.txt",
+        "Content\\..\\evil.txt",
+        "..",
+        "/etc/passwd",
+        "\\server\\share",
+        "C:/Windows/evil.dll",
+        "C:evil.dll",
+        "Content/C:evil.dll",
+        "Content/file.txt:stream",
+    ] {
+        assert!(unsafe_reason(name).is_some(), "{}", name);
+    }
+    for name in [
+        "Info",
+        "Content/Items/Sword.png",
+        "..hidden",
+        "a..b/c",
+        "Content/.../x",
+    ] {
+        assert_eq!(unsafe_reason(name), None, "{}", name);
+    }
+
+    let dir = TempDir::new("unsafe-paths");
+    let path = dir.write(
+        "Evil.tmod",
+        &raw_archive(&[("Info", 5, 5), ("../evil.txt", 4, 4)], b"helloevil"),
+    );
+    let out = dir.0.join("out");
+    assert!(matches!(
+        unpack(path.clone(), Some(out.clone()), &unpack_options()),
+        Err(TModError::InvalidEntryTable(1))
+    ));
+    let mut reader = TModReader::open(&path).unwrap();
+    assert!(matches!(
+        reader.extract_to(&out),
+        Err(TModError::UnsafePath(..))
+    ));
+    assert!(!dir.0.join("evil.txt").exists());
+}
+
+// a 2048-bit key made with `openssl genrsa`, and what `openssl dgst -sha1
+// -sign` made of `tmod-unpacker` with it
+const TEST_KEY: &str = "<RSAKeyValue><Modulus>td66noyIBa/OwRx+/535IoWqMXdRuvzVEmhpyU92w2yuxb+V7pQTokoUhtA5OnEZek4wLiANITPlW+Alh+QEjZILhloQBLQRoa4IEs15R3c6ybbWkxNzg1DU2m8Z4XnbB225JjSOhfEpZ6hIA0MOZ9eJmZGy7MIpTS8TLH1Iy6Y1Tsr+JrNJcH1KC/MGIOXGO0LhB0H0VH+s7wc9QIkyi3cy86UNJZcRgzdpbaeVfJuZELPTCESX+CU7WaUEnA5ThMI/SaLBXlnanIEmY+elSlOIZwftdk19xgJE3ggQVV47am+vCYHIqwtTmvUYSNMf+JHxqFu1QHKlviiVV06MTw==</Modulus><Exponent>AQAB</Exponent></RSAKeyValue>";
+const TEST_SIGNATURE: &str = "25194d9d63b5b6987ba139e4449dbc94b2c3dac4f48e730dcc391a297e4b4ef19a2df7439f885db4a03b88e1a7aebfad21edaa39ee08b323e2b0acb5e8ffc799bcfce60ff48890a0d739fa9c75c20b259838118b08f3baaec7865d60a4754be28c40bb31f06ab9e06870403c9475b658d2b939bc9d013d81a23c676f4cb1a1fe7b9761f6785e828f303056e4369bc882e7699c676cdbdc9a750a1a2bfa83d7f9d4a386aea31fbebbdf4836fe8fd0ba7d87b85c90f8ecbfc5a7480e9e18c87e734ea68e0c0fa977934d42f071d59043065d1158ba8e843baa388b263bf4312ec342df599db0c6bb669ea4392abae9d6f732f2f45ca431dec4e611fc4b49b5275a";
+// `openssl pkeyutl -sign` of the bare digest: padded right, but without the
+// DigestInfo that says it is SHA1
+const TEST_SIGNATURE_WITHOUT_DIGEST_INFO: &str = "ab9d1f302ee8767552b6e962660c365085eb258428040fff52674d3b06db7b14f9fe77beb3c566687a0b6138035080017e91060147b1b94d97065fc4933dcde6260a25611bc5531acc7b640122bcae5c7dc9b0e950804e6d39af2e06926ba28cd7cb2110f38a5a9e2565b61d809633ae937ab5299ff592e1c062eb420e1f682812423b8a4e35435acafe733954a4990f0c25928e9c16a02d45eda38aa976fbaf9968493294d21420c045208486c1f51bbeaec19314303a9f2e20cb6f25fee247756a52faefa4ebe3fa8935ee3bb6e4c59b693a750fa5a3f02679b5b24e72424571fa5c872afe7e68ad0cfa6e85d1efbacd4c954bbf03d207349454858b8abfc5";
+
+#[test]
+fn signatures_are_checked_against_known_answers() {
+    let key = PublicKey::parse(TEST_KEY).unwrap();
+    let digest = Sha1::digest(b"tmod-unpacker");
+    let signature = hex::decode(TEST_SIGNATURE).unwrap();
+    assert!(key.verify(&digest, &signature));
+
+    let other_digest = Sha1::digest(b"tmod-unpacker!");
+    assert!(!key.verify(&other_digest, &signature));
+
+    for bit in [0, 7, 1000, 2047] {
+        let mut flipped = signature.clone();
+        flipped[bit / 8] ^= 1 << (bit % 8);
+        assert!(!key.verify(&digest, &flipped), "bit {}", bit);
+    }
+
+    // the modulus and anything bigger are refused, not reduced
+    let modulus = base64::engine::general_purpose::STANDARD
+        .decode(
+            TEST_KEY
+                .split("<Modulus>")
+                .nth(1)
+                .and_then(|rest| rest.split("</Modulus>").next())
+                .unwrap(),
+        )
+        .unwrap();
+    assert!(!key.verify(&digest, &modulus));
+    assert!(!key.verify(&digest, &[0xff; 256]));
+    assert!(!key.verify(&digest, &[0xff; 257]));
+
+    let without_digest_info = hex::decode(TEST_SIGNATURE_WITHOUT_DIGEST_INFO).unwrap();
+    assert!(!key.verify(&digest, &without_digest_info));
+
+    assert!(!key.verify(&digest, &[]));
+    assert!(!PublicKey::mod_browser().verify(&digest, &signature));
+    assert!(PublicKey::parse("<RSAKeyValue></RSAKeyValue>").is_err());
+}
+
+#[cfg(all(unix, feature = "archives"))]
+#[test]
+fn archives_are_not_written_through_a_planted_symlink() {
+    use crate::sink::{archive_sink, ArchiveFormat};
+
+    let dir = TempDir::new("partial-symlink");
+    let victim = dir.write("victim", b"keep me");
+    std::os::unix::fs::symlink(&victim, dir.0.join(".out.zip.partial")).unwrap();
+
+    let mut sink = archive_sink(ArchiveFormat::Zip, &dir.0.join("out.zip")).unwrap();
+    let file = ModFile {
+        name: "Info".to_string(),
+        uncompressed_len: 5,
+        compressed_len: 5,
+    };
+    sink.write_entry(&file, b"hello").unwrap();
+    sink.finish().unwrap();
+
+    assert_eq!(std::fs::read(&victim).unwrap(), b"keep me");
+    let zip = std::fs::File::open(dir.0.join("out.zip")).unwrap();
+    assert_eq!(zip::ZipArchive::new(zip).unwrap().len(), 1);
+}
+
+#[cfg(feature = "serve")]
+mod serve {
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::time::Duration;
+
+    use super::{fixture, negative_length, raw_archive};
+    #[cfg(feature = "http")]
+    use crate::serve::is_public;
+    use crate::serve::{serve_on, ServeOptions};
+
+    fn options() -> ServeOptions {
+        ServeOptions {
+            listen: String::new(),
+            max_size: 1024 * 1024,
+            max_unpacked: 1024 * 1024,
+            timeout: Duration::from_secs(5),
+            max_connections: 4,
+            allow_fetch: false,
+        }
+    }
+
+    fn start(options: ServeOptions) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || serve_on(listener, &options));
+        addr
+    }
+
+    fn post(addr: SocketAddr, target: &str, body: &[u8]) -> (u16, String, Vec<u8>) {
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: test\r\nContent-Length: {}\r\n\r\n",
+            target,
+            body.len()
+        )
+        .into_bytes();
+        request.extend_from_slice(body);
+        send(addr, &request)
+    }
+
+    // the status, the headers and the body, with chunked encoding undone
+    fn send(addr: SocketAddr, request: &[u8]) -> (u16, String, Vec<u8>) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request).unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+
+        let head_end = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .unwrap();
+        let head = String::from_utf8(response[..head_end].to_vec()).unwrap();
+        let status = head[9..12].parse().unwrap();
+        let mut body = response[head_end + 4..].to_vec();
+        if head.contains("Transfer-Encoding: chunked") {
+            let mut rest = &body[..];
+            let mut decoded = Vec::new();
+            loop {
+                let line_end = rest
+                    .windows(2)
+                    .position(|window| window == b"\r\n")
+                    .unwrap();
+                let len = std::str::from_utf8(&rest[..line_end]).unwrap();
+                let len = usize::from_str_radix(len, 16).unwrap();
+                if len == 0 {
+                    assert_eq!(&rest[line_end..], b"\r\n\r\n");
+                    break;
+                }
+                decoded.extend_from_slice(&rest[line_end + 2..line_end + 2 + len]);
+                rest = &rest[line_end + 2 + len + 2..];
+            }
+            body = decoded;
+        }
+        (status, head, body)
+    }
+
+    #[test]
+    fn inspects_and_extracts() {
+        let addr = start(options());
+        let (status, _, body) = post(addr, "/inspect", &fixture());
+        assert_eq!(status, 200);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["entries"].as_array().unwrap().len(), 2);
+
+        let (status, head, body) = post(addr, "/extract", &fixture());
+        assert_eq!(status, 200);
+        assert!(head.contains("Transfer-Encoding: chunked"));
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(body)).unwrap();
+        assert_eq!(zip.len(), 2);
+        let mut read = |name| {
+            let mut data = Vec::new();
+            zip.by_name(name).unwrap().read_to_end(&mut data).unwrap();
+            data
+        };
+        assert_eq!(read("Info"), b"hello");
+        assert_eq!(read("Foo.cs"), b"public class Foo { }\n".repeat(100));
+    }
+
+    #[test]
+    fn refuses_bad_requests() {
+        let addr = start(ServeOptions {
+            max_size: 4096,
+            max_unpacked: 1024,
+            ..options()
+        });
+        assert_eq!(post(addr, "/nowhere", b"").0, 404);
+        assert_eq!(
+            send(addr, b"GET /inspect HTTP/1.1\r\nHost: test\r\n\r\n").0,
+            405
+        );
+        assert_eq!(
+            send(
+                addr,
+                b"POST /inspect HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n"
+            )
+            .0,
+            411
+        );
+        assert_eq!(
+            send(
+                addr,
+                b"POST /inspect HTTP/1.1\r\nContent-Length: 4097\r\n\r\n"
+            )
+            .0,
+            413
+        );
+        // one byte over the limit, all of which the server reads
+        assert_eq!(
+            send(addr, &[&b"GET /"[..], &[b'a'; 16 * 1024 - 4]].concat()).0,
+            431
+        );
+        assert_eq!(post(addr, "/inspect", b"").0, 400);
+        assert_eq!(post(addr, "/inspect", &negative_length()).0, 400);
+        // 2100 bytes of source, over `max_unpacked`
+        assert_eq!(post(addr, "/extract", &fixture()).0, 413);
+        let unsafe_path = raw_archive(&[("../evil", 5, 5)], b"hello");
+        assert_eq!(post(addr, "/extract", &unsafe_path).0, 400);
+    }
+
+    #[test]
+    fn refuses_to_fetch_unless_allowed() {
+        let addr = start(options());
+        let target = format!("/inspect?url=http%3A%2F%2F{}%2F", addr);
+        assert_eq!(post(addr, &target, b"").0, 403);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn refuses_to_fetch_internal_addresses() {
+        let addr = start(ServeOptions {
+            allow_fetch: true,
+            ..options()
+        });
+        let target = format!("/inspect?url=http%3A%2F%2F{}%2F", addr);
+        assert_eq!(post(addr, &target, b"").0, 502);
+    }
+
+    #[test]
+    fn caps_connections() {
+        let addr = start(ServeOptions {
+            max_connections: 1,
+            ..options()
+        });
+        // holds the only slot until it sends its request
+        let mut idle = TcpStream::connect(addr).unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || sender.send(post(addr, "/inspect", &fixture()).0));
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(receiver.try_recv().is_err());
+
+        idle.write_all(b"POST /nowhere HTTP/1.1\r\n\r\n").unwrap();
+        idle.read_to_end(&mut Vec::new()).unwrap();
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap(), 200);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn only_public_addresses_are_fetched() {
+        use std::net::IpAddr;
+
+        for ip in [
+            "0.0.0.0",
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "255.255.255.255",
+            "::",
+            "::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "64:ff9b::a00:1",
+            "fd00::1",
+            "fe80::1",
+            "ff02::1",
+        ] {
+            assert!(!is_public(ip.parse::<IpAddr>().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "1.1.1.1",
+            "93.184.216.34",
+            "::ffff:1.1.1.1",
+            "2606:4700::1111",
+        ] {
+            assert!(is_public(ip.parse::<IpAddr>().unwrap()), "{}", ip);
+        }
+    }
+}
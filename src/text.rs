@@ -0,0 +1,162 @@
+use std::borrow::Cow;
+
+use log::warn;
+
+// how much of an entry is inspected to tell text from binary data
+const SNIFF_LEN: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    // not valid in any supported encoding, decoded with replacement characters
+    Lossy,
+}
+
+impl Encoding {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf8Bom => "UTF-8 (BOM)",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Lossy => "lossy UTF-8",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+// `--normalize-eol` and `--strip-bom`, so trees extracted from mods built on
+// different machines diff cleanly
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Normalize {
+    pub eol: Option<LineEnding>,
+    pub strip_bom: bool,
+}
+
+impl Normalize {
+    pub fn is_enabled(&self) -> bool {
+        self.eol.is_some() || self.strip_bom
+    }
+
+    // only entries that are entirely valid UTF-8 (with or without a BOM) and
+    // look like text are touched, everything else comes back as it was
+    pub fn apply(&self, data: Vec<u8>) -> Vec<u8> {
+        let sniff = &data[..data.len().min(SNIFF_LEN)];
+        if sniff.contains(&0) || std::str::from_utf8(&data).is_err() {
+            return data;
+        }
+
+        let body = match data.strip_prefix(b"\xef\xbb\xbf") {
+            Some(rest) if self.strip_bom => rest,
+            _ => &data,
+        };
+        let eol = match self.eol {
+            Some(eol) => eol,
+            None if body.len() == data.len() => return data,
+            None => return body.to_vec(),
+        };
+
+        let mut out = Vec::with_capacity(body.len());
+        let mut bytes = body.iter().peekable();
+        while let Some(&byte) = bytes.next() {
+            // a lone `\r` is an old Mac line break
+            let line_break = match byte {
+                b'\r' => {
+                    bytes.next_if_eq(&&b'\n');
+                    true
+                }
+                b'\n' => true,
+                _ => false,
+            };
+            match (line_break, eol) {
+                (false, _) => out.push(byte),
+                (true, LineEnding::Lf) => out.push(b'\n'),
+                (true, LineEnding::CrLf) => out.extend(b"\r\n"),
+            }
+        }
+        out
+    }
+}
+
+pub struct DecodedText<'a> {
+    pub text: Cow<'a, str>,
+    pub encoding: Encoding,
+}
+
+// decodes an entry as text, or returns `None` if it looks like binary data.
+// `name` is only used for the warning when falling back to lossy decoding.
+pub fn decode<'a>(name: &str, data: &'a [u8]) -> Option<DecodedText<'a>> {
+    if let Some(rest) = data.strip_prefix(b"\xef\xbb\xbf") {
+        return Some(utf8_or_lossy(name, rest, Encoding::Utf8Bom));
+    }
+    if let Some(rest) = data.strip_prefix(b"\xff\xfe") {
+        return Some(decode_utf16(rest, Encoding::Utf16Le));
+    }
+    if let Some(rest) = data.strip_prefix(b"\xfe\xff") {
+        return Some(decode_utf16(rest, Encoding::Utf16Be));
+    }
+
+    let sniff = &data[..data.len().min(SNIFF_LEN)];
+    if sniff.contains(&0) {
+        // mostly-ASCII UTF-16 without a BOM has a NUL in every other byte
+        return utf16_without_bom(sniff).map(|encoding| decode_utf16(data, encoding));
+    }
+
+    Some(utf8_or_lossy(name, data, Encoding::Utf8))
+}
+
+fn utf8_or_lossy<'a>(name: &str, data: &'a [u8], encoding: Encoding) -> DecodedText<'a> {
+    match std::str::from_utf8(data) {
+        Ok(text) => DecodedText {
+            text: Cow::Borrowed(text),
+            encoding,
+        },
+        Err(_) => {
+            warn!("{} is not valid UTF-8, decoding it lossily", name);
+            DecodedText {
+                text: String::from_utf8_lossy(data),
+                encoding: Encoding::Lossy,
+            }
+        }
+    }
+}
+
+fn decode_utf16(data: &[u8], encoding: Encoding) -> DecodedText<'static> {
+    let units = data.chunks_exact(2).map(|pair| match encoding {
+        Encoding::Utf16Be => u16::from_be_bytes([pair[0], pair[1]]),
+        _ => u16::from_le_bytes([pair[0], pair[1]]),
+    });
+    let text: String = char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+    DecodedText {
+        text: Cow::Owned(text),
+        encoding,
+    }
+}
+
+fn utf16_without_bom(sniff: &[u8]) -> Option<Encoding> {
+    let pairs = sniff.len() / 2;
+    if pairs == 0 {
+        return None;
+    }
+    let even_nuls = sniff.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_nuls = sniff.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+    // demand that nearly every code unit looks like ASCII before guessing
+    let threshold = pairs * 9 / 10;
+    if odd_nuls >= threshold && even_nuls == 0 {
+        Some(Encoding::Utf16Le)
+    } else if even_nuls >= threshold && odd_nuls == 0 {
+        Some(Encoding::Utf16Be)
+    } else {
+        None
+    }
+}
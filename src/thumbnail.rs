@@ -0,0 +1,94 @@
+use log::warn;
+
+use crate::names::extension;
+use crate::rawimg::{self, RawImage};
+use crate::transform::resize;
+use crate::TModError;
+
+// thumbnails go into a tree of their own next to the extracted entries
+pub const THUMBNAIL_DIR: &str = "_thumbs";
+
+// where the thumbnail of a texture goes, always as a PNG
+pub fn thumbnail_name(name: &str) -> String {
+    let stem = match name.rsplit_once('.') {
+        Some((stem, ext)) if !ext.contains('/') => stem,
+        _ => name,
+    };
+    format!("{}/{}.png", THUMBNAIL_DIR, stem)
+}
+
+// a PNG of the texture scaled to fit within `size`x`size`, keeping its
+// aspect ratio. textures that already fit are kept at their size. returns
+// `None` for entries that aren't textures, or that can't be decoded.
+pub fn thumbnail(name: &str, data: &[u8], size: u32) -> Result<Option<Vec<u8>>, TModError> {
+    if !cfg!(feature = "images") {
+        return Err(TModError::FeatureDisabled("images"));
+    }
+    let decoded =
+        match extension(name).as_deref() {
+            Some("rawimg") => rawimg::parse(name, data)
+                .map(|image| (image.width, image.height, image.rgba.to_vec())),
+            #[cfg(feature = "images")]
+            Some("png") => decode_png(data)
+                .map_err(|e| TModError::InvalidImage(name.to_string(), e.to_string())),
+            _ => return Ok(None),
+        };
+    let (width, height, rgba) = match decoded {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            warn!("skipping thumbnail: {}", e);
+            return Ok(None);
+        }
+    };
+
+    let (new_width, new_height) = fit(width, height, size);
+    let rgba = if (new_width, new_height) == (width, height) {
+        rgba
+    } else {
+        resize(&rgba, (width, height), (new_width, new_height))
+    };
+    let image = RawImage {
+        width: new_width,
+        height: new_height,
+        rgba: &rgba,
+    };
+    image.to_png().map(Some)
+}
+
+fn fit(width: u32, height: u32, size: u32) -> (u32, u32) {
+    let longest = width.max(height);
+    if longest <= size {
+        return (width, height);
+    }
+    let scale = |side: u32| ((side as u64 * size as u64 / longest as u64) as u32).max(1);
+    (scale(width), scale(height))
+}
+
+// any PNG as 8-bit RGBA
+#[cfg(feature = "images")]
+fn decode_png(data: &[u8]) -> Result<(u32, u32, Vec<u8>), png::DecodingError> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+    buf.truncate(info.buffer_size());
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => buf,
+        png::ColorType::Rgb => buf
+            .chunks_exact(3)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+            .collect(),
+        // indexed images are expanded to RGB(A) by the decoder
+        png::ColorType::Grayscale | png::ColorType::Indexed => buf
+            .iter()
+            .flat_map(|&gray| [gray, gray, gray, 255])
+            .collect(),
+    };
+    Ok((info.width, info.height, rgba))
+}
@@ -0,0 +1,92 @@
+use std::path::{Path, PathBuf};
+
+use log::trace;
+
+use crate::TModError;
+
+// an external program some feature runs. nothing else needs it, so it is
+// looked for when such a feature is asked for, to fail up front with what to
+// do about it rather than with a spawn error halfway through.
+pub struct Tool {
+    pub name: &'static str,
+    // any of these does the job, the first one found is used
+    pub programs: &'static [&'static str],
+    pub needed_for: &'static str,
+    // how to do without it
+    pub instead: &'static str,
+}
+
+pub const GIT: Tool = Tool {
+    name: "git",
+    programs: &["git"],
+    needed_for: "`--git-init`",
+    instead: "leave out `--git-init`",
+};
+
+pub const AUDIO_ENCODER: Tool = Tool {
+    name: "oggenc or ffmpeg",
+    programs: &["oggenc", "ffmpeg"],
+    needed_for: "`pack --reencode-audio`",
+    instead: "leave out `--reencode-audio`",
+};
+
+pub const STEAMCMD: Tool = Tool {
+    name: "steamcmd",
+    programs: &["steamcmd"],
+    needed_for: "`fetch` when Steam hasn't downloaded the item",
+    instead: "subscribe to the item in Steam, or download it and pass the .tmod file",
+};
+
+pub const ALL: &[&Tool] = &[&GIT, &AUDIO_ENCODER, &STEAMCMD];
+
+impl Tool {
+    pub fn find(&self) -> Option<PathBuf> {
+        self.programs.iter().find_map(|program| which(program))
+    }
+
+    pub fn require(&self) -> Result<PathBuf, TModError> {
+        self.find().ok_or(TModError::ToolMissing(
+            self.name,
+            self.needed_for,
+            self.instead,
+        ))
+    }
+}
+
+// where `program` would be run from, searching the PATH like a shell does
+pub fn which(program: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    // Windows runs `git` as `git.exe`, or with any other extension in PATHEXT
+    let extensions = match cfg!(windows) {
+        true => std::env::var("PATHEXT")
+            .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string())
+            .split(';')
+            .map(str::to_string)
+            .collect(),
+        false => vec![String::new()],
+    };
+    for dir in std::env::split_paths(&path) {
+        for extension in &extensions {
+            let candidate = dir.join(format!("{}{}", program, extension));
+            if is_executable(&candidate) {
+                trace!("found {} at {:?}", program, candidate);
+                return Some(candidate);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata().map_or(false, |metadata| {
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    })
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
@@ -0,0 +1,177 @@
+use log::warn;
+
+use crate::names::extension;
+use crate::rawimg::{self, RawImage};
+use crate::route::{compile_glob, GlobMatcher};
+use crate::TModError;
+
+// one stage of a transform. however they are written on the command line,
+// the steps of a transform always run in the order of this enum: decode the
+// texture, unpremultiply, resize, then encode it again (as PNG with `Png`,
+// otherwise back to rawimg)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Step {
+    Unpremultiply,
+    Resize(u32, u32),
+    Png,
+}
+
+impl Step {
+    pub fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "png" => Step::Png,
+            "unpremultiply" => Step::Unpremultiply,
+            _ => {
+                let size = value.strip_prefix("resize=")?;
+                let (width, height) = size.split_once('x')?;
+                let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+                if width == 0 || height == 0 {
+                    return None;
+                }
+                Step::Resize(width, height)
+            }
+        })
+    }
+}
+
+// converts the contents of entries matching a glob while extracting them,
+// written on the command line as `<glob>=<step>:<step>...`
+pub struct Transform {
+    pub pattern: String,
+    matcher: GlobMatcher,
+    steps: Vec<Step>,
+}
+
+impl Transform {
+    pub fn parse(value: &str) -> Result<Self, TModError> {
+        let invalid = |reason: &str| TModError::InvalidTransform(value.to_string(), reason.into());
+        let (pattern, steps) = value
+            .split_once('=')
+            .ok_or_else(|| invalid("expected `<glob>=<step>:<step>...`"))?;
+        let (pattern, steps) = (pattern.trim(), steps.trim());
+
+        let mut parsed = Vec::new();
+        for step in steps.split(':') {
+            let step = Step::parse(step.trim()).ok_or_else(|| {
+                invalid("steps are png, unpremultiply and resize=<width>x<height>")
+            })?;
+            if parsed
+                .iter()
+                .any(|other| std::mem::discriminant(other) == std::mem::discriminant(&step))
+            {
+                return Err(invalid("each step can only be given once"));
+            }
+            parsed.push(step);
+        }
+        parsed.sort();
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            matcher: compile_glob(pattern)?,
+            steps: parsed,
+        })
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        self.matcher.is_match(name)
+    }
+
+    // the name the entry is written under
+    pub fn output_name(&self, name: &str) -> String {
+        let is_rawimg = extension(name).as_deref() == Some("rawimg");
+        match name.strip_suffix(".rawimg") {
+            Some(stem) if is_rawimg && self.steps.contains(&Step::Png) => format!("{}.png", stem),
+            _ => name.to_string(),
+        }
+    }
+
+    // runs every step on one entry's contents. only `.rawimg` textures can be
+    // transformed, anything else matching the glob is written unchanged.
+    pub fn apply(&self, name: &str, data: Vec<u8>) -> Result<Vec<u8>, TModError> {
+        if extension(name).as_deref() != Some("rawimg") {
+            warn!("not transforming {}: only .rawimg entries can be", name);
+            return Ok(data);
+        }
+
+        let image = rawimg::parse(name, &data)?;
+        let (mut width, mut height) = (image.width, image.height);
+        let mut rgba = image.rgba.to_vec();
+        let mut png = false;
+        for step in &self.steps {
+            match *step {
+                Step::Unpremultiply => unpremultiply(&mut rgba),
+                Step::Resize(new_width, new_height) => {
+                    rgba = resize(&rgba, (width, height), (new_width, new_height));
+                    (width, height) = (new_width, new_height);
+                }
+                Step::Png => png = true,
+            }
+        }
+
+        let image = RawImage {
+            width,
+            height,
+            rgba: &rgba,
+        };
+        if png {
+            image.to_png()
+        } else {
+            Ok(image.to_rawimg())
+        }
+    }
+}
+
+// the first transform matching an entry, like routes
+pub fn find<'a>(transforms: &'a [Transform], name: &str) -> Option<&'a Transform> {
+    transforms.iter().find(|transform| transform.matches(name))
+}
+
+// textures are stored with their color multiplied by their alpha, divide it
+// out again. fully transparent pixels become transparent black.
+fn unpremultiply(rgba: &mut [u8]) {
+    for pixel in rgba.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel[..3] {
+            *channel = match alpha {
+                0 => 0,
+                _ => ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8,
+            };
+        }
+    }
+}
+
+// averages every source pixel a destination pixel covers (at least one), so
+// downscaling doesn't skip detail and upscaling repeats pixels. integer math
+// only, the result is the same on every platform.
+pub fn resize(rgba: &[u8], from: (u32, u32), to: (u32, u32)) -> Vec<u8> {
+    let span = |index: u32, from: u32, to: u32| {
+        let start = (index as u64 * from as u64 / to as u64) as usize;
+        let end = ((index as u64 + 1) * from as u64 / to as u64) as usize;
+        start..end.max(start + 1).min(from as usize)
+    };
+
+    let mut out = Vec::with_capacity(to.0 as usize * to.1 as usize * 4);
+    if from.0 == 0 || from.1 == 0 {
+        out.resize(to.0 as usize * to.1 as usize * 4, 0);
+        return out;
+    }
+    for y in 0..to.1 {
+        let rows = span(y, from.1, to.1);
+        for x in 0..to.0 {
+            let columns = span(x, from.0, to.0);
+            let mut sum = [0u64; 4];
+            let mut count = 0u64;
+            for row in rows.clone() {
+                for column in columns.clone() {
+                    let at = (row * from.0 as usize + column) * 4;
+                    for (total, value) in sum.iter_mut().zip(&rgba[at..at + 4]) {
+                        *total += *value as u64;
+                    }
+                    count += 1;
+                }
+            }
+            out.extend(sum.map(|total| ((total + count / 2) / count) as u8));
+        }
+    }
+    out
+}
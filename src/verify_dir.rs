@@ -0,0 +1,150 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use log::{debug, trace};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+
+use crate::entry_log::EntryRecord;
+use crate::output::{self, OutputFormat};
+use crate::TModError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum Change {
+    Changed,
+    Removed,
+    Added,
+}
+
+impl Change {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Change::Changed => "changed",
+            Change::Removed => "removed",
+            Change::Added => "added",
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ChangedFile {
+    pub path: String,
+    pub change: Change,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct DirReport {
+    pub unchanged: usize,
+    pub changes: Vec<ChangedFile>,
+}
+
+// loads a manifest written by `--emit-entries`, either as JSON lines or as a
+// single JSON array of the same records
+pub fn load_manifest(path: impl AsRef<Path>) -> Result<Vec<EntryRecord>, TModError> {
+    let contents = std::fs::read_to_string(path)?;
+    if contents.trim_start().starts_with('[') {
+        return Ok(serde_json::from_str(&contents)?);
+    }
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+// compares what is on disk in `dir` against a manifest of an earlier
+// extraction, by entry name and content hash
+pub fn verify_dir(dir: &Path, manifest: &[EntryRecord]) -> Result<DirReport, TModError> {
+    let expected: BTreeMap<&str, &str> = manifest
+        .iter()
+        .map(|record| (record.name.as_str(), record.sha1.as_str()))
+        .collect();
+
+    let mut on_disk = BTreeSet::new();
+    collect_files(dir, dir, &mut on_disk)?;
+    debug!(
+        "{} files on disk, {} in manifest",
+        on_disk.len(),
+        expected.len()
+    );
+
+    let mut unchanged = 0;
+    let mut changes = Vec::new();
+    for (name, sha1) in &expected {
+        if !on_disk.contains(*name) {
+            changes.push(ChangedFile {
+                path: name.to_string(),
+                change: Change::Removed,
+            });
+            continue;
+        }
+        trace!("hashing file: {}", name);
+        let data = std::fs::read(dir.join(name))?;
+        if hex::encode(Sha1::digest(&data)) == *sha1 {
+            unchanged += 1;
+        } else {
+            changes.push(ChangedFile {
+                path: name.to_string(),
+                change: Change::Changed,
+            });
+        }
+    }
+    for name in on_disk {
+        if !expected.contains_key(name.as_str()) {
+            changes.push(ChangedFile {
+                path: name,
+                change: Change::Added,
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(DirReport { unchanged, changes })
+}
+
+// every file below `dir`, relative to `root` and with `/` separators to match
+// entry names
+fn collect_files(root: &Path, dir: &Path, files: &mut BTreeSet<String>) -> Result<(), TModError> {
+    for entry in std::fs::read_dir(dir)? {
+        let path: PathBuf = entry?.path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            let components: Vec<_> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            files.insert(components.join("/"));
+        }
+    }
+    Ok(())
+}
+
+pub fn print_report(dir: &Path, report: &DirReport, format: OutputFormat) -> Result<(), TModError> {
+    if format != OutputFormat::Text {
+        println!("{}", output::render(format, "verify-dir", report)?);
+        return Ok(());
+    }
+
+    for changed in &report.changes {
+        println!("{:<8} {}", changed.change.label(), changed.path);
+    }
+    let count = |change| report.changes.iter().filter(|c| c.change == change).count();
+    println!(
+        "{}: {} unchanged, {} changed, {} removed, {} added",
+        dir.display(),
+        report.unchanged,
+        count(Change::Changed),
+        count(Change::Removed),
+        count(Change::Added)
+    );
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use std::io::Write;
+
+use binary_rw::BinaryReader;
+use log::{debug, trace};
+
+use crate::source;
+use crate::text::{self, Encoding};
+use crate::{read_entry_table, read_file_data, read_header, TModError, TModReader};
+
+// prints a single entry as text. only that entry is needed, so local files
+// are mapped rather than read.
+pub fn view(path: String, entry_name: &str) -> Result<(), TModError> {
+    let mut reader = TModReader::open_mapped(&path)?;
+    let data = reader.read(entry_name)?;
+    let decoded = text::decode(entry_name, &data)
+        .ok_or_else(|| TModError::BinaryEntry(entry_name.to_string()))?;
+    debug!("{} decoded as {}", entry_name, decoded.encoding.label());
+
+    print!("{}", decoded.text);
+    if !decoded.text.ends_with('\n') {
+        println!();
+    }
+
+    Ok(())
+}
+
+// writes a single entry to stdout byte for byte, decompressing it as it goes
+// when the mod is mapped
+pub fn cat(path: String, entry_name: &str) -> Result<(), TModError> {
+    let mut reader = TModReader::open_mapped(&path)?;
+    let index = reader
+        .entries()
+        .iter()
+        .position(|file| file.name == entry_name)
+        .ok_or_else(|| TModError::EntryNotFound(entry_name.to_string()))?;
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    // stdout is buffered, so the pipe may only turn out to be closed when
+    // the rest is flushed
+    let result = reader
+        .copy_entry(index, &mut out)
+        .and_then(|_| Ok(out.flush()?));
+    match result {
+        // `cat ... | head` closing the pipe early isn't a failure
+        Err(TModError::IoError(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+// prints every line of every text entry that contains `pattern`
+pub fn grep(path: String, pattern: &str, ignore_case: bool) -> Result<(), TModError> {
+    trace!("opening file: {}", path);
+    let mut stream = source::open(&path)?;
+    let mut reader = BinaryReader::new(&mut stream, binary_rw::Endian::Little);
+
+    read_header(&mut reader, None)?;
+    let file_entries = read_entry_table(&mut reader)?;
+
+    let pattern = if ignore_case {
+        pattern.to_lowercase()
+    } else {
+        pattern.to_string()
+    };
+
+    let mut matches = 0;
+    for file in &file_entries {
+        let data = read_file_data(&mut reader, file)?;
+        let decoded = match text::decode(&file.name, &data) {
+            Some(decoded) => decoded,
+            None => {
+                trace!("skipping binary entry: {}", file.name);
+                continue;
+            }
+        };
+        if decoded.encoding != Encoding::Utf8 {
+            debug!("{} decoded as {}", file.name, decoded.encoding.label());
+        }
+
+        for (number, line) in decoded.text.lines().enumerate() {
+            let found = if ignore_case {
+                line.to_lowercase().contains(&pattern)
+            } else {
+                line.contains(&pattern)
+            };
+            if found {
+                matches += 1;
+                println!("{}:{}: {}", file.name, number + 1, line);
+            }
+        }
+    }
+    debug!("{} matching lines", matches);
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::cancel::CancellationToken;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+struct State {
+    done: usize,
+    total: usize,
+    // the entries being extracted and when they were started, oldest first
+    current: Vec<(String, Instant)>,
+    finished: bool,
+    // why the run was cancelled and how far it got, once it was
+    timed_out: Option<String>,
+}
+
+// enforces `--timeout` and `--entry-timeout` from a separate thread. when a
+// deadline passes, the watchdog cancels the run through its token, so it
+// stops at the next chunk and cleans up after itself like it does when it is
+// cancelled by hand. a write stuck on a dead network mount can't be
+// interrupted, so the run only stops once that write returns.
+pub struct Watchdog {
+    state: Arc<Mutex<State>>,
+}
+
+impl Watchdog {
+    pub fn start(
+        overall: Option<Duration>,
+        per_entry: Option<Duration>,
+        cancel: CancellationToken,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(State {
+            done: 0,
+            total: 0,
+            current: Vec::new(),
+            finished: false,
+            timed_out: None,
+        }));
+        if overall.is_some() || per_entry.is_some() {
+            let state = Arc::clone(&state);
+            thread::spawn(move || watch(state, overall, per_entry, cancel));
+        }
+        Self { state }
+    }
+
+    // what a run that was stopped for taking too long got done, for
+    // `TModError::Timeout`
+    pub fn timed_out(&self) -> Option<String> {
+        self.state.lock().unwrap().timed_out.clone()
+    }
+
+    // the number of files that will be extracted, once it is known
+    pub fn set_total(&self, total: usize) {
+        self.state.lock().unwrap().total = total;
+    }
+
+    pub fn begin(&self, name: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.current.push((name.to_string(), Instant::now()));
+    }
+
+    pub fn end(&self, name: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.done += 1;
+        if let Some(position) = state
+            .current
+            .iter()
+            .position(|(current, _)| current == name)
+        {
+            state.current.remove(position);
+        }
+    }
+}
+
+// stops watching however the run ends, including errors
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.state.lock().unwrap().finished = true;
+    }
+}
+
+fn watch(
+    state: Arc<Mutex<State>>,
+    overall: Option<Duration>,
+    per_entry: Option<Duration>,
+    cancel: CancellationToken,
+) {
+    let started = Instant::now();
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let mut state = state.lock().unwrap();
+        if state.finished {
+            return;
+        }
+
+        let reason = match (state.current.first(), overall, per_entry) {
+            (Some((name, _)), Some(overall), _) if started.elapsed() >= overall => {
+                format!(
+                    "the run took longer than {:?} while extracting `{}`",
+                    overall, name
+                )
+            }
+            (None, Some(overall), _) if started.elapsed() >= overall => {
+                format!("the run took longer than {:?}", overall)
+            }
+            (Some((name, since)), _, Some(per_entry)) if since.elapsed() >= per_entry => {
+                format!("`{}` took longer than {:?}", name, per_entry)
+            }
+            _ => continue,
+        };
+
+        state.timed_out = Some(format!(
+            "{}, extracted {} of {} files",
+            reason, state.done, state.total
+        ));
+        cancel.cancel();
+        return;
+    }
+}
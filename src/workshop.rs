@@ -0,0 +1,203 @@
+use std::{path::PathBuf, process::Command};
+
+use log::{debug, trace};
+
+use crate::locate::{self, TMODLOADER_APP_ID};
+use crate::tools;
+use crate::{collect_mods, TModError};
+
+// the public Steam Web API call that describes workshop items, no key needed
+#[cfg(feature = "http")]
+const DETAILS_URL: &str =
+    "https://api.steampowered.com/ISteamRemoteStorage/GetPublishedFileDetails/v1/";
+
+// a workshop item id, given as is or as a link to its page, like
+// `https://steamcommunity.com/sharedfiles/filedetails/?id=2824688072`
+pub fn parse_id(item: &str) -> Result<u64, TModError> {
+    let id = match item.split_once("id=") {
+        Some((_, rest)) => rest.split(|c: char| !c.is_ascii_digit()).next(),
+        None => item.trim_end_matches('/').rsplit('/').next(),
+    };
+    id.and_then(|id| id.parse().ok()).ok_or_else(|| {
+        TModError::WorkshopError(
+            item.to_string(),
+            "not a workshop item id or link".to_string(),
+        )
+    })
+}
+
+// what the Web API says about a workshop item
+struct Details {
+    // a direct download, which only some items have
+    file_url: Option<String>,
+    // when the item was last updated, in seconds since the Unix epoch
+    time_updated: Option<u64>,
+}
+
+// where `fetch` keeps what it downloads: steamcmd's install directory, and
+// mods downloaded from a url, named after their hash (see
+// `source::open_cached`)
+pub fn cache_dir() -> PathBuf {
+    std::env::temp_dir().join("tmod-unpacker-workshop")
+}
+
+// where to read a workshop mod from, as an input for `unpack`. the cheapest
+// source wins:
+//
+// - a copy Steam already downloaded into one of its libraries
+// - the item's direct download url, which the Web API has for some items
+//   (needs the `http` feature). read through `cache_dir`, only its header is
+//   requested when the cached copy has the same hash.
+// - steamcmd, which can download the workshop items of free games like
+//   tModLoader without an account, into `cache_dir`. an earlier download is
+//   used again as long as the item hasn't been updated since.
+//
+// with `refresh`, copies that are already there are ignored and the item is
+// downloaded again.
+pub fn resolve(item: &str, refresh: bool) -> Result<String, TModError> {
+    let id = parse_id(item)?;
+    if let (false, Some(path)) = (refresh, installed(id)?) {
+        println!("Found workshop item {} in {}", id, path.display());
+        return Ok(path.to_string_lossy().into_owned());
+    }
+    let time_updated = match details(id) {
+        Ok(Details {
+            file_url: Some(url),
+            ..
+        }) => {
+            println!("Fetching workshop item {} from {}", id, url);
+            return Ok(url);
+        }
+        Ok(details) => {
+            debug!("workshop item {} has no direct download", id);
+            details.time_updated
+        }
+        // steamcmd may still get it, and reports its own errors
+        Err(e) => {
+            debug!("could not look up workshop item {}: {}", id, e);
+            None
+        }
+    };
+    steamcmd(id, time_updated, refresh)
+}
+
+// the newest copy of the item in the workshop folder of a Steam library
+fn installed(id: u64) -> Result<Option<std::path::PathBuf>, TModError> {
+    let mut candidates = Vec::new();
+    for dir in locate::mods_dirs() {
+        let dir = dir.join(id.to_string());
+        if dir.is_dir() {
+            trace!("searching for workshop item {}: {:?}", id, dir);
+            collect_mods(&dir, &mut candidates)?;
+        }
+    }
+    Ok(locate::newest(candidates))
+}
+
+#[cfg(feature = "http")]
+fn details(id: u64) -> Result<Details, TModError> {
+    let error = |message: String| TModError::WorkshopError(id.to_string(), message);
+
+    trace!("looking up workshop item {}", id);
+    let body = ureq::post(DETAILS_URL)
+        .send_form(&[("itemcount", "1"), ("publishedfileids[0]", &id.to_string())])
+        .map_err(|e| error(e.to_string()))?
+        .into_string()?;
+    let response: serde_json::Value = serde_json::from_str(&body)?;
+    let details = &response["response"]["publishedfiledetails"][0];
+    if details["result"].as_u64() != Some(1) {
+        return Err(error("no such item".to_string()));
+    }
+    // a number in the response
+    if details["consumer_app_id"].as_u64() != TMODLOADER_APP_ID.parse().ok() {
+        return Err(error(format!(
+            "`{}` isn't a tModLoader mod",
+            details["title"].as_str().unwrap_or_default()
+        )));
+    }
+    Ok(Details {
+        file_url: details["file_url"]
+            .as_str()
+            .filter(|url| !url.is_empty())
+            .map(str::to_string),
+        time_updated: details["time_updated"].as_u64().filter(|time| *time > 0),
+    })
+}
+
+#[cfg(not(feature = "http"))]
+fn details(_id: u64) -> Result<Details, TModError> {
+    Err(TModError::FeatureDisabled("http"))
+}
+
+// `time_updated` is what the Web API says, if it could be asked. it is
+// written next to each download, so a download with the same time is known
+// to be current and steamcmd isn't run at all.
+fn steamcmd(id: u64, time_updated: Option<u64>, refresh: bool) -> Result<String, TModError> {
+    let install_dir = cache_dir();
+    let id = id.to_string();
+    let dir = install_dir
+        .join("steamapps")
+        .join("workshop")
+        .join("content")
+        .join(TMODLOADER_APP_ID)
+        .join(&id);
+    let stamp = install_dir.join(format!("{}.updated", id));
+
+    let downloaded = std::fs::read_to_string(&stamp)
+        .ok()
+        .and_then(|text| text.trim().parse::<u64>().ok());
+    if !refresh && time_updated.is_some() && downloaded == time_updated {
+        let mut candidates = Vec::new();
+        if dir.is_dir() {
+            collect_mods(&dir, &mut candidates)?;
+        }
+        if let Some(path) = locate::newest(candidates) {
+            println!(
+                "Workshop item {} is unchanged since it was downloaded to {}",
+                id,
+                path.display()
+            );
+            return Ok(path.to_string_lossy().into_owned());
+        }
+    }
+
+    let program = tools::STEAMCMD.require()?;
+    println!("Downloading workshop item {} with steamcmd", id);
+    // the install directory has to be set before logging in
+    let status = Command::new(program)
+        .arg("+force_install_dir")
+        .arg(&install_dir)
+        .args([
+            "+login",
+            "anonymous",
+            "+workshop_download_item",
+            TMODLOADER_APP_ID,
+            &id,
+            "+quit",
+        ])
+        .status()
+        .map_err(|e| {
+            TModError::WorkshopError(id.clone(), format!("could not run steamcmd: {}", e))
+        })?;
+
+    // steamcmd exits with 0 even when the download failed, so only the
+    // files tell
+    let mut candidates = Vec::new();
+    if dir.is_dir() {
+        collect_mods(&dir, &mut candidates)?;
+    }
+    let path = locate::newest(candidates).ok_or_else(|| {
+        TModError::WorkshopError(
+            id.clone(),
+            format!("steamcmd didn't download a mod ({})", status),
+        )
+    })?;
+    match time_updated {
+        Some(time_updated) => std::fs::write(&stamp, time_updated.to_string())?,
+        // nothing to compare the next download against
+        None => {
+            let _ = std::fs::remove_file(&stamp);
+        }
+    }
+    Ok(path.to_string_lossy().into_owned())
+}
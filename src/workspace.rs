@@ -0,0 +1,307 @@
+use std::path::{Path, PathBuf};
+
+use log::trace;
+
+use crate::audio;
+use crate::pack::{self, PackOptions};
+use crate::tools;
+use crate::TModError;
+
+// what `pack --workspace` looks for in the directory it is given
+pub const WORKSPACE_FILE: &str = "tmodpack.toml";
+
+// the keys allowed at the top of the file and in each `[[mod]]`, with the
+// kind of value each one takes
+const TOP_LEVEL_KEYS: &[(&str, &str)] = &[
+    ("tml-version", "a string"),
+    ("output-dir", "a string"),
+    ("optimize-png", "`true` or `false`"),
+    ("reencode-audio", "a string"),
+    ("shared", "an array of strings"),
+];
+const MOD_KEYS: &[(&str, &str)] = &[
+    ("path", "a string"),
+    ("name", "a string"),
+    ("version", "a string"),
+];
+
+// several mods built together with the same settings, described by a
+// `tmodpack.toml` like
+//
+//     tml-version = "2023.6.25.1"
+//     output-dir = "dist"
+//     optimize-png = true
+//     shared = ["SharedAssets"]
+//
+//     [[mod]]
+//     path = "CoreMod"
+//
+//     [[mod]]
+//     path = "CoreModAddon"
+//     version = "1.2"
+//
+// paths are relative to the file. every mod gets the files below the
+// `shared` directories it doesn't have itself.
+pub struct Workspace {
+    pub tmodloader_version: Option<String>,
+    pub output_dir: Option<PathBuf>,
+    pub optimize_png: bool,
+    pub reencode_audio: Option<String>,
+    pub shared: Vec<PathBuf>,
+    pub mods: Vec<WorkspaceMod>,
+}
+
+pub struct WorkspaceMod {
+    pub path: PathBuf,
+    // like `pack --name` and `--version`, from the directory by default
+    pub name: Option<String>,
+    pub version: Option<String>,
+}
+
+pub struct WorkspaceOptions {
+    // the workspace file, or the directory it is in
+    pub path: PathBuf,
+    // these win over the file
+    pub tmodloader_version: Option<String>,
+    pub optimize_png: bool,
+    pub reencode_audio: Option<String>,
+}
+
+enum Value {
+    String(String),
+    Bool(bool),
+    Array(Vec<String>),
+}
+
+// the keys of a table with their values and the line each one is on
+type Table = Vec<(String, Value, usize)>;
+
+impl Workspace {
+    pub fn load(path: &Path) -> Result<Self, TModError> {
+        trace!("reading workspace: {:?}", path);
+        let text = std::fs::read_to_string(path)?;
+        let invalid = |message: String| TModError::InvalidWorkspace(path.to_path_buf(), message);
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let mut tables = parse(&text).map_err(invalid)?.into_iter();
+        let mut workspace = Workspace {
+            tmodloader_version: None,
+            output_dir: None,
+            optimize_png: false,
+            reencode_audio: None,
+            shared: Vec::new(),
+            mods: Vec::new(),
+        };
+        let (_, top_level) = tables.next().expect("the top level");
+        for (key, value, line) in top_level {
+            match (key.as_str(), value) {
+                ("tml-version", Value::String(version)) => {
+                    workspace.tmodloader_version = Some(version)
+                }
+                ("output-dir", Value::String(output_dir)) => {
+                    workspace.output_dir = Some(dir.join(output_dir))
+                }
+                ("optimize-png", Value::Bool(optimize_png)) => {
+                    workspace.optimize_png = optimize_png
+                }
+                ("reencode-audio", Value::String(reencode)) => {
+                    workspace.reencode_audio = Some(reencode)
+                }
+                ("shared", Value::Array(shared)) => {
+                    workspace.shared = shared.iter().map(|shared| dir.join(shared)).collect()
+                }
+                (key, _) => return Err(invalid(unexpected(key, line, TOP_LEVEL_KEYS))),
+            }
+        }
+        for (header_line, table) in tables {
+            let mut path = None;
+            let mut workspace_mod = WorkspaceMod {
+                path: PathBuf::new(),
+                name: None,
+                version: None,
+            };
+            for (key, value, line) in table {
+                match (key.as_str(), value) {
+                    ("path", Value::String(mod_path)) => path = Some(dir.join(mod_path)),
+                    ("name", Value::String(name)) => workspace_mod.name = Some(name),
+                    ("version", Value::String(version)) => workspace_mod.version = Some(version),
+                    (key, _) => return Err(invalid(unexpected(key, line, MOD_KEYS))),
+                }
+            }
+            workspace_mod.path = path.ok_or_else(|| {
+                invalid(format!("line {}: `[[mod]]` without a `path`", header_line))
+            })?;
+            workspace.mods.push(workspace_mod);
+        }
+        if workspace.mods.is_empty() {
+            return Err(invalid("there is no `[[mod]]` to pack".to_string()));
+        }
+        Ok(workspace)
+    }
+}
+
+// packs every mod of a workspace in the order they are listed, stopping at
+// the first that fails
+pub fn pack_workspace(options: &WorkspaceOptions) -> Result<(), TModError> {
+    let path = match options.path.is_dir() {
+        true => options.path.join(WORKSPACE_FILE),
+        false => options.path.clone(),
+    };
+    let workspace = Workspace::load(&path)?;
+
+    let tmodloader_version = options
+        .tmodloader_version
+        .clone()
+        .or(workspace.tmodloader_version)
+        .ok_or(TModError::MissingValue("--tml-version"))?;
+    let reencode_audio = match options
+        .reencode_audio
+        .as_ref()
+        .or(workspace.reencode_audio.as_ref())
+    {
+        Some(reencode) => {
+            tools::AUDIO_ENCODER.require()?;
+            Some(audio::Reencode::parse(reencode)?)
+        }
+        None => None,
+    };
+    for shared in &workspace.shared {
+        if !shared.is_dir() {
+            return Err(TModError::InvalidWorkspace(
+                path.clone(),
+                format!("the shared directory {:?} doesn't exist", shared),
+            ));
+        }
+    }
+    if let Some(output_dir) = &workspace.output_dir {
+        std::fs::create_dir_all(output_dir)?;
+    }
+
+    for workspace_mod in &workspace.mods {
+        println!("Packing {}", workspace_mod.path.display());
+        pack::pack(&PackOptions {
+            input: workspace_mod.path.clone(),
+            name: workspace_mod.name.clone(),
+            version: workspace_mod.version.clone(),
+            tmodloader_version: tmodloader_version.clone(),
+            output: None,
+            output_dir: workspace.output_dir.clone(),
+            shared: workspace.shared.clone(),
+            optimize_png: options.optimize_png || workspace.optimize_png,
+            reencode_audio,
+        })?;
+    }
+    println!("Packed {} mods", workspace.mods.len());
+    Ok(())
+}
+
+fn unexpected(key: &str, line: usize, keys: &[(&str, &str)]) -> String {
+    match keys.iter().find(|(known, _)| *known == key) {
+        Some((_, kind)) => format!("line {}: `{}` must be {}", line, key, kind),
+        None => format!("line {}: unknown key `{}`", line, key),
+    }
+}
+
+// the top level and each `[[mod]]` table, with the line the table starts
+// on. only as much TOML as a workspace needs: `key = value` lines with
+// strings, booleans or arrays of strings as values, `[[mod]]` headers and
+// comments.
+fn parse(text: &str) -> Result<Vec<(usize, Table)>, String> {
+    let mut tables = vec![(0, Table::new())];
+    for (number, line) in text.lines().enumerate() {
+        let number = number + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            let header = line.split('#').next().unwrap_or_default();
+            if header.replace(char::is_whitespace, "") != "[[mod]]" {
+                return Err(format!(
+                    "line {}: unknown table `{}`, only `[[mod]]` is allowed",
+                    number,
+                    header.trim()
+                ));
+            }
+            tables.push((number, Vec::new()));
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected `key = value`", number))?;
+        let key = key.trim();
+        let (value, rest) =
+            parse_value(value.trim_start()).map_err(|e| format!("line {}: {}", number, e))?;
+        let rest = rest.trim_start();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return Err(format!(
+                "line {}: unexpected `{}` after the value",
+                number, rest
+            ));
+        }
+        let (_, table) = tables.last_mut().expect("the top level");
+        if table.iter().any(|(existing, ..)| existing == key) {
+            return Err(format!("line {}: `{}` is set twice", number, key));
+        }
+        table.push((key.to_string(), value, number));
+    }
+    Ok(tables)
+}
+
+// a value and what follows it on the line
+fn parse_value(text: &str) -> Result<(Value, &str), String> {
+    if let Some(rest) = text.strip_prefix("true") {
+        return Ok((Value::Bool(true), rest));
+    }
+    if let Some(rest) = text.strip_prefix("false") {
+        return Ok((Value::Bool(false), rest));
+    }
+    if let Some(mut rest) = text.strip_prefix('[') {
+        let mut values = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(values), after));
+            }
+            let (value, after) = parse_string(rest)?;
+            values.push(value);
+            rest = after.trim_start();
+            match rest.strip_prefix(',') {
+                Some(after) => rest = after,
+                None if rest.starts_with(']') => {}
+                None => return Err("expected `,` or `]` in the array".to_string()),
+            }
+        }
+    }
+    let (value, rest) = parse_string(text)?;
+    Ok((Value::String(value), rest))
+}
+
+// a "basic" string with the common escapes, or a 'literal' one without any
+fn parse_string(text: &str) -> Result<(String, &str), String> {
+    let expected = || "expected a string, `true`, `false` or an array of strings".to_string();
+    if let Some(literal) = text.strip_prefix('\'') {
+        let end = literal
+            .find('\'')
+            .ok_or_else(|| "unterminated string".to_string())?;
+        return Ok((literal[..end].to_string(), &literal[end + 1..]));
+    }
+    let basic = text.strip_prefix('"').ok_or_else(expected)?;
+    let mut value = String::new();
+    let mut chars = basic.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &basic[i + 1..])),
+            '\\' => value.push(match chars.next() {
+                Some((_, '"')) => '"',
+                Some((_, '\\')) => '\\',
+                Some((_, 'n')) => '\n',
+                Some((_, 't')) => '\t',
+                Some((_, other)) => return Err(format!("unsupported escape `\\{}`", other)),
+                None => break,
+            }),
+            c => value.push(c),
+        }
+    }
+    Err("unterminated string".to_string())
+}